@@ -0,0 +1,8 @@
+// Generate the gRPC service (src/inject/grpc.rs) from proto/worldview.proto,
+// with a bundled protoc, so building needs no system install.
+fn main() {
+    if std::env::var_os("PROTOC").is_none() {
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path().unwrap());
+    }
+    tonic_build::compile_protos("proto/worldview.proto").unwrap();
+}
@@ -0,0 +1,36 @@
+// Push PLY files to a worldview serving gRPC, as a producer on another
+// machine would.  Files are named as for the filesystem injectors,
+// "<instance>.<artifact>.ply", and sent in order on one stream.
+//
+//     cargo run -- grpc 127.0.0.1:50051
+//     cargo run --example grpc_client -- http://127.0.0.1:50051 0.cloud.ply 1.cloud.ply
+
+use std::path::Path;
+use worldview::grpc::proto::{worldview_client::WorldviewClient, Frame};
+use worldview::Key;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let addr = args.next().ok_or("usage: grpc_client URL FILE...")?;
+
+    let mut frames = vec![];
+    for path in args {
+        let key = Key::from_path(Path::new(&path)).ok_or(format!("{}: not <instance>.<artifact>.ply", path))?;
+        frames.push(Frame {
+            artifact: key.artifact,
+            instance: key.instance.map(|instance| instance.to_string()).unwrap_or_default(),
+            ply: std::fs::read(&path)?,
+        });
+    }
+
+    let mut client = WorldviewClient::connect(addr)
+        .await?
+        .max_encoding_message_size(usize::MAX);
+    let reply = client
+        .push_artifact(tokio_stream::iter(frames))
+        .await?
+        .into_inner();
+    println!("{} shown, {} rejected", reply.accepted, reply.rejected);
+    Ok(())
+}
@@ -0,0 +1,93 @@
+// Drive worldview from a simulation loop, without touching the
+// filesystem: a wireframe cube spinning about the vertical axis.
+//
+//     cargo run --example rotating_cube
+
+use std::time::Duration;
+use worldview::{Key, Viewer, WorldviewConfig};
+
+const CORNERS: [[f32; 3]; 8] = [
+    [-1.0, -1.0, -1.0],
+    [1.0, -1.0, -1.0],
+    [1.0, 1.0, -1.0],
+    [-1.0, 1.0, -1.0],
+    [-1.0, -1.0, 1.0],
+    [1.0, -1.0, 1.0],
+    [1.0, 1.0, 1.0],
+    [-1.0, 1.0, 1.0],
+];
+
+const FACETS: [[u32; 3]; 12] = [
+    [0, 2, 1],
+    [0, 3, 2],
+    [4, 5, 6],
+    [4, 6, 7],
+    [0, 1, 5],
+    [0, 5, 4],
+    [3, 6, 2],
+    [3, 7, 6],
+    [0, 4, 7],
+    [0, 7, 3],
+    [1, 2, 6],
+    [1, 6, 5],
+];
+
+fn cube_ply(angle: f32) -> String {
+    let (sin, cos) = angle.sin_cos();
+    let mut ply = format!(
+        "ply\n\
+         format ascii 1.0\n\
+         element vertex {}\n\
+         property float x\n\
+         property float y\n\
+         property float z\n\
+         element face {}\n\
+         property list uchar int vertex_indices\n\
+         end_header\n",
+        CORNERS.len(),
+        FACETS.len()
+    );
+    for [x, y, z] in CORNERS {
+        ply += &format!("{} {} {}\n", cos * x + sin * z, y, -sin * x + cos * z);
+    }
+    for [a, b, c] in FACETS {
+        ply += &format!("3 {} {} {}\n", a, b, c);
+    }
+    ply
+}
+
+async fn spin(viewer: Viewer) {
+    let key = Key {
+        instance: None,
+        artifact: "cube".to_string(),
+    };
+
+    let mut angle = 0.0f32;
+    let mut interval = tokio::time::interval(Duration::from_millis(16));
+    while !viewer.exiting() {
+        interval.tick().await;
+        viewer.push_ply(key.clone(), cube_ply(angle).as_bytes());
+        angle += 0.02;
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::builder()
+        .filter_level(log::LevelFilter::Info)
+        .filter_module("wgpu_hal", log::LevelFilter::Error)
+        .format_timestamp(None)
+        .init();
+
+    // Watch a scratch directory so the default file injector stays idle.
+    let scratch = std::env::temp_dir().join("worldview-rotating-cube");
+    std::fs::create_dir_all(&scratch).unwrap();
+    let config = WorldviewConfig {
+        injector: Some(worldview::DependencyInjector::Notify {
+            path: Some(scratch),
+        }),
+        ..Default::default()
+    };
+
+    worldview::run_with(config, spin).await;
+}
@@ -1,13 +1,32 @@
 use crate::{
+    colormap::{Colormap, ColormapAxis},
+    model,
     pipeline::{Mesh, PointCloud, Wireframe},
-    WindowState,
+    element::header_for,
+    obj::Obj,
+    progress::Progress,
+    read_header,
+    stl::{self, Stl},
+    upload::Upload,
+    Element, WindowState,
 };
 
-use std::io::BufRead;
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, Cursor, Read},
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+};
 
+use itertools::Itertools;
 use ply_rs::ply;
 
 pub trait RenderArtifact {
+    // The parsed PLY payload, held on the CPU until it is uploaded.
+    type Stage: Send;
+
     fn update_count(&mut self, header: &ply::Header);
     fn create_pipeline_layout(
         device: &wgpu::Device,
@@ -17,26 +36,578 @@ pub trait RenderArtifact {
 
     fn create_pipeline(device: &wgpu::Device, playback: &WindowState) -> wgpu::RenderPipeline;
 
-    fn create_uniform_buffer(device: &wgpu::Device) -> wgpu::Buffer;
-    fn needs_resize(&self, header: &ply::Header) -> bool;
-    fn read_ply(&mut self, f: &mut impl BufRead, header: &ply::Header);
-    fn write_buffer(&self, queue: &wgpu::Queue);
+    // The color defaults to one per artifact type.
+    const COLOR: [f32; 4];
+    fn create_uniform_buffer(
+        device: &wgpu::Device,
+        color: Option<[f32; 4]>,
+        transform: [[f32; 4]; 4],
+    ) -> wgpu::Buffer;
+    // Whether the buffers are too small for the staged payload.
+    fn needs_resize(&self, header: &ply::Header, stage: &Self::Stage) -> bool;
+    // Bytes of GPU buffers held.
+    fn allocated(&self) -> u64;
+    fn read_ply(f: &mut impl BufRead, header: &ply::Header) -> io::Result<Self::Stage>;
+    fn stage(&mut self, stage: Self::Stage);
+    // Where the staged vertices lie, if there are any.
+    fn bounds(&self) -> Option<model::Bounds>;
+    fn write_buffer(&self, upload: &mut Upload);
     fn render<'rpass>(&'rpass self, render_pass: &mut wgpu::RenderPass<'rpass>);
 }
 
-
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct ArtifactUniform {
     color: [f32; 4],
+    // Places the artifact in the world, before the camera view.
+    transform: [[f32; 4]; 4],
+    // For points colored by height: the lowest and highest coordinate
+    // along the axis, the axis, and the colormap.  Written every frame.
+    height: [f32; 4],
 }
 
 impl ArtifactUniform {
-    pub fn new(color: [f32; 4]) -> Self {
-        Self { color }
+    pub fn new(color: [f32; 4], transform: [[f32; 4]; 4]) -> Self {
+        Self {
+            color,
+            transform,
+            height: [0.0; 4],
+        }
+    }
+
+    // Where the height range is in the buffer, to write it alone.
+    pub const HEIGHT_OFFSET: wgpu::BufferAddress = std::mem::offset_of!(ArtifactUniform, height) as wgpu::BufferAddress;
+
+    pub fn height(bounds: &model::Bounds, axis: ColormapAxis, colormap: Colormap) -> [f32; 4] {
+        let index = axis.index();
+        [bounds.min[index], bounds.max[index], index as f32, colormap.index()]
+    }
+}
+
+// How an artifact is drawn.  Every artifact type has a natural mode, but
+// artifacts with facets can also be shown with a sparser topology reusing
+// the same vertex buffer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RenderMode {
+    Points,
+    Wireframe,
+    Fill,
+    // Fill, sampling the texture given with --texture.
+    Textured,
+    // Points, faded by their own opacity.
+    Translucent,
+    // Points, each in its own color from the PLY file.
+    Colored,
+    // Points, colored by their height through the --colormap.
+    Height,
+    // The outline of a filled mesh, drawn over it (--edges).  Not a mode
+    // of its own, so never cycled to.
+    Edges,
+    // Fill, blended by the alpha of the color (--alpha).  Not a mode of
+    // its own either; it stands in for Fill when the mesh is see-through.
+    SeeThrough,
+}
+
+impl RenderMode {
+    pub fn create_pipeline(&self, device: &wgpu::Device, state: &WindowState) -> wgpu::RenderPipeline {
+        match self {
+            RenderMode::Points => PointCloud::create_pipeline(device, state),
+            RenderMode::Wireframe => match state.edge_color {
+                EdgeColor::Uniform => Wireframe::create_pipeline(device, state),
+                _ => Wireframe::create_scalar_pipeline(device, state),
+            },
+            RenderMode::Fill => Mesh::create_pipeline(device, state),
+            RenderMode::Textured => Mesh::create_textured_pipeline(device, state),
+            RenderMode::Translucent => PointCloud::create_translucent_pipeline(device, state),
+            RenderMode::Colored => PointCloud::create_colored_pipeline(device, state),
+            RenderMode::Height => PointCloud::create_height_pipeline(device, state),
+            RenderMode::Edges => Mesh::create_edges_pipeline(device, state),
+            RenderMode::SeeThrough => Mesh::create_see_through_pipeline(device, state),
+        }
+    }
+
+    // Whether meshes drawn this way are filled, and so can have their
+    // edges drawn over them.
+    pub fn is_filled(&self) -> bool {
+        matches!(self, RenderMode::Fill | RenderMode::Textured | RenderMode::SeeThrough)
+    }
+}
+
+// How wireframe edges are colored.  Anything but a uniform color is
+// computed per edge when the artifact is loaded.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum EdgeColor {
+    #[default]
+    Uniform,
+    Length,
+    Facet,
+}
+
+// Which artifact type a PLY header describes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, clap::ValueEnum)]
+pub enum ArtifactKind {
+    #[value(name = "pointcloud")]
+    PointCloud,
+    Wireframe,
+    Mesh,
+}
+
+impl ArtifactKind {
+    // Detect which artifact type we want to show, given the PLY header.
+    // When there is none, the error explains why, for the user.
+    pub fn detect(header: &ply::Header) -> Result<ArtifactKind, String> {
+        let has = |element: Element| header.elements.contains_key(&element.to_string());
+        if has(Element::Vertex) && has(Element::Facet) {
+            Ok(ArtifactKind::Wireframe)
+        } else if has(Element::Vertex) {
+            Ok(ArtifactKind::PointCloud)
+        } else {
+            Err(format!(
+                "no '{}' element among [{}]",
+                Element::Vertex,
+                header.elements.keys().join(", ")
+            ))
+        }
+    }
+
+    // Like detect(), but the file may ask for another interpretation, and
+    // the user may force one, as long as the header has the elements that
+    // interpretation needs.  The same vertices and facets may be shown as
+    // a mesh or as a wireframe, so, first to last:
+    //
+    //   1. --as, for all artifacts or by name;
+    //   2. a ".mesh", ".wireframe" or ".pointcloud" suffix on the artifact
+    //      name, e.g. "3.bunny.mesh.ply" (see of_name());
+    //   3. a "comment kind mesh" line in the PLY header (see of_comment());
+    //   4. the elements: vertices and facets are a wireframe, vertices
+    //      alone a point cloud.
+    //
+    // The sequencer folds 1 and 2 into `forced`.  A kind the header
+    // comment asks for, but the elements do not allow, is ignored.
+    pub fn resolve(
+        header: &ply::Header,
+        forced: Option<ArtifactKind>,
+    ) -> Result<ArtifactKind, String> {
+        let kind = match forced {
+            Some(kind) => kind,
+            None => {
+                return match ArtifactKind::of_comment(header).map(|kind| kind.fits(header)) {
+                    Some(Ok(kind)) => Ok(kind),
+                    Some(Err(reason)) => {
+                        log::warn!("Ignoring the artifact type in the header: {}", reason);
+                        ArtifactKind::detect(header)
+                    }
+                    None => ArtifactKind::detect(header),
+                }
+            }
+        };
+        kind.fits(header)
+    }
+
+    // This kind, if the header has the elements it needs.
+    fn fits(self, header: &ply::Header) -> Result<ArtifactKind, String> {
+        let needs: &[Element] = match self {
+            ArtifactKind::PointCloud => &[Element::Vertex],
+            ArtifactKind::Wireframe | ArtifactKind::Mesh => &[Element::Vertex, Element::Facet],
+        };
+        match needs
+            .iter()
+            .find(|element| !header.elements.contains_key(&element.to_string()))
+        {
+            Some(missing) => Err(format!("{:?} needs a '{}' element", self, missing)),
+            None => Ok(self),
+        }
+    }
+
+    // The kind a "comment kind KIND" header line asks for, if there is
+    // one, e.g. "comment kind mesh".
+    pub fn of_comment(header: &ply::Header) -> Option<ArtifactKind> {
+        header.comments.iter().find_map(|comment| match comment.split_whitespace().collect::<Vec<_>>()[..] {
+            ["kind", kind] => <ArtifactKind as clap::ValueEnum>::from_str(kind, true).ok(),
+            _ => None,
+        })
+    }
+
+    // The kind named by the last dot-separated part of an artifact name,
+    // if it is one, e.g. "bunny.mesh" or "scan.pointcloud".
+    pub fn of_name(artifact: &str) -> Option<ArtifactKind> {
+        let (_, suffix) = artifact.rsplit_once('.')?;
+        <ArtifactKind as clap::ValueEnum>::from_str(suffix, false).ok()
+    }
+}
+
+// The name used on the command line, e.g. "pointcloud".
+impl fmt::Display for ArtifactKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = clap::ValueEnum::to_possible_value(self).unwrap();
+        write!(f, "{}", value.get_name())
+    }
+}
+
+// A user override of the detected artifact type, written as
+// `kind[:artifact]`.  Without an artifact name it applies to every
+// artifact.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KindOverride {
+    pub kind: ArtifactKind,
+    pub artifact: Option<String>,
+}
+
+impl KindOverride {
+    // The forced kind for an artifact name, if any.  An override naming
+    // the artifact wins over one that applies to all of them.
+    pub fn find(overrides: &[KindOverride], artifact: &str) -> Option<ArtifactKind> {
+        overrides
+            .iter()
+            .find(|o| o.artifact.as_deref() == Some(artifact))
+            .or_else(|| overrides.iter().find(|o| o.artifact.is_none()))
+            .map(|o| o.kind)
+    }
+
+    // The kind to force on an artifact: by --as, else by a suffix on its
+    // name.  Steps 1 and 2 of ArtifactKind::resolve().
+    pub fn forced(overrides: &[KindOverride], artifact: &str) -> Option<ArtifactKind> {
+        KindOverride::find(overrides, artifact).or_else(|| ArtifactKind::of_name(artifact))
+    }
+}
+
+impl FromStr for KindOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, artifact) = match s.split_once(':') {
+            Some((kind, artifact)) => (kind, Some(artifact.to_string())),
+            None => (s, None),
+        };
+        Ok(KindOverride {
+            kind: <ArtifactKind as clap::ValueEnum>::from_str(kind, true)?,
+            artifact,
+        })
+    }
+}
+
+// A user given opacity for an artifact, written as `name=alpha`, from
+// 0 (invisible) to 1 (opaque).
+#[derive(Clone, Debug, PartialEq)]
+pub struct AlphaOverride {
+    pub artifact: String,
+    pub alpha: f32,
+}
+
+impl FromStr for AlphaOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (artifact, alpha) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected NAME=ALPHA, not '{}'", s))?;
+        let alpha: f32 = alpha.parse().map_err(|e| format!("bad alpha '{}': {}", alpha, e))?;
+        if !(0.0..=1.0).contains(&alpha) {
+            return Err(format!("alpha {} is not between 0 and 1", alpha));
+        }
+        Ok(AlphaOverride {
+            artifact: artifact.to_string(),
+            alpha,
+        })
+    }
+}
+
+// A user given color for an artifact, written as `name=r,g,b` or
+// `name=r,g,b,a`, each from 0 to 1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorOverride {
+    pub artifact: String,
+    pub color: [f32; 4],
+}
+
+impl FromStr for ColorOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (artifact, color) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected NAME=R,G,B, not '{}'", s))?;
+        let values = color
+            .split(',')
+            .map(|value| value.trim().parse::<f32>().map_err(|err| format!("{}: {}", value, err)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let color = match values[..] {
+            [r, g, b] => [r, g, b, 1.0],
+            [r, g, b, a] => [r, g, b, a],
+            _ => return Err("expected NAME=R,G,B or NAME=R,G,B,A".to_string()),
+        };
+        if !color.iter().all(|value| (0.0..=1.0).contains(value)) {
+            return Err(format!("{}: color values are between 0 and 1", artifact));
+        }
+        Ok(ColorOverride {
+            artifact: artifact.to_string(),
+            color,
+        })
+    }
+}
+
+// The file formats artifacts are read from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    Ply,
+    Obj,
+    Stl,
+}
+
+impl Format {
+    // By the file extension, if it is one we read.
+    pub fn of_path(path: &Path) -> Option<Format> {
+        match path.extension()?.to_str()? {
+            "ply" => Some(Format::Ply),
+            "obj" => Some(Format::Obj),
+            // CAD tools often write the extension in capitals.
+            "stl" | "STL" => Some(Format::Stl),
+            _ => None,
+        }
+    }
+
+    // By the bytes themselves, for files kept without their name
+    // (--history).  Every PLY file starts with "ply", and every STL file
+    // is either binary of the right length, or starts with "solid".
+    pub fn sniff(bytes: &[u8]) -> Format {
+        if bytes.starts_with(b"ply") {
+            Format::Ply
+        } else if stl::is_binary(bytes) || bytes.starts_with(b"solid") {
+            Format::Stl
+        } else {
+            Format::Obj
+        }
+    }
+}
+
+// A PLY file that has been parsed, but not yet uploaded to the GPU.
+// Parsing is the expensive half of an injection and needs no GPU access,
+// so it can run on any thread; the upload that follows is short and is
+// serialized by the sequencer.
+pub struct Staged {
+    pub header: ply::Header,
+    payload: Payload,
+    // The file as read, when it is to be kept for --history.
+    pub bytes: Option<Arc<[u8]>>,
+}
+
+enum Payload {
+    PointCloud(<PointCloud as RenderArtifact>::Stage),
+    Wireframe(<Wireframe as RenderArtifact>::Stage),
+    Mesh(<Mesh as RenderArtifact>::Stage),
+}
+
+impl Staged {
+    // Files are read by their extension; any but .obj and .stl are read as
+    // PLY.
+    pub fn from_path(path: &Path, forced: Option<ArtifactKind>) -> io::Result<Option<Staged>> {
+        let file = File::open(path)?;
+        let total = file.metadata()?.len();
+        let mut f = Progress::new(BufReader::new(file), path, total);
+        Staged::read(&mut f, Format::of_path(path).unwrap_or(Format::Ply), forced)
+    }
+
+    // Like from_path(), but keep the bytes read, for --history.
+    pub fn from_path_keeping(path: &Path, forced: Option<ArtifactKind>) -> io::Result<Option<Staged>> {
+        let file = File::open(path)?;
+        let total = file.metadata()?.len();
+        let mut bytes = Vec::with_capacity(total as usize);
+        Progress::new(BufReader::new(file), path, total).read_to_end(&mut bytes)?;
+        Staged::from_bytes(bytes.into(), Format::of_path(path).unwrap_or(Format::Ply), forced)
+    }
+
+    // Parse a whole file held in memory, and keep it.
+    pub fn from_bytes(bytes: Arc<[u8]>, format: Format, forced: Option<ArtifactKind>) -> io::Result<Option<Staged>> {
+        let staged = Staged::read(&mut Cursor::new(&bytes[..]), format, forced)?;
+        Ok(staged.map(|staged| Staged {
+            bytes: Some(bytes),
+            ..staged
+        }))
+    }
+
+    pub fn read(f: &mut impl BufRead, format: Format, forced: Option<ArtifactKind>) -> io::Result<Option<Staged>> {
+        match format {
+            Format::Ply => Staged::read_ply(f, forced),
+            Format::Obj => Staged::read_obj(f, forced),
+            Format::Stl => Staged::read_stl(f, forced),
+        }
+    }
+
+    // Parse the header and payload, as the forced artifact type if given.
+    // Returns None for a well-formed PLY that does not describe any
+    // artifact type we can show.
+    pub fn read_ply(
+        f: &mut impl BufRead,
+        forced: Option<ArtifactKind>,
+    ) -> io::Result<Option<Staged>> {
+        let header = read_header(f)?;
+
+        let payload = match ArtifactKind::resolve(&header, forced) {
+            Ok(ArtifactKind::PointCloud) => Payload::PointCloud(PointCloud::read_ply(f, &header)?),
+            Ok(ArtifactKind::Wireframe) => Payload::Wireframe(Wireframe::read_ply(f, &header)?),
+            Ok(ArtifactKind::Mesh) => Payload::Mesh(Mesh::read_ply(f, &header)?),
+            Err(reason) => {
+                if forced.is_some() {
+                    log::warn!("Cannot override artifact type: {}", reason);
+                }
+                return Ok(None);
+            }
+        };
+        Ok(Some(Staged::new(header, payload)))
+    }
+
+    // Like read_ply(), for a Wavefront OBJ file; see obj.rs.  The payload
+    // is the same as for a PLY file with the same elements.
+    pub fn read_obj(f: &mut impl BufRead, forced: Option<ArtifactKind>) -> io::Result<Option<Staged>> {
+        let obj = Obj::read(f)?;
+        let header = obj.header();
+
+        let payload = match ArtifactKind::resolve(&header, forced) {
+            Ok(ArtifactKind::PointCloud) => Payload::PointCloud((obj.vertices, None, None)),
+            Ok(ArtifactKind::Wireframe) => Payload::Wireframe((obj.vertices, obj.facets)),
+            Ok(ArtifactKind::Mesh) => Payload::Mesh((obj.vertices, obj.facets, obj.uvs, obj.normals)),
+            Err(reason) => {
+                if forced.is_some() {
+                    log::warn!("Cannot override artifact type: {}", reason);
+                }
+                return Ok(None);
+            }
+        };
+        Ok(Some(Staged::new(header, payload)))
+    }
+
+    // Like read_obj(), for an STL file; see stl.rs.  STL holds nothing but
+    // triangles, so it is a mesh unless forced otherwise.
+    pub fn read_stl(f: &mut impl BufRead, forced: Option<ArtifactKind>) -> io::Result<Option<Staged>> {
+        let stl = Stl::read(f)?;
+        let header = header_for(stl.vertices.len(), &["x", "y", "z"], stl.facets.len());
+
+        let payload = match ArtifactKind::resolve(&header, forced.or(Some(ArtifactKind::Mesh))) {
+            Ok(ArtifactKind::PointCloud) => Payload::PointCloud((stl.vertices, None, None)),
+            Ok(ArtifactKind::Wireframe) => Payload::Wireframe((stl.vertices, stl.facets)),
+            Ok(ArtifactKind::Mesh) => Payload::Mesh((stl.vertices, stl.facets, None, None)),
+            Err(reason) => {
+                if forced.is_some() {
+                    log::warn!("Cannot override artifact type: {}", reason);
+                }
+                return Ok(None);
+            }
+        };
+        Ok(Some(Staged::new(header, payload)))
+    }
+
+    fn new(mut header: ply::Header, payload: Payload) -> Staged {
+        // A point cloud needs only the vertices, and parsing stops right
+        // after them, so facets of a mesh shown as points (--as
+        // pointcloud) are never read.  Drop the unread elements from the
+        // header as well, so nothing downstream sizes buffers for them.
+        if let Payload::PointCloud(_) = payload {
+            let unread: Vec<String> = header
+                .elements
+                .keys()
+                .filter(|name| **name != Element::Vertex.to_string())
+                .cloned()
+                .collect();
+            for name in unread {
+                let element = header.elements.remove(&name).unwrap();
+                log::trace!("Skipped {} {} elements", element.count, name);
+            }
+        }
+
+        Staged {
+            header,
+            payload,
+            bytes: None,
+        }
+    }
+
+    // When the frame was captured, in seconds, if the producer stamped it
+    // with a "comment timestamp 12345.678" header line.
+    pub fn timestamp(&self) -> Option<f64> {
+        self.header.comments.iter().find_map(|comment| match comment.split_whitespace().collect::<Vec<_>>()[..] {
+            ["timestamp", seconds] => seconds.parse::<f64>().ok().filter(|seconds| seconds.is_finite()),
+            _ => None,
+        })
+    }
+
+    pub fn kind(&self) -> ArtifactKind {
+        match self.payload {
+            Payload::PointCloud(_) => ArtifactKind::PointCloud,
+            Payload::Wireframe(_) => ArtifactKind::Wireframe,
+            Payload::Mesh(_) => ArtifactKind::Mesh,
+        }
+    }
+
+    // Uniformly subsample a point cloud down to at most `max_points`
+    // vertices.  The header count follows, so the GPU buffers are sized
+    // for the reduced set.  Artifacts with facets are left alone, because
+    // the facets index into the full vertex list.
+    pub fn decimate(&mut self, max_points: usize) {
+        let (vertices, opacity, colors) = match &mut self.payload {
+            Payload::PointCloud(stage) => stage,
+            _ => return,
+        };
+        let count = vertices.len();
+        if count <= max_points {
+            return;
+        }
+
+        *vertices = (0..max_points)
+            .map(|i| vertices[i * count / max_points])
+            .collect();
+        if let Some(opacity) = opacity {
+            *opacity = (0..max_points)
+                .map(|i| opacity[i * count / max_points])
+                .collect();
+        }
+        if let Some(colors) = colors {
+            *colors = (0..max_points)
+                .map(|i| colors[i * count / max_points])
+                .collect();
+        }
+        if let Some(element) = self.header.elements.get_mut(&Element::Vertex.to_string()) {
+            element.count = vertices.len();
+        }
+        log::trace!("Decimated {} points to {}", count, vertices.len());
+    }
+
+    // Merge mesh and wireframe vertices within `epsilon` of each other
+    // into one, shared by all the facets that used them.  Off by default,
+    // because shading across the welded seams changes: normals from the
+    // file no longer fit, so they are dropped and computed again from the
+    // welded facets.  Textured meshes are left alone, because vertices on
+    // a texture seam share a position but not their texture coordinates.
+    // A welded wireframe draws each shared edge once.
+    pub fn weld(&mut self, epsilon: f32) {
+        let (vertices, facets) = match &mut self.payload {
+            Payload::Mesh((vertices, facets, None, normals)) => {
+                *normals = None;
+                (vertices, facets)
+            }
+            Payload::Wireframe((vertices, facets)) => (vertices, facets),
+            Payload::Mesh(_) => {
+                log::debug!("Not welding a mesh with texture coordinates");
+                return;
+            }
+            _ => return,
+        };
+        let count = vertices.len();
+        *vertices = model::weld(vertices, facets, epsilon);
+        if let Some(element) = self.header.elements.get_mut(&Element::Vertex.to_string()) {
+            element.count = vertices.len();
+        }
+        log::debug!(
+            "Welded {} vertices to {} ({:.1}x)",
+            count,
+            vertices.len(),
+            count as f32 / vertices.len().max(1) as f32
+        );
     }
 }
 
+// There are only ever a handful of artifacts, each living for many
+// frames, so the size of the largest variant does not matter.
+#[allow(clippy::large_enum_variant)]
 pub enum Artifact {
     PointCloud(PointCloud),
     Wireframe(Wireframe),
@@ -44,68 +615,342 @@ pub enum Artifact {
 }
 
 impl Artifact {
-    pub fn new(device: &wgpu::Device, header: &ply::Header) -> Option<Artifact> {
-        // Detect which artifact type we want to show, given the PLY header.
-        if let Some(wireframe) = Wireframe::new(&device, &header) {
-            return Some(Artifact::Wireframe(wireframe));
+    // Meshes with texture coordinates are drawn textured, if there is a
+    // texture to draw them with.
+    pub fn new(
+        device: &wgpu::Device,
+        staged: &Staged,
+        edge_color: EdgeColor,
+        textured: bool,
+    ) -> Option<Artifact> {
+        let header = &staged.header;
+        match &staged.payload {
+            Payload::PointCloud(_) => PointCloud::new(device, header).map(Artifact::PointCloud),
+            Payload::Wireframe((_, facets)) => {
+                Wireframe::new(device, header, facets, edge_color).map(Artifact::Wireframe)
+            }
+            Payload::Mesh((_, facets, _, _)) => {
+                Mesh::new(device, header, facets, edge_color, textured).map(Artifact::Mesh)
+            }
         }
+    }
 
-        if let Some(point_cloud) = PointCloud::new(&device, &header) {
-            return Some(Artifact::PointCloud(point_cloud));
+    // True if the staged payload cannot be loaded into this artifact's
+    // buffers, either because they are too small or of another type.
+    pub fn needs_resize(&self, staged: &Staged) -> bool {
+        let header = &staged.header;
+        match (self, &staged.payload) {
+            (Artifact::PointCloud(point_cloud), Payload::PointCloud(stage)) => {
+                point_cloud.needs_resize(header, stage)
+            }
+            (Artifact::Wireframe(wireframe), Payload::Wireframe(stage)) => {
+                wireframe.needs_resize(header, stage)
+            }
+            (Artifact::Mesh(mesh), Payload::Mesh(stage)) => mesh.needs_resize(header, stage),
+            _ => true,
         }
+    }
 
-        None
+    pub fn allocated(&self) -> u64 {
+        match self {
+            Artifact::PointCloud(point_cloud) => point_cloud.allocated(),
+            Artifact::Wireframe(wireframe) => wireframe.allocated(),
+            Artifact::Mesh(mesh) => mesh.allocated(),
+        }
     }
 
-    pub fn needs_resize(&self, header: &ply::Header) -> bool {
+    // Take over the staged payload and counts, ready for write_buffer().
+    pub fn stage(&mut self, staged: Staged) {
+        let header = &staged.header;
+        match (self, staged.payload) {
+            (Artifact::PointCloud(point_cloud), Payload::PointCloud(stage)) => {
+                point_cloud.stage(stage);
+                point_cloud.update_count(header);
+            }
+            (Artifact::Wireframe(wireframe), Payload::Wireframe(stage)) => {
+                wireframe.stage(stage);
+                wireframe.update_count(header);
+            }
+            (Artifact::Mesh(mesh), Payload::Mesh(stage)) => {
+                mesh.stage(stage);
+                mesh.update_count(header);
+            }
+            _ => log::error!("Staged payload does not match the artifact type"),
+        }
+    }
+
+    pub fn bounds(&self) -> Option<model::Bounds> {
         match self {
-            Artifact::PointCloud(point_cloud) => point_cloud.needs_resize(&header),
-            Artifact::Mesh(mesh) => mesh.needs_resize(&header),
-            Artifact::Wireframe(wireframe) => wireframe.needs_resize(&header),
+            Artifact::PointCloud(point_cloud) => point_cloud.bounds(),
+            Artifact::Wireframe(wireframe) => wireframe.bounds(),
+            Artifact::Mesh(mesh) => mesh.bounds(),
         }
     }
 
-    pub fn read_ply(&mut self, f: &mut impl BufRead, header: &ply::Header) {
+    pub fn write_buffer(&self, upload: &mut Upload) {
         match self {
-            Artifact::PointCloud(point_cloud) => point_cloud.read_ply(f, &header),
-            Artifact::Wireframe(wireframe) => wireframe.read_ply(f, &header),
-            Artifact::Mesh(mesh) => mesh.read_ply(f, &header),
+            Artifact::PointCloud(point_cloud) => point_cloud.write_buffer(upload),
+            Artifact::Wireframe(wireframe) => wireframe.write_buffer(upload),
+            Artifact::Mesh(mesh) => mesh.write_buffer(upload),
         }
     }
 
-    pub fn write_buffer(&self, queue: &wgpu::Queue) {
+    // What the colormap shows for this artifact, the bounds it maps from
+    // blue to red, and the colormap, if the artifact's edges are
+    // colormapped at all.  Points colored by height are up to the window.
+    pub fn colormap(&self) -> Option<(&'static str, f32, f32, Colormap)> {
+        let range = match self {
+            Artifact::PointCloud(_) => None,
+            Artifact::Wireframe(wireframe) => wireframe.scalar_range,
+            Artifact::Mesh(mesh) => mesh.scalar_range,
+        };
+        range.map(|(min, max)| ("edge length", min, max, Colormap::Jet))
+    }
+
+    // Which artifact type this is, as resolved when it was staged.
+    pub fn kind(&self) -> ArtifactKind {
         match self {
-            Artifact::PointCloud(point_cloud) => point_cloud.write_buffer(&queue),
-            Artifact::Wireframe(wireframe) => wireframe.write_buffer(&queue),
-            Artifact::Mesh(mesh) => mesh.write_buffer(&queue),
+            Artifact::PointCloud(_) => ArtifactKind::PointCloud,
+            Artifact::Wireframe(_) => ArtifactKind::Wireframe,
+            Artifact::Mesh(_) => ArtifactKind::Mesh,
         }
     }
 
-    pub fn update_count(&mut self, header: &ply::Header) {
+    // The render modes this artifact supports; the first is its natural
+    // mode, used unless the user picks another.
+    pub fn render_modes(&self) -> &'static [RenderMode] {
         match self {
-            Artifact::PointCloud(point_cloud) => point_cloud.update_count(header),
-            Artifact::Wireframe(wireframe) => wireframe.update_count(header),
-            Artifact::Mesh(mesh) => mesh.update_count(header),
+            Artifact::PointCloud(point_cloud) if point_cloud.is_colored() && point_cloud.is_translucent() => {
+                &[RenderMode::Colored, RenderMode::Translucent, RenderMode::Points, RenderMode::Height]
+            }
+            Artifact::PointCloud(point_cloud) if point_cloud.is_colored() => {
+                &[RenderMode::Colored, RenderMode::Points, RenderMode::Height]
+            }
+            Artifact::PointCloud(point_cloud) if point_cloud.is_translucent() => {
+                &[RenderMode::Translucent, RenderMode::Points, RenderMode::Height]
+            }
+            Artifact::PointCloud(_) => &[RenderMode::Points, RenderMode::Height],
+            Artifact::Wireframe(_) => &[RenderMode::Wireframe, RenderMode::Points],
+            Artifact::Mesh(mesh) if mesh.is_textured() => &[
+                RenderMode::Textured,
+                RenderMode::Fill,
+                RenderMode::Wireframe,
+                RenderMode::Points,
+            ],
+            Artifact::Mesh(_) => &[RenderMode::Fill, RenderMode::Wireframe, RenderMode::Points],
+        }
+    }
+
+    pub fn natural_mode(&self) -> RenderMode {
+        self.render_modes()[0]
+    }
+
+    pub fn render<'rpass>(&'rpass self, mode: RenderMode, render_pass: &mut wgpu::RenderPass<'rpass>) {
+        match (self, mode) {
+            (Artifact::PointCloud(point_cloud), RenderMode::Translucent) => {
+                point_cloud.render_translucent(render_pass)
+            }
+            (Artifact::PointCloud(point_cloud), RenderMode::Colored) => point_cloud.render_colored(render_pass),
+            (Artifact::PointCloud(point_cloud), _) => point_cloud.render(render_pass),
+            (Artifact::Wireframe(wireframe), RenderMode::Points) => {
+                wireframe.render_points(render_pass)
+            }
+            (Artifact::Wireframe(wireframe), _) => wireframe.render(render_pass),
+            (Artifact::Mesh(mesh), RenderMode::Points | RenderMode::Translucent | RenderMode::Colored | RenderMode::Height) => {
+                mesh.render_points(render_pass)
+            }
+            (Artifact::Mesh(mesh), RenderMode::Wireframe) => mesh.render_edges(render_pass),
+            (Artifact::Mesh(mesh), RenderMode::Fill | RenderMode::SeeThrough) => mesh.render(render_pass),
+            (Artifact::Mesh(mesh), RenderMode::Textured) => mesh.render_textured(render_pass),
+            (Artifact::Mesh(mesh), RenderMode::Edges) => mesh.render_outline(render_pass),
         }
     }
 
-    pub fn create_uniform_buffer(&self, device: &wgpu::Device) -> wgpu::Buffer {
+    // The color of the artifact type, when none is given.
+    pub fn color(&self) -> [f32; 4] {
         match self {
-            Artifact::PointCloud(_) => PointCloud::create_uniform_buffer(&device),
-            Artifact::Wireframe(_) => Wireframe::create_uniform_buffer(&device),
-            Artifact::Mesh(_) => Mesh::create_uniform_buffer(&device),
+            Artifact::PointCloud(_) => PointCloud::COLOR,
+            Artifact::Wireframe(_) => Wireframe::COLOR,
+            Artifact::Mesh(_) => Mesh::COLOR,
         }
     }
 
-    pub fn create_pipeline(
+    pub fn create_uniform_buffer(
         &self,
         device: &wgpu::Device,
-        state: &WindowState,
-    ) -> wgpu::RenderPipeline {
+        color: Option<[f32; 4]>,
+        transform: [[f32; 4]; 4],
+    ) -> wgpu::Buffer {
         match self {
-            Artifact::PointCloud(_) => PointCloud::create_pipeline(&device, &state),
-            Artifact::Wireframe(_) => Wireframe::create_pipeline(&device, &state),
-            Artifact::Mesh(_) => Mesh::create_pipeline(&device, &state),
+            Artifact::PointCloud(_) => PointCloud::create_uniform_buffer(device, color, transform),
+            Artifact::Wireframe(_) => Wireframe::create_uniform_buffer(device, color, transform),
+            Artifact::Mesh(_) => Mesh::create_uniform_buffer(device, color, transform),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POSITIONS: [[f32; 3]; 4] = [[0.0, 0.0, 0.0], [1.5, -2.25, 3.0], [-1e6, 0.125, 7.0], [0.1, 0.2, 0.3]];
+    const FACES: [&[i32]; 2] = [&[0, 1, 2], &[0, 1, 2, 3]];
+
+    // The same wireframe in each PLY format, with numbers that read back
+    // wrong if bytes are swapped.
+    fn ply(format: &str) -> Vec<u8> {
+        let mut bytes = format!(
+            "ply\n\
+             format {} 1.0\n\
+             element vertex {}\n\
+             property float x\n\
+             property float y\n\
+             property float z\n\
+             element face {}\n\
+             property list uchar int vertex_indices\n\
+             end_header\n",
+            format,
+            POSITIONS.len(),
+            FACES.len()
+        )
+        .into_bytes();
+        let big = format == "binary_big_endian";
+        let float = |v: &f32| if big { v.to_be_bytes() } else { v.to_le_bytes() };
+        let int = |v: &i32| if big { v.to_be_bytes() } else { v.to_le_bytes() };
+        match format {
+            "ascii" => {
+                for [x, y, z] in POSITIONS {
+                    bytes.extend(format!("{} {} {}\n", x, y, z).bytes());
+                }
+                for face in FACES {
+                    bytes.extend(format!("{} {}\n", face.len(), face.iter().join(" ")).bytes());
+                }
+            }
+            _ => {
+                bytes.extend(POSITIONS.iter().flatten().flat_map(float));
+                for face in FACES {
+                    bytes.push(face.len() as u8);
+                    bytes.extend(face.iter().flat_map(int));
+                }
+            }
+        }
+        bytes
+    }
+
+    fn header(vertices: usize, facets: usize, comment: Option<&str>) -> ply::Header {
+        let mut header = header_for(vertices, &["x", "y", "z"], facets);
+        header.comments.extend(comment.map(str::to_string));
+        header
+    }
+
+    #[test]
+    fn detect_by_elements() {
+        assert_eq!(ArtifactKind::resolve(&header(3, 0, None), None), Ok(ArtifactKind::PointCloud));
+        assert_eq!(ArtifactKind::resolve(&header(3, 1, None), None), Ok(ArtifactKind::Wireframe));
+        assert!(ArtifactKind::resolve(&header(0, 0, None), None).is_err());
+    }
+
+    #[test]
+    fn override_beats_detection() {
+        let header = header(3, 1, Some("kind wireframe"));
+        assert_eq!(ArtifactKind::resolve(&header, Some(ArtifactKind::Mesh)), Ok(ArtifactKind::Mesh));
+        assert_eq!(ArtifactKind::resolve(&header, Some(ArtifactKind::PointCloud)), Ok(ArtifactKind::PointCloud));
+    }
+
+    #[test]
+    fn header_comment_beats_elements() {
+        assert_eq!(ArtifactKind::resolve(&header(3, 1, Some("kind mesh")), None), Ok(ArtifactKind::Mesh));
+        // Unless the elements do not allow it.
+        assert_eq!(ArtifactKind::resolve(&header(3, 0, Some("kind mesh")), None), Ok(ArtifactKind::PointCloud));
+    }
+
+    #[test]
+    fn incompatible_override_is_rejected() {
+        let header = header(3, 0, None);
+        assert!(ArtifactKind::resolve(&header, Some(ArtifactKind::Mesh)).is_err());
+        assert!(ArtifactKind::resolve(&header, Some(ArtifactKind::Wireframe)).is_err());
+        // And the file is not shown at all, rather than as a point cloud.
+        let ply = b"ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nend_header\n0\n";
+        assert!(Staged::read_ply(&mut Cursor::new(ply), Some(ArtifactKind::Mesh)).unwrap().is_none());
+    }
+
+    #[test]
+    fn named_override_beats_general() {
+        let overrides: Vec<KindOverride> = ["wireframe", "mesh:bunny"].iter().map(|s| s.parse().unwrap()).collect();
+        assert_eq!(KindOverride::find(&overrides, "bunny"), Some(ArtifactKind::Mesh));
+        assert_eq!(KindOverride::find(&overrides, "cage"), Some(ArtifactKind::Wireframe));
+        assert_eq!(KindOverride::find(&[], "cage"), None);
+        assert_eq!(ArtifactKind::of_name("bunny.mesh"), Some(ArtifactKind::Mesh));
+        assert_eq!(ArtifactKind::of_name("scan.2024"), None);
+    }
+
+    // Each step of ArtifactKind::resolve() beats the ones after it.
+    #[test]
+    fn override_precedence() {
+        let resolve = |overrides: &[&str], artifact: &str, header: &ply::Header| {
+            let overrides: Vec<KindOverride> = overrides.iter().map(|s| s.parse().unwrap()).collect();
+            ArtifactKind::resolve(header, KindOverride::forced(&overrides, artifact))
+        };
+        let commented = header(3, 1, Some("kind mesh"));
+        let plain = header(3, 1, None);
+        // 1. --as, by name before all artifacts.
+        assert_eq!(resolve(&["wireframe", "pointcloud:bunny.mesh"], "bunny.mesh", &commented), Ok(ArtifactKind::PointCloud));
+        assert_eq!(resolve(&["wireframe"], "bunny.mesh", &commented), Ok(ArtifactKind::Wireframe));
+        // 2. The suffix on the name.
+        assert_eq!(resolve(&[], "bunny.pointcloud", &commented), Ok(ArtifactKind::PointCloud));
+        // 3. The header comment.
+        assert_eq!(resolve(&[], "bunny", &commented), Ok(ArtifactKind::Mesh));
+        // 4. The elements.
+        assert_eq!(resolve(&[], "bunny", &plain), Ok(ArtifactKind::Wireframe));
+        // Overrides for other artifacts do not apply.
+        assert_eq!(resolve(&["pointcloud:cage"], "bunny", &plain), Ok(ArtifactKind::Wireframe));
+    }
+
+    #[test]
+    fn ply_formats_read_alike() {
+        for format in ["ascii", "binary_little_endian", "binary_big_endian"] {
+            let staged = Staged::read_ply(&mut Cursor::new(ply(format)), None).unwrap().unwrap();
+            let Payload::Wireframe((vertices, facets)) = staged.payload else {
+                panic!("{}: not read as a wireframe", format);
+            };
+            let positions: Vec<[f32; 3]> = vertices.iter().map(|v| v.position).collect();
+            assert_eq!(positions, POSITIONS, "{}", format);
+            let indices: Vec<&[i32]> = facets.iter().map(|f| f.vertex_indices.as_slice()).collect();
+            assert_eq!(indices, FACES, "{}", format);
+        }
+    }
+
+    // A body cut anywhere short, as a file caught mid-write, is an error
+    // rather than a panic or a partial artifact.
+    #[test]
+    fn truncated_body_is_an_error() {
+        for format in ["ascii", "binary_little_endian", "binary_big_endian"] {
+            let bytes = ply(format);
+            let body = bytes.windows(11).position(|w| w == b"end_header\n").unwrap() + 11;
+            for end in body..bytes.len() - 1 {
+                let read = Staged::read_ply(&mut Cursor::new(&bytes[..end]), None);
+                assert!(read.is_err(), "{} cut at {} of {}", format, end, bytes.len());
+            }
+        }
+    }
+
+    // Clouds over --max-points are cut down to exactly the cap, header
+    // included; smaller ones are left alone.
+    #[test]
+    fn decimate_to_the_cap() {
+        let points: String = (0..10).map(|i| format!("{} 0 0\n", i)).collect();
+        let cloud = format!(
+            "ply\nformat ascii 1.0\nelement vertex 10\nproperty float x\nproperty float y\nproperty float z\nend_header\n{}",
+            points
+        );
+        for (max_points, count) in [(1, 1), (4, 4), (9, 9), (10, 10), (20, 10)] {
+            let mut staged = Staged::read_ply(&mut Cursor::new(&cloud), None).unwrap().unwrap();
+            staged.decimate(max_points);
+            let Payload::PointCloud((vertices, _, _)) = &staged.payload else {
+                panic!("not read as a point cloud");
+            };
+            assert_eq!(vertices.len(), count, "--max-points {}", max_points);
+            assert_eq!(Element::Vertex.find(&staged.header).unwrap().count, count);
         }
     }
 }
@@ -1,5 +1,6 @@
-use cgmath::{InnerSpace, Matrix4, Point3, Rad, Vector3};
-use std::time::Instant;
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Rad, Vector3};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path, time::Instant};
 use winit::{dpi, event::MouseScrollDelta};
 
 // These calculations are mostly copied straight from this lifesaving
@@ -13,13 +14,63 @@ use winit::{dpi, event::MouseScrollDelta};
 use std::f32::consts::FRAC_PI_2;
 const SAFE_FRAC_PI_2: f32 = FRAC_PI_2 - 0.0001;
 
+// Which world axis points up in the data.  Graphics conventionally
+// uses Y-up, while robotics data is usually Z-up.  The camera always
+// works in Y-up, so Z-up worlds are rotated into place before viewing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum UpAxis {
+    #[default]
+    Y,
+    Z,
+}
+
+impl UpAxis {
+    // Rotation from world coordinates into the camera's Y-up frame.
+    pub fn world_matrix(&self) -> Matrix4<f32> {
+        match self {
+            UpAxis::Y => Matrix4::from_scale(1.0),
+            UpAxis::Z => Matrix4::from_angle_x(cgmath::Deg(-90.0)),
+        }
+    }
+}
+
+// Preset views, like the numpad views of a CAD tool.  They are named for
+// the world as --up orients it; the camera always works in its own Y-up
+// frame, into which a Z-up world is already rotated, so the same angles
+// serve both conventions.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum View {
+    // Looking along -Z (Y-up) or +Y (Z-up), with up on screen.
+    Front,
+    // Looking along -X, from the right.
+    Side,
+    // Looking straight down.
+    Top,
+    // Looking down the diagonal from the +X, +up, front corner.
+    Isometric,
+}
+
+impl View {
+    // Yaw and pitch, in degrees.
+    fn angles(&self) -> (f32, f32) {
+        match self {
+            View::Front => (-90.0, 0.0),
+            View::Side => (180.0, 0.0),
+            View::Top => (-90.0, -SAFE_FRAC_PI_2.to_degrees()),
+            // The cube diagonal is atan(1/sqrt(2)) = 35.264° below level.
+            View::Isometric => (-135.0, -35.264),
+        }
+    }
+}
+
 // Camera is the CPU side camera model that plays nice with the camera
 // controller.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Camera {
     position: Point3<f32>,
     yaw: Rad<f32>,
     pitch: Rad<f32>,
+    up: UpAxis,
 }
 
 // CameraUniform is the GPU side camera model + projection matrix used by 
@@ -33,52 +84,326 @@ pub struct CameraUniform {
 
 impl Default for Camera {
     fn default() -> Camera {
+        Camera::new(UpAxis::default())
+    }
+}
+
+impl Camera {
+    pub fn new(up: UpAxis) -> Camera {
         // Choose some nominally useful initial pose.
         Camera {
             position: (0.0, 5.0, 10.0).into(),
             yaw: cgmath::Deg(-90.0).into(),
             pitch: cgmath::Deg(-30.0).into(),
+            up,
         }
     }
-}
 
-impl Camera {
-    pub fn calc_matrix(&self) -> Matrix4<f32> {
+    pub fn up(&self) -> UpAxis {
+        self.up
+    }
+
+    // The direction the camera looks in.
+    fn forward(&self) -> Vector3<f32> {
         let (sin_pitch, cos_pitch) = self.pitch.0.sin_cos();
         let (sin_yaw, cos_yaw) = self.yaw.0.sin_cos();
+        Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw).normalize()
+    }
+
+    pub fn calc_matrix(&self) -> Matrix4<f32> {
+        Matrix4::look_to_rh(self.position, self.forward(), Vector3::unit_y())
+            * self.up.world_matrix()
+    }
+
+    // Turn to a preset view.  This camera flies rather than orbits, so
+    // the target is taken to be the point straight ahead, as far away as
+    // the world origin; that is the origin itself when it is centered.
+    // The camera keeps its distance from the target.
+    pub fn snap(&mut self, view: View) {
+        let (yaw, pitch) = view.angles();
+        self.turn_about_target(cgmath::Deg(yaw).into(), cgmath::Deg(pitch).into());
+    }
+
+    // Circle the same target, turning `degrees` about the vertical, like
+    // a turntable.
+    pub fn orbit(&mut self, degrees: f32) {
+        let yaw = self.yaw + Rad::from(cgmath::Deg(degrees));
+        self.turn_about_target(yaw, self.pitch);
+    }
+
+    // Back away along the current view direction until a sphere around
+    // `center` (in world coordinates) fits the vertical field of view and
+    // the horizontal one at `aspect`.  Returns the distance to the
+    // center, which then is the target.
+    pub fn frame(&mut self, center: Point3<f32>, radius: f32, fovy: Rad<f32>, aspect: f32) -> f32 {
+        use cgmath::Transform;
+        let center = self.up.world_matrix().transform_point(center);
+        let fovx = Rad(2.0 * ((fovy / 2.0).0.tan() * aspect).atan());
+        let fov = if fovx < fovy { fovx } else { fovy };
+        let distance = radius.max(f32::EPSILON) / (fov / 2.0).0.sin();
+        self.position = center - self.forward() * distance;
+        distance
+    }
+
+    // How far the target is: the world origin, as for snap().
+    pub fn target_distance(&self) -> f32 {
+        self.position.to_vec().magnitude()
+    }
+
+    // Slide sideways and up or down, by `right` and `up` world units,
+    // without turning.  The target moves along with the camera.
+    pub fn pan(&mut self, right: f32, up: f32) {
+        let forward = self.forward();
+        let right_axis = forward.cross(Vector3::unit_y()).normalize();
+        let up_axis = right_axis.cross(forward);
+        self.position += right_axis * right + up_axis * up;
+    }
 
-        Matrix4::look_to_rh(
-            self.position,
-            Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw).normalize(),
-            Vector3::unit_y(),
-        )
+    fn turn_about_target(&mut self, yaw: Rad<f32>, pitch: Rad<f32>) {
+        let distance = self.position.to_vec().magnitude();
+        let target = self.position + self.forward() * distance;
+        self.yaw = yaw;
+        self.pitch = pitch;
+        self.position = target - self.forward() * distance;
     }
 }
 
+// Perspective, or orthographic for CAD style views in which parallel
+// lines stay parallel and sizes do not shrink with distance.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectionKind {
+    #[default]
+    Perspective,
+    Orthographic,
+}
+
 pub struct Projection {
     aspect: f32,
     fovy: Rad<f32>,
     znear: f32,
     zfar: f32,
+    kind: ProjectionKind,
+    // Height of the orthographic view, in world units.  Moving the camera
+    // closer changes nothing in an orthographic view, so the scroll wheel
+    // zooms by changing this instead.
+    height: f32,
 }
 
+// The vertical field of view is kept within a range where perspective
+// still looks sane.
+pub const DEFAULT_FOVY: f32 = 45.0;
+const MIN_FOVY: f32 = 10.0;
+const MAX_FOVY: f32 = 120.0;
+
+// Anything nearer than the near plane or beyond the far plane is clipped.
+// Filled meshes are depth tested, and the depth buffer spreads its
+// precision over far / near, mostly close up, so keep the ratio modest.
+//
+// Depth stays linear in clip space, not logarithmic.  With a 32-bit float
+// buffer (DEPTH_FORMAT) and these defaults, 1000:1, two surfaces resolve
+// down to about 1e-4 apart at distance 10 and 1e-2 at the far plane,
+// which is finer than the grid and edges need anyway.  Logarithmic
+// depth would only pay off at ratios of 1e6 and beyond, and it costs:
+// written per fragment (frag_depth) it turns off early depth rejection in
+// every filled pipeline; written per vertex it bends across large
+// triangles, so fills and their edges disagree and z-fight.  Either way
+// every geometry shader would change, and the depth bias would no longer
+// mean the same thing.  Scenes that outgrow the defaults set --near and
+// --far, or let include() widen them; should that stop being enough,
+// reversed depth (far at 0, compare Greater) is the cheaper next step,
+// since it only touches the projection matrix and the depth states.
+pub const DEFAULT_ZNEAR: f32 = 0.1;
+pub const DEFAULT_ZFAR: f32 = 100.0;
+
+// The orthographic view never closes in below this height.
+const MIN_HEIGHT: f32 = 1e-3;
+
 impl Projection {
     pub fn default(size: dpi::PhysicalSize<u32>) -> Self {
+        Self::new(size, DEFAULT_FOVY, DEFAULT_ZNEAR, DEFAULT_ZFAR)
+    }
+
+    pub fn new(size: dpi::PhysicalSize<u32>, fovy_degrees: f32, znear: f32, zfar: f32) -> Self {
         Self {
             aspect: size.width as f32 / size.height as f32,
-            fovy: cgmath::Deg(45.0).into(),
-            znear: 0.1,
-            zfar: 100.0,
+            fovy: cgmath::Deg(fovy_degrees.clamp(MIN_FOVY, MAX_FOVY)).into(),
+            znear,
+            zfar,
+            kind: ProjectionKind::Perspective,
+            height: 1.0,
         }
     }
 
+    pub fn aspect(&self) -> f32 {
+        self.aspect
+    }
+
+    // Widen the clip planes, if need be, so nothing between `nearest` and
+    // `farthest` from the camera is clipped.  They never narrow, because
+    // they were chosen by the user.
+    pub fn include(&mut self, nearest: f32, farthest: f32) {
+        if nearest < self.znear {
+            self.znear = (nearest / 2.0).max(f32::EPSILON);
+            log::info!("Near clip plane moved to {}", self.znear);
+        }
+        if farthest > self.zfar {
+            self.zfar = farthest * 2.0;
+            log::info!("Far clip plane moved to {}", self.zfar);
+        }
+    }
+
+    // Vertical field of view, in degrees.
+    pub fn fovy(&self) -> f32 {
+        cgmath::Deg::from(self.fovy).0
+    }
+
+    pub fn set_fovy(&mut self, degrees: f32) {
+        self.fovy = cgmath::Deg(degrees.clamp(MIN_FOVY, MAX_FOVY)).into();
+    }
+
     pub fn resize(&mut self, width: u32, height: u32) {
         self.aspect = width as f32 / height as f32;
     }
 
-    pub fn calc_matrix(&self) -> Matrix4<f32> {
+    pub fn kind(&self) -> ProjectionKind {
+        self.kind
+    }
+
+    // Switch between perspective and orthographic.  The orthographic view
+    // starts out as tall as the perspective one is at `distance`, so the
+    // target keeps its size on screen.
+    pub fn toggle_kind(&mut self, distance: f32) -> ProjectionKind {
+        self.kind = match self.kind {
+            ProjectionKind::Perspective => {
+                self.fit(distance);
+                ProjectionKind::Orthographic
+            }
+            ProjectionKind::Orthographic => ProjectionKind::Perspective,
+        };
+        self.kind
+    }
+
+    // Size the orthographic view to the perspective one at `distance`,
+    // measured by projecting a point there, so the two agree on screen
+    // whatever the perspective matrix does.
+    pub fn fit(&mut self, distance: f32) {
+        let point = self.perspective() * cgmath::Vector4::new(0.0, 1.0, -distance.max(self.znear), 1.0);
+        self.height = (2.0 * point.w / point.y).max(MIN_HEIGHT);
+    }
+
+    fn perspective(&self) -> Matrix4<f32> {
         OPENGL_TO_WGPU_MATRIX * cgmath::perspective(self.fovy, self.aspect, self.znear, self.zfar)
     }
+
+    // How much of the world, in world units, the view spans vertically
+    // at `distance` from the camera.
+    pub fn view_height(&self, distance: f32) -> f32 {
+        match self.kind {
+            ProjectionKind::Perspective => 2.0 * distance * (self.fovy / 2.0).0.tan(),
+            ProjectionKind::Orthographic => self.height,
+        }
+    }
+
+    // Close in on the orthographic view by `amount` world units, as the
+    // scroll wheel would move a perspective camera.  The step is relative
+    // to the height, so zooming feels the same at any scale.
+    pub fn zoom(&mut self, amount: f32) {
+        self.height = (self.height * (-amount / self.height).exp()).max(MIN_HEIGHT);
+    }
+
+    pub fn calc_matrix(&self) -> Matrix4<f32> {
+        match self.kind {
+            ProjectionKind::Perspective => self.perspective(),
+            // Built directly in wgpu's clip space, depth 0 at the near plane
+            // to 1 at the far plane, with w left at 1.
+            ProjectionKind::Orthographic => {
+                let depth = self.zfar - self.znear;
+                #[rustfmt::skip]
+                let matrix = Matrix4::new(
+                    2.0 / (self.height * self.aspect), 0.0, 0.0, 0.0,
+                    0.0, 2.0 / self.height, 0.0, 0.0,
+                    0.0, 0.0, -1.0 / depth, 0.0,
+                    0.0, 0.0, -self.znear / depth, 1.0,
+                );
+                matrix
+            }
+        }
+    }
+}
+
+// A view saved to a JSON file (the V key, --camera), to come back to in
+// another run, e.g. for comparison screenshots:
+//
+//     {
+//       "eye": [0.0, 5.0, 10.0],
+//       "yaw": -90.0,
+//       "pitch": -30.0,
+//       "fovy": 45.0,
+//       "projection": "orthographic",
+//       "height": 8.3
+//     }
+//
+// Angles are in degrees.  The eye is in the camera's Y-up frame, into
+// which a Z-up world is already rotated, so a view saved with --up z is
+// only the same view with --up z.  The target is the point straight ahead,
+// as for snap().  The height of the orthographic view is optional; without
+// it, the view is fit to the target.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SavedView {
+    eye: [f32; 3],
+    yaw: f32,
+    pitch: f32,
+    fovy: f32,
+    #[serde(default)]
+    projection: ProjectionKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    height: Option<f32>,
+}
+
+impl SavedView {
+    pub fn of(camera: &Camera, projection: &Projection) -> SavedView {
+        SavedView {
+            eye: camera.position.into(),
+            yaw: cgmath::Deg::from(camera.yaw).0,
+            pitch: cgmath::Deg::from(camera.pitch).0,
+            fovy: projection.fovy(),
+            projection: projection.kind,
+            height: (projection.kind == ProjectionKind::Orthographic).then_some(projection.height),
+        }
+    }
+
+    // A file that does not parse, or holds numbers no view can have, is
+    // an error, and nothing is applied.
+    pub fn from_path(path: &Path) -> Result<SavedView, String> {
+        let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let view: SavedView = serde_json::from_str(&text).map_err(|err| err.to_string())?;
+        let numbers = view.eye.iter().chain([&view.yaw, &view.pitch, &view.fovy]).chain(&view.height);
+        if !numbers.into_iter().all(|number| number.is_finite()) {
+            return Err("numbers must be finite".to_string());
+        }
+        Ok(view)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        fs::write(path, text + "\n").map_err(|err| err.to_string())
+    }
+
+    // The field of view and pitch are kept within their usual limits.
+    pub fn apply(&self, camera: &mut Camera, projection: &mut Projection) {
+        camera.position = self.eye.into();
+        camera.yaw = cgmath::Deg(self.yaw).into();
+        let max_pitch = SAFE_FRAC_PI_2.to_degrees();
+        camera.pitch = cgmath::Deg(self.pitch.clamp(-max_pitch, max_pitch)).into();
+        projection.set_fovy(self.fovy);
+        projection.kind = self.projection;
+        match self.height {
+            Some(height) => projection.height = height.max(MIN_HEIGHT),
+            None => projection.fit(camera.target_distance()),
+        }
+    }
 }
 
 #[rustfmt::skip]
@@ -89,6 +414,16 @@ pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     0.0, 0.0, 0.0, 1.0,
 );
 
+// How fast the mouse turns the camera, by default, and the presets the
+// P key steps through.
+pub const DEFAULT_SENSITIVITY: f32 = 0.5;
+const SENSITIVITY_PRESETS: [f32; 4] = [0.25, 0.5, 1.0, 2.0];
+// Mouse motion shorter than this, in device units, is jitter.
+const DEAD_ZONE: f64 = 1.0;
+// With --smooth, motion still to come below this (radians, or world
+// units of zoom) is finished at once, so the camera comes to rest.
+const SETTLED: f32 = 1e-4;
+
 #[derive(Debug)]
 pub struct CameraController {
     amount_left: f32,
@@ -102,11 +437,26 @@ pub struct CameraController {
     scroll: f32,
     speed: f32,
     sensitivity: f32,
+    // Turn the other way, for users of tools that drag the world rather
+    // than the camera.
+    invert_x: bool,
+    invert_y: bool,
+    // Share of a turn or zoom still left after 1/60 s (--smooth), or 0
+    // to follow the mouse exactly.
+    smooth: f32,
+    // Yaw, pitch and zoom still to come.
+    pending: [f32; 3],
     last_render_time: Instant,
 }
 
+impl Default for CameraController {
+    fn default() -> Self {
+        Self::new(DEFAULT_SENSITIVITY, false, false, 0.0)
+    }
+}
+
 impl CameraController {
-    pub fn new() -> Self {
+    pub fn new(sensitivity: f32, invert_x: bool, invert_y: bool, smooth: f32) -> Self {
         Self {
             amount_left: 0.0,
             amount_right: 0.0,
@@ -118,14 +468,62 @@ impl CameraController {
             rotate_vertical: 0.0,
             scroll: 0.0,
             speed: 4.0,
-            sensitivity: 0.5,
+            sensitivity,
+            invert_x,
+            invert_y,
+            smooth,
+            pending: [0.0; 3],
             last_render_time: Instant::now(),
         }
     }
 
     pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
-        self.rotate_horizontal = mouse_dx as f32;
-        self.rotate_vertical = mouse_dy as f32;
+        if mouse_dx.hypot(mouse_dy) < DEAD_ZONE {
+            return;
+        }
+        let sign = |invert: bool| if invert { -1.0 } else { 1.0 };
+        self.rotate_horizontal = sign(self.invert_x) * mouse_dx as f32;
+        self.rotate_vertical = sign(self.invert_y) * mouse_dy as f32;
+    }
+
+    // Step to the next sensitivity preset above the current one, wrapping
+    // around to the slowest.  Returns the new sensitivity.
+    pub fn cycle_sensitivity(&mut self) -> f32 {
+        self.sensitivity = SENSITIVITY_PRESETS
+            .into_iter()
+            .find(|preset| *preset > self.sensitivity)
+            .unwrap_or(SENSITIVITY_PRESETS[0]);
+        self.sensitivity
+    }
+
+    // With --smooth, new motion joins whatever is still to come, and only
+    // a share of that is done this frame, easing the camera into place
+    // exponentially.  The share depends on the time passed, so the camera
+    // settles as fast at any frame rate.  After a pause, such as the
+    // first scroll in a while, it counts as one frame, not the whole
+    // pause, so that motion eases in too.
+    fn ease(&mut self, motion: [f32; 3], dt: f32) -> [f32; 3] {
+        if self.smooth <= 0.0 {
+            return motion;
+        }
+        let share = 1.0 - self.smooth.powf(dt.min(1.0 / 30.0) * 60.0);
+        let mut steps = motion;
+        for (pending, step) in self.pending.iter_mut().zip(steps.iter_mut()) {
+            *pending += *step;
+            *step = *pending * share;
+            *pending -= *step;
+            if pending.abs() < SETTLED {
+                *step += *pending;
+                *pending = 0.0;
+            }
+        }
+        steps
+    }
+
+    // Whether a smoothed turn or zoom is still easing in, and needs more
+    // frames drawn.
+    pub fn settling(&self) -> bool {
+        self.pending != [0.0; 3]
     }
 
     pub fn process_scroll(&mut self, delta: MouseScrollDelta) {
@@ -136,7 +534,9 @@ impl CameraController {
         };
     }
 
-    pub fn update_camera(&mut self, camera: &mut Camera) {
+    // In an orthographic projection, scrolling zooms the projection
+    // rather than moving the camera.
+    pub fn update_camera(&mut self, camera: &mut Camera, projection: &mut Projection) {
         let now = Instant::now();
         let dt = now - self.last_render_time;
         self.last_render_time = now;
@@ -157,7 +557,18 @@ impl CameraController {
         let (pitch_sin, pitch_cos) = camera.pitch.0.sin_cos();
         let scrollward =
             Vector3::new(pitch_cos * yaw_cos, pitch_sin, pitch_cos * yaw_sin).normalize();
-        camera.position += scrollward * self.scroll * self.speed * self.sensitivity * dt;
+        let [yaw, pitch, zoom] = self.ease(
+            [
+                self.rotate_horizontal * self.sensitivity * dt,
+                -self.rotate_vertical * self.sensitivity * dt,
+                self.scroll * self.speed * self.sensitivity * dt,
+            ],
+            dt,
+        );
+        match projection.kind() {
+            ProjectionKind::Perspective => camera.position += scrollward * zoom,
+            ProjectionKind::Orthographic => projection.zoom(zoom),
+        }
         self.scroll = 0.0;
 
         // Move up/down. Since we don't use roll, we can just
@@ -165,8 +576,8 @@ impl CameraController {
         camera.position.y -= (self.amount_up - self.amount_down) * self.speed * dt;
 
         // Rotate
-        camera.yaw += Rad(self.rotate_horizontal) * self.sensitivity * dt;
-        camera.pitch += Rad(-self.rotate_vertical) * self.sensitivity * dt;
+        camera.yaw += Rad(yaw);
+        camera.pitch += Rad(pitch);
 
         // If process_mouse isn't called every frame, these values
         // will not get set to zero, and the camera will rotate
@@ -183,6 +594,12 @@ impl CameraController {
     }
 }
 
+impl Default for CameraUniform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CameraUniform {
     pub fn new() -> Self {
         use cgmath::SquareMatrix;
@@ -192,8 +609,45 @@ impl CameraUniform {
         }
     }
 
+    // The projection and view, as the vertex shaders apply them.
+    pub fn view_proj(&self) -> Matrix4<f32> {
+        self.view_proj.into()
+    }
+
+    // Where the camera is, in data coordinates.
+    pub fn view_position(&self) -> Point3<f32> {
+        Point3::new(self.view_position[0], self.view_position[1], self.view_position[2])
+    }
+
     pub fn update_view_proj(&mut self, camera: &Camera, projection: &Projection) {
-        self.view_position = camera.position.to_homogeneous().into();
+        // Shaders light the world where the data lives, before a Z-up
+        // world is rotated into the camera's frame.
+        use cgmath::{SquareMatrix, Transform};
+        let to_world = camera.up.world_matrix().invert().unwrap();
+        self.view_position = to_world.transform_point(camera.position).to_homogeneous().into();
         self.view_proj = (projection.calc_matrix() * camera.calc_matrix()).into();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A resize sets the aspect ratio, and both projections squeeze x by
+    // it, so circles stay round in any window.
+    #[test]
+    fn resize_sets_the_aspect_ratio() {
+        let mut projection = Projection::default(dpi::PhysicalSize::new(800, 600));
+        assert_eq!(projection.aspect(), 800.0 / 600.0);
+        projection.resize(1600, 400);
+        assert_eq!(projection.aspect(), 4.0);
+
+        for kind in [ProjectionKind::Perspective, ProjectionKind::Orthographic] {
+            if projection.kind() != kind {
+                projection.toggle_kind(10.0);
+            }
+            let matrix = projection.calc_matrix();
+            assert!((matrix.x.x * 4.0 - matrix.y.y).abs() < 1e-6, "{:?}", kind);
+        }
+    }
+}
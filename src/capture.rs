@@ -0,0 +1,127 @@
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+// Turntable captures (--orbit): the camera turns a fixed angle about the
+// view center every frame, and every frame is saved as a numbered PNG,
+// ready for ffmpeg.  Worldview exits after the last one.
+#[derive(Clone, Debug)]
+pub struct Orbit {
+    // Turn per frame.
+    pub degrees: f32,
+    pub frames: u32,
+    pub out_dir: PathBuf,
+}
+
+impl Orbit {
+    pub fn path(&self, frame: u32) -> PathBuf {
+        frame_path(&self.out_dir, frame)
+    }
+}
+
+// Numbered frames, as saved by --orbit and --headless.
+pub fn frame_path(dir: &Path, frame: u32) -> PathBuf {
+    dir.join(format!("frame_{:05}.png", frame))
+}
+
+// Where the S key saves the view: named by the time it was taken, in UTC,
+// e.g. "worldview_2024-05-01T12-30-45.123Z.png".  Colons are left out, as
+// Windows does not allow them in file names.
+pub fn screenshot_path(dir: &Path) -> PathBuf {
+    let time = humantime::format_rfc3339_millis(SystemTime::now()).to_string();
+    dir.join(format!("worldview_{}.png", time.replace(':', "-")))
+}
+
+// Reads rendered frames back from the surface texture.  The surface must
+// be configured with COPY_SRC usage.
+pub struct Capture {
+    // Rows in the readback buffer are padded to the copy alignment.
+    buffer: Option<(wgpu::Buffer, wgpu::Extent3d, u32)>,
+    format: wgpu::TextureFormat,
+}
+
+impl Capture {
+    pub fn new() -> Capture {
+        Capture {
+            buffer: None,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+        }
+    }
+
+    // Copy the frame out of `texture`, after it is rendered and before it
+    // is presented.
+    pub fn copy(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture) {
+        let size = texture.size();
+        let padded_row = (4 * size.width).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        if !matches!(&self.buffer, Some((_, extent, _)) if *extent == size) {
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                mapped_at_creation: false,
+                size: (padded_row * size.height) as u64,
+                label: Some("capture::readback"),
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            });
+            self.buffer = Some((buffer, size, padded_row));
+        }
+        let (buffer, _, _) = self.buffer.as_ref().unwrap();
+        self.format = texture.format();
+
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: Some(size.height),
+                },
+            },
+            size,
+        );
+    }
+
+    // Wait for the copied frame, and write it to `path` as an RGB PNG.
+    // Call after the frame is submitted.
+    pub fn save(&self, device: &wgpu::Device, path: &Path) -> Result<(), String> {
+        let (buffer, size, padded_row) = match &self.buffer {
+            Some(buffer) => buffer,
+            None => return Err("no frame was copied".to_string()),
+        };
+        let swap = match self.format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            format => return Err(format!("cannot capture {:?} frames", format)),
+        };
+
+        let slice = buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::Maintain::Wait);
+        let mut pixels = Vec::with_capacity((3 * size.width * size.height) as usize);
+        {
+            let data = slice.get_mapped_range();
+            for row in data.chunks_exact(*padded_row as usize) {
+                for p in row[..(4 * size.width) as usize].chunks_exact(4) {
+                    match swap {
+                        true => pixels.extend([p[2], p[1], p[0]]),
+                        false => pixels.extend([p[0], p[1], p[2]]),
+                    }
+                }
+            }
+        }
+        buffer.unmap();
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+        }
+        let file = File::create(path).map_err(|err| err.to_string())?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), size.width, size.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(|err| err.to_string())?;
+        writer
+            .write_image_data(&pixels)
+            .map_err(|err| err.to_string())
+    }
+}
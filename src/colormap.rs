@@ -0,0 +1,83 @@
+// Colormaps turn a scalar, scaled to [0, 1], into a color: wireframe
+// edges by their length, and points by their height (--colormap).  Each
+// is computed, not looked up, the same way here for the legend as in the
+// shaders.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Colormap {
+    // Dark blue through green to yellow, evenly bright steps, and legible
+    // to the color blind.
+    #[default]
+    Viridis,
+    // Blue through cyan, green and yellow to red.
+    Jet,
+    Grayscale,
+}
+
+impl Colormap {
+    // The color at `t`, like colormap() in height_geometry.wsgl.
+    pub fn color(self, t: f32) -> [f32; 4] {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            // A polynomial fit to matplotlib's viridis.  The fit is in
+            // sRGB, and colors here are linear.
+            Colormap::Viridis => {
+                const C: [[f32; 3]; 7] = [
+                    [0.277_727_3, 0.005_407_344, 0.334_099_8],
+                    [0.105_093_04, 1.404_613_5, 1.384_590_2],
+                    [-0.330_861_83, 0.214_847_56, 0.095_095_16],
+                    [-4.634_230_6, -5.799_101, -19.332_441],
+                    [6.228_27, 14.179_933, 56.690_55],
+                    [4.776_385, -13.745_145, -65.353_03],
+                    [-5.435_456, 4.645_852_6, 26.312_435],
+                ];
+                let channel = |i: usize| C.iter().rev().fold(0.0, |sum, c| sum * t + c[i]).clamp(0.0, 1.0).powf(2.2);
+                [channel(0), channel(1), channel(2), 1.0]
+            }
+            // Also colormap() in scalar_geometry.wsgl, for edges.
+            Colormap::Jet => {
+                let x = 4.0 * t;
+                let channel = |center: f32| (1.5 - (x - center).abs()).clamp(0.0, 1.0);
+                [channel(3.0), channel(2.0), channel(1.0), 1.0]
+            }
+            Colormap::Grayscale => [t, t, t, 1.0],
+        }
+    }
+
+    // How the shaders pick the colormap.
+    pub fn index(self) -> f32 {
+        match self {
+            Colormap::Viridis => 0.0,
+            Colormap::Jet => 1.0,
+            Colormap::Grayscale => 2.0,
+        }
+    }
+}
+
+// The coordinate points are colored by (--colormap-axis), as in the PLY
+// file, before any --up rotation or transform.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColormapAxis {
+    X,
+    Y,
+    #[default]
+    Z,
+}
+
+impl ColormapAxis {
+    pub fn index(self) -> usize {
+        match self {
+            ColormapAxis::X => 0,
+            ColormapAxis::Y => 1,
+            ColormapAxis::Z => 2,
+        }
+    }
+
+    // The legend title.
+    pub fn label(self) -> &'static str {
+        match self {
+            ColormapAxis::X => "height (x)",
+            ColormapAxis::Y => "height (y)",
+            ColormapAxis::Z => "height (z)",
+        }
+    }
+}
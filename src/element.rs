@@ -4,8 +4,17 @@
 // Element is a enum that fixes specifically what elements we support,
 // and how they appear in PLY files.
 
-use ply_rs::ply;
-use std::mem;
+use ply_rs::{
+    parser::Parser,
+    ply::{self, Addable},
+};
+use std::{
+    io::{self, BufRead, Read},
+    mem,
+};
+
+// A header that has not ended by now is not a PLY header.
+const MAX_HEADER_BYTES: u64 = 1 << 20;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Element {
@@ -19,32 +28,195 @@ pub trait IntoElement {
     where
         Self: Sized,
     {
-        let element_size = mem::size_of::<Self>();
         let element_name = Self::element().to_string();
         let element_count = match header.elements.get(&element_name) {
             Some(element) => element.count,
             None => return false, // Cannot allocate buffer anyway
         };
-        let buffer_size = buffer.size() as usize;
-        buffer_size < element_size * element_count
+        Self::too_small(element_count, buffer)
+    }
+
+    // For counts known only once the payload is read, such as the
+    // triangles that facets split into.
+    fn too_small(count: usize, buffer: &wgpu::Buffer) -> bool
+    where
+        Self: Sized,
+    {
+        (buffer.size() as usize) < mem::size_of::<Self>() * count
     }
 }
 
 impl Element {
-    pub fn from(e: &String) -> Option<Element> {
-        match e.as_ref() {
-            "vertex" => Some(Element::Vertex),
-            "face" => Some(Element::Facet),
+    pub fn from(e: &str) -> Option<Element> {
+        match e.to_ascii_lowercase().as_str() {
+            "vertex" | "vertices" => Some(Element::Vertex),
+            "face" | "faces" => Some(Element::Facet),
             _ => None,
         }
     }
+
+    // The definition of this element in the header, if it has one.
+    pub fn find(self, header: &ply::Header) -> Option<&ply::ElementDef> {
+        header.elements.get(&self.to_string())
+    }
+
+    // Like find(), for parsers that cannot go on without the element.
+    pub fn require(self, header: &ply::Header) -> io::Result<&ply::ElementDef> {
+        self.find(header).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("no '{}' element", self))
+        })
+    }
 }
 
-impl std::string::ToString for Element {
-    fn to_string(&self) -> String {
+// Read a PLY header, more forgiving than ply_rs alone.  Comment and
+// obj_info lines are free text, which in the wild includes invalid UTF-8,
+// stray carriage returns and other control characters that ply_rs rejects,
+// so they are cleaned up before it sees them.  Elements we know under
+// another name ("Vertex", "vertices") are renamed to the name we look them
+// up by, keeping their order, which is the order of the payload.
+pub fn read_header(f: &mut impl BufRead) -> io::Result<ply::Header> {
+    let mut text = String::new();
+    let mut remarks = 0;
+    let mut f = f.take(MAX_HEADER_BYTES);
+    loop {
+        let mut line = vec![];
+        if f.read_until(b'\n', &mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "PLY header has no end_header",
+            ));
+        }
+        let line = String::from_utf8_lossy(&line);
+        let line = line.trim();
+
+        let remark = ["comment", "obj_info"]
+            .into_iter()
+            .find_map(|keyword| line.strip_prefix(keyword).map(|rest| (keyword, rest)));
+        match remark {
+            Some((keyword, rest)) => {
+                let rest: String = rest.chars().map(|c| if c.is_control() { ' ' } else { c }).collect();
+                text.push_str(&format!("{} {}\n", keyword, rest.trim()));
+                remarks += 1;
+            }
+            None if line.is_empty() => continue,
+            None => {
+                text.push_str(line);
+                text.push('\n');
+            }
+        }
+        if line == "end_header" {
+            break;
+        }
+    }
+    log::trace!("Header has {} comment and obj_info lines", remarks);
+
+    let mut header = Parser::<ply::DefaultElement>::new().read_header(&mut text.as_bytes())?;
+    let mut elements = ply::KeyMap::new();
+    for (name, mut element) in header.elements {
+        if let Some(known) = Element::from(&name).map(|e| e.to_string()) {
+            if known != name && !elements.contains_key(&known) {
+                log::debug!("Reading '{}' elements as '{}'", name, known);
+                element.name = known;
+            }
+        }
+        elements.insert(element.name.clone(), element);
+    }
+    header.elements = elements;
+    Ok(header)
+}
+
+// The header a PLY file would have for `vertices` vertices with float
+// `properties`, and `facets` facets, for artifacts read from other
+// formats, so the buffers are sized the same way.  Without vertices there
+// is no vertex element, and so no artifact at all.
+pub fn header_for(vertices: usize, properties: &[&str], facets: usize) -> ply::Header {
+    let mut header = ply::Header::new();
+    if vertices == 0 {
+        return header;
+    }
+
+    let mut vertex = ply::ElementDef::new(Element::Vertex.to_string());
+    vertex.count = vertices;
+    for name in properties {
+        vertex.properties.add(ply::PropertyDef::new(
+            name.to_string(),
+            ply::PropertyType::Scalar(ply::ScalarType::Float),
+        ));
+    }
+    header.elements.add(vertex);
+
+    if facets > 0 {
+        let mut facet = ply::ElementDef::new(Element::Facet.to_string());
+        facet.count = facets;
+        facet.properties.add(ply::PropertyDef::new(
+            "vertex_indices".to_string(),
+            ply::PropertyType::List(ply::ScalarType::UChar, ply::ScalarType::Int),
+        ));
+        header.elements.add(facet);
+    }
+    header
+}
+
+impl std::fmt::Display for Element {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            Element::Vertex => "vertex".to_string(),
-            Element::Facet => "face".to_string(),
+            Element::Vertex => write!(f, "vertex"),
+            Element::Facet => write!(f, "face"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn crlf_header_with_comments() {
+        let text = b"ply\r\n\
+            format ascii 1.0\r\n\
+            comment made by a scanner \xff\xfe\r\n\
+            comment tab\tand bell\x07 inside\r\n\
+            obj_info serial: 12\r\r\n\
+            \r\n\
+            element Vertex 2\r\n\
+            property float x\r\n\
+            property float y\r\n\
+            property float z\r\n\
+            element faces 1\r\n\
+            property list uchar int vertex_indices\r\n\
+            end_header\r\n\
+            0 0 0\r\n\
+            1 1 1\r\n\
+            3 0 1 1\r\n"
+            .to_vec();
+        let mut f = Cursor::new(text);
+        let header = read_header(&mut f).unwrap();
+
+        assert_eq!(header.comments.len(), 2);
+        assert_eq!(header.obj_infos, ["serial: 12"]);
+        assert_eq!(Element::Vertex.require(&header).unwrap().count, 2);
+        assert_eq!(Element::Facet.require(&header).unwrap().count, 1);
+        let names: Vec<&String> = header.elements.keys().collect();
+        assert_eq!(names, ["vertex", "face"]);
+
+        // The payload starts right after the header.
+        let mut rest = String::new();
+        f.read_to_string(&mut rest).unwrap();
+        assert!(rest.starts_with("0 0 0\r\n"));
+    }
+
+    #[test]
+    fn header_without_end() {
+        let mut f = Cursor::new(b"ply\nformat ascii 1.0\nelement vertex 1\n".to_vec());
+        assert!(read_header(&mut f).is_err());
+    }
+
+    #[test]
+    fn element_aliases() {
+        assert_eq!(Element::from("Vertex"), Some(Element::Vertex));
+        assert_eq!(Element::from("vertices"), Some(Element::Vertex));
+        assert_eq!(Element::from("FACE"), Some(Element::Facet));
+        assert_eq!(Element::from("edge"), None);
+    }
+}
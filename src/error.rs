@@ -0,0 +1,35 @@
+use crate::{vram, Element, Key};
+use std::{io, path::PathBuf};
+
+// Why an artifact was not shown.  Injectors log these and carry on with
+// the next file, so one bad file never ends a stream.
+#[derive(Debug, thiserror::Error)]
+pub enum WorldviewError {
+    // The file name does not name an artifact; see Key::from_path().
+    #[error("cannot match {}", .0.display())]
+    NoKey(PathBuf),
+    // Most likely caught mid-write.  Files are retried on the next event.
+    #[error("skipping partial PLY {source_name}: {error}")]
+    Unreadable {
+        source_name: String,
+        #[source]
+        error: io::Error,
+    },
+    // Neither the header nor an override tells what to draw.
+    #[error("unknown artifact {0}")]
+    Unknown(Key),
+    #[error("{0} has no '{1}' element; skipping it")]
+    Missing(Key, Element),
+    #[error("{0} is empty; rejecting it")]
+    Empty(Key),
+    #[error("{key} needs {:.1} MiB, more than the whole --vram-limit; skipping it", vram::mebibytes(*.bytes))]
+    OverBudget { key: Key, bytes: u64 },
+}
+
+// Log an artifact that was not shown, for injectors to go on with the
+// next one.
+pub(crate) fn skip_failed(result: Result<Key, WorldviewError>) {
+    if let Err(err) = result {
+        log::warn!("{}", err);
+    }
+}
@@ -0,0 +1,66 @@
+use crate::{ArtifactKind, Instance, Key};
+use serde_json::{json, Value};
+use std::io::Write;
+
+// Machine readable injection events (--events-out), for tools that drive
+// worldview and need to know when their frames are on screen.  Every
+// artifact shown or removed is printed to stdout as one line of JSON:
+//
+//     {"event":"add","key":"cloud","instance":12,"kind":"pointcloud","verts":10234}
+//     {"event":"remove","key":"cloud","instance":12}
+//
+// Logs go to stderr, so stdout carries nothing else.
+
+pub enum Event<'a> {
+    Add {
+        key: &'a Key,
+        kind: ArtifactKind,
+        verts: usize,
+        faces: Option<usize>,
+    },
+    Remove {
+        key: &'a Key,
+    },
+}
+
+pub fn emit(event: Event) {
+    let line = match event {
+        Event::Add {
+            key,
+            kind,
+            verts,
+            faces,
+        } => {
+            let mut line = json!({
+                "event": "add",
+                "key": key.artifact,
+                "instance": instance(key),
+                "kind": kind.to_string(),
+                "verts": verts,
+            });
+            if let Some(faces) = faces {
+                line["faces"] = faces.into();
+            }
+            line
+        }
+        Event::Remove { key } => json!({
+            "event": "remove",
+            "key": key.artifact,
+            "instance": instance(key),
+        }),
+    };
+
+    // One write per line, so lines from concurrent injectors never mix.
+    let mut stdout = std::io::stdout().lock();
+    writeln!(stdout, "{}", line).ok();
+    stdout.flush().ok();
+}
+
+// Frame numbers stay numbers; other instances are strings.
+fn instance(key: &Key) -> Value {
+    match &key.instance {
+        Some(Instance::Number(number)) => (*number).into(),
+        Some(Instance::Name(name)) => name.as_str().into(),
+        None => Value::Null,
+    }
+}
@@ -0,0 +1,62 @@
+use std::str::FromStr;
+
+// Distance fog (--fog): fragments fade linearly from their own color at
+// `near` to the fog color at `far`, distances along the view direction.
+// It makes depth readable in flat colored scans.  Without a color, the fog
+// is the background, so distant points fade out of view.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Fog {
+    pub near: f32,
+    pub far: f32,
+    pub color: Option<[f32; 3]>,
+}
+
+// Parsed from "near,far" or "near,far,r,g,b".
+impl FromStr for Fog {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|value| value.trim().parse::<f32>().map_err(|err| format!("{}: {}", value, err)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let (near, far, color) = match values[..] {
+            [near, far] => (near, far, None),
+            [near, far, r, g, b] => (near, far, Some([r, g, b])),
+            _ => return Err("expected near,far or near,far,r,g,b".to_string()),
+        };
+        if !(near >= 0.0 && far > near) {
+            return Err("fog needs 0 <= near < far".to_string());
+        }
+        Ok(Fog { near, far, color })
+    }
+}
+
+// FogUniform is the GPU side of the fog, shared by every pane.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FogUniform {
+    color: [f32; 4],
+    // near, far, and 1 when fog is on.
+    range: [f32; 4],
+}
+
+impl FogUniform {
+    pub fn new(fog: Option<&Fog>, background: [f32; 3]) -> FogUniform {
+        match fog {
+            Some(fog) => {
+                let [r, g, b] = fog.color.unwrap_or(background);
+                FogUniform {
+                    color: [r, g, b, 1.0],
+                    range: [fog.near, fog.far, 1.0, 0.0],
+                }
+            }
+            // The range is still one wide, so the shader never divides by
+            // zero.
+            None => FogUniform {
+                color: [0.0; 4],
+                range: [0.0, 1.0, 0.0, 0.0],
+            },
+        }
+    }
+}
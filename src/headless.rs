@@ -0,0 +1,77 @@
+use crate::{capture, history::History, playback, window, ArtifactsLock, InjectionEvent, WindowState, WorldviewConfig};
+use std::{future::Future, path::PathBuf};
+use tokio::sync::{mpsc, watch};
+
+// Rendering without a window (--headless), for CI and thumbnails on
+// servers without a display.  The same pipelines draw into a texture
+// instead of the window's surface.  Every time artifacts come or go, the
+// view is framed around everything shown, as by the F key, and saved as
+// the next numbered PNG, as --orbit saves frames.  A view saved with
+// --camera is kept as it is instead, for images to compare across runs.  Events that arrive
+// while a frame is drawn are handled together, drawn as one frame.
+//
+// Once every injector and the producer are done, e.g. after `playback
+// --once`, the last frames are saved and worldview exits.  Network
+// injectors and watchers run until interrupted.
+#[allow(clippy::too_many_arguments)]
+pub async fn run(
+    panes: Vec<(ArtifactsLock, Option<History>)>,
+    mut events: mpsc::UnboundedReceiver<InjectionEvent>,
+    mut injected: mpsc::Receiver<()>,
+    config: &WorldviewConfig,
+    paused: watch::Sender<bool>,
+    rescan: watch::Sender<()>,
+    control: watch::Sender<playback::Control>,
+    shutdown: impl Future<Output = ()>,
+) {
+    // Nothing can ask for another pass without a window, so playback
+    // --once ends after its first.
+    drop(rescan);
+    drop(control);
+    let out_dir = config.headless.clone().unwrap_or_else(|| PathBuf::from("."));
+
+    let mut app = match WindowState::offscreen(panes.clone(), config, paused) {
+        Ok(app) => app,
+        Err(err) => {
+            log::error!("{}", err);
+            shutdown.await;
+            std::process::exit(1);
+        }
+    };
+
+    let mut frame = 0;
+    loop {
+        // Events already sent are drawn before the injectors are seen to
+        // be done, so the last of them is not lost.
+        let event = tokio::select! {
+            biased;
+            event = events.recv() => event,
+            _ = injected.recv() => None,
+        };
+        let Some(event) = event else {
+            break;
+        };
+        for event in std::iter::once(event).chain(std::iter::from_fn(|| events.try_recv().ok())) {
+            if let InjectionEvent::Settings(settings) = event {
+                app.reconfigure(*settings);
+            }
+        }
+
+        if config.camera.is_none() {
+            app.frame_all();
+        }
+        let path = capture::frame_path(&out_dir, frame);
+        match app.render_offscreen(&path) {
+            Ok(()) => log::info!("Saved {}", path.display()),
+            Err(err) => {
+                log::error!("{}: {}", path.display(), err);
+                break;
+            }
+        }
+        frame += 1;
+    }
+    log::info!("Rendered {} frames in {}", frame, out_dir.display());
+
+    shutdown.await;
+    window::release(app, panes);
+}
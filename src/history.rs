@@ -0,0 +1,174 @@
+use crate::{error::skip_failed, Format, Key, Sequencer, Staged};
+use std::{
+    collections::{HashMap, VecDeque},
+    io::Cursor,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::watch;
+
+// The last N injected PLY files, kept as they were read (--history N), so
+// recent frames can be shown again without going back to the producer,
+// whose files may be long overwritten.  The window steps through them with
+// the arrow keys; live injection pauses meanwhile, and resumes when the
+// window goes back to live.
+//
+// Frames are numbered in the order they were kept, and the numbers are
+// never reused, so a selection stays put while old frames fall out.
+
+struct Frame {
+    serial: u64,
+    key: Key,
+    bytes: Arc<[u8]>,
+}
+
+struct Frames {
+    capacity: usize,
+    frames: VecDeque<Frame>,
+    next: u64,
+}
+
+#[derive(Clone)]
+pub struct History {
+    frames: Arc<Mutex<Frames>>,
+    // The frame on show, or None for live.
+    shown: Arc<watch::Sender<Option<u64>>>,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> History {
+        History {
+            frames: Arc::new(Mutex::new(Frames {
+                capacity,
+                frames: VecDeque::with_capacity(capacity),
+                next: 0,
+            })),
+            shown: Arc::new(watch::channel(None).0),
+        }
+    }
+
+    // Remember the bytes that were injected under `key`, forgetting the
+    // oldest frame if full.
+    pub fn keep(&self, key: &Key, bytes: Arc<[u8]>) {
+        let mut frames = self.frames.lock().unwrap();
+        if frames.frames.len() == frames.capacity {
+            frames.frames.pop_front();
+        }
+        let serial = frames.next;
+        frames.next += 1;
+        frames.frames.push_back(Frame {
+            serial,
+            key: key.clone(),
+            bytes,
+        });
+    }
+
+    pub fn shown(&self) -> Option<u64> {
+        *self.shown.borrow()
+    }
+
+    // Show the frame before the one on show, or before the newest when
+    // live.  Returns false if there is none.
+    pub fn back(&self) -> bool {
+        let frames = self.frames.lock().unwrap();
+        let before = match self.shown() {
+            Some(shown) => shown,
+            // Live shows the newest frame already.
+            None => match frames.frames.back() {
+                Some(newest) => newest.serial,
+                None => return false,
+            },
+        };
+        match frames.frames.iter().rev().find(|frame| frame.serial < before) {
+            Some(frame) => {
+                self.shown.send_replace(Some(frame.serial));
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Show the frame after the one on show.  Stepping past the newest goes
+    // back to live.
+    pub fn forward(&self) {
+        let shown = match self.shown() {
+            Some(shown) => shown,
+            None => return,
+        };
+        let frames = self.frames.lock().unwrap();
+        let newer = frames.frames.iter().find(|frame| frame.serial > shown);
+        match newer {
+            Some(frame) if frame.serial != frames.frames.back().unwrap().serial => {
+                self.shown.send_replace(Some(frame.serial));
+            }
+            _ => {
+                self.shown.send_replace(None);
+            }
+        }
+    }
+
+    pub fn live(&self) {
+        self.shown.send_if_modified(|shown| shown.take().is_some());
+    }
+
+    // Where the frame on show is, counting back from the newest (0), and
+    // its key, for the window to show.
+    pub fn position(&self) -> Option<(usize, Key)> {
+        let shown = self.shown()?;
+        let frames = self.frames.lock().unwrap();
+        let (back, frame) = frames
+            .frames
+            .iter()
+            .rev()
+            .enumerate()
+            .find(|(_, frame)| frame.serial == shown)?;
+        Some((back, frame.key.clone()))
+    }
+
+    // The frames to upload for the selection: the one on show, or, going
+    // back to live, the newest of every key, to undo what was shown.
+    fn selected(&self, shown: Option<u64>) -> Vec<(Key, Arc<[u8]>)> {
+        let frames = self.frames.lock().unwrap();
+        match shown {
+            Some(shown) => frames
+                .frames
+                .iter()
+                .filter(|frame| frame.serial == shown)
+                .map(|frame| (frame.key.clone(), frame.bytes.clone()))
+                .collect(),
+            None => {
+                let newest: HashMap<&Key, &Arc<[u8]>> =
+                    frames.frames.iter().map(|frame| (&frame.key, &frame.bytes)).collect();
+                newest
+                    .into_iter()
+                    .map(|(key, bytes)| (key.clone(), bytes.clone()))
+                    .collect()
+            }
+        }
+    }
+}
+
+// Upload the selected frames through the sequencer, as the window steps
+// through the history.  Frames shown again are not kept again.
+pub async fn replay(history: History, sequencer: impl Sequencer, exit: watch::Sender<bool>) {
+    let mut shown = history.shown.subscribe();
+    let mut exit = exit.subscribe();
+    loop {
+        tokio::select! {
+            _ = exit.changed() => return,
+            changed = shown.changed() => if changed.is_err() { return },
+        }
+
+        let selected = *shown.borrow_and_update();
+        for (key, bytes) in history.selected(selected) {
+            let format = Format::sniff(&bytes);
+            let staged = Staged::read(&mut Cursor::new(&bytes[..]), format, sequencer.forced_kind(&key));
+            match staged {
+                Ok(Some(staged)) => {
+                    skip_failed(tokio::task::block_in_place(|| sequencer.add_staged(key, staged)));
+                }
+                Ok(None) => log::debug!("Unknown artifact {}", key),
+                Err(err) => log::warn!("Cannot show {} again: {}", key, err),
+            }
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{inject::wait_unpaused, Instance, Key, Sequencer};
+use std::{io::Cursor, net::SocketAddr};
+use tokio::sync::watch;
+use tonic::{transport::Server, Request, Response, Status, Streaming};
+
+// A gRPC server (proto/worldview.proto), so producers on other machines
+// can push artifacts without a shared filesystem.  Each client streams
+// frames of raw PLY bytes, named as files would be, and every frame goes
+// through the sequencer like a file does.
+//
+// Frames are read one at a time, and only after the previous one is
+// shown, so a fast producer is held back by HTTP/2 flow control rather
+// than queued here.  While injection is paused, streams are not read at
+// all.
+
+pub mod proto {
+    tonic::include_proto!("worldview");
+}
+
+use proto::{
+    worldview_server::{Worldview, WorldviewServer},
+    Frame, PushReply,
+};
+
+// Larger than any frame a producer should send.  tonic's default, 4 MiB,
+// is too small for dense point clouds.
+const MAX_FRAME: usize = 1 << 30;
+
+struct Ingest<S> {
+    sequencer: S,
+    exit: watch::Sender<bool>,
+    paused: watch::Receiver<bool>,
+}
+
+#[tonic::async_trait]
+impl<S: Sequencer + Send + Sync + 'static> Worldview for Ingest<S> {
+    async fn push_artifact(&self, request: Request<Streaming<Frame>>) -> Result<Response<PushReply>, Status> {
+        let peer = match request.remote_addr() {
+            Some(peer) => peer.to_string(),
+            None => "client".to_string(),
+        };
+        log::info!("{} connected", peer);
+        let mut frames = request.into_inner();
+        let mut exit = self.exit.subscribe();
+        let mut paused = self.paused.clone();
+        let mut reply = PushReply::default();
+
+        loop {
+            if wait_unpaused(&mut paused, &mut exit).await {
+                return Err(Status::unavailable("worldview is exiting"));
+            }
+            let frame = tokio::select! {
+                _ = exit.changed() => return Err(Status::unavailable("worldview is exiting")),
+                frame = frames.message() => frame,
+            };
+            let frame = match frame {
+                Ok(Some(frame)) => frame,
+                Ok(None) => break,
+                // Most likely the client went away mid-stream.
+                Err(status) => {
+                    log::warn!("{} disconnected: {}", peer, status.message());
+                    return Err(status);
+                }
+            };
+
+            if frame.artifact.is_empty() {
+                log::warn!("{}: frame without an artifact name", peer);
+                reply.rejected += 1;
+                continue;
+            }
+            let key = Key {
+                instance: (!frame.instance.is_empty()).then(|| Instance::from(frame.instance.as_str())),
+                artifact: frame.artifact,
+            };
+            let added = tokio::task::block_in_place(|| {
+                self.sequencer.add_reader(key, &mut Cursor::new(&frame.ply[..]))
+            });
+            match added {
+                Ok(_) => reply.accepted += 1,
+                Err(err) => {
+                    log::warn!("{}: {}", peer, err);
+                    reply.rejected += 1;
+                }
+            }
+        }
+
+        log::info!("{} done: {} frames shown, {} rejected", peer, reply.accepted, reply.rejected);
+        Ok(Response::new(reply))
+    }
+}
+
+pub async fn run(
+    addr: SocketAddr,
+    sequencer: impl Sequencer + Send + Sync + 'static,
+    exit: watch::Sender<bool>,
+    paused: watch::Receiver<bool>,
+) {
+    let mut shutdown = exit.subscribe();
+    let service = WorldviewServer::new(Ingest {
+        sequencer,
+        exit,
+        paused,
+    })
+    .max_decoding_message_size(MAX_FRAME);
+
+    let served = Server::builder()
+        .add_service(service)
+        .serve_with_shutdown(addr, async move {
+            shutdown.changed().await.ok();
+        })
+        .await;
+    if let Err(err) = served {
+        log::error!("gRPC server on {}: {}", addr, err);
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{error::skip_failed, inject::wait_unpaused, Format, Instance, Key, Sequencer, Staged};
+use axum::{
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::StatusCode,
+    routing::post,
+    Router,
+};
+use serde::Deserialize;
+use std::{io::Cursor, net::SocketAddr, sync::Arc};
+use tokio::sync::watch;
+
+// One-shot uploads over HTTP, for clients simpler than a gRPC producer:
+//
+//     curl --data-binary @cloud.ply http://localhost:8080/push/cloud
+//     curl -X DELETE http://localhost:8080/push/cloud
+//
+// "?instance=12" gives the instance, as "12.cloud.ply" would.  Both
+// HTTP/1.1 and HTTP/2 are served.  A body that does not parse is answered
+// with 400 and the reason, and nothing on screen changes.
+
+// Larger than any artifact a client should post.  axum's default, 2 MiB,
+// is too small for dense point clouds.
+const MAX_BODY: usize = 1 << 30;
+
+struct Push<S> {
+    sequencer: S,
+    exit: watch::Sender<bool>,
+    paused: watch::Receiver<bool>,
+}
+
+#[derive(Deserialize)]
+struct Params {
+    instance: Option<String>,
+}
+
+impl Params {
+    fn key(self, artifact: String) -> Key {
+        Key {
+            instance: self.instance.as_deref().map(Instance::from),
+            artifact,
+        }
+    }
+}
+
+type Reply = (StatusCode, String);
+
+async fn push<S: Sequencer>(
+    State(state): State<Arc<Push<S>>>,
+    Path(artifact): Path<String>,
+    Query(params): Query<Params>,
+    body: Bytes,
+) -> Reply {
+    let key = params.key(artifact);
+    // While paused, the request waits, as a file would on disk.
+    let mut exit = state.exit.subscribe();
+    let mut paused = state.paused.clone();
+    if wait_unpaused(&mut paused, &mut exit).await {
+        return (StatusCode::SERVICE_UNAVAILABLE, "worldview is exiting\n".to_string());
+    }
+
+    let sequencer = &state.sequencer;
+    let forced = sequencer.forced_kind(&key);
+    tokio::task::block_in_place(|| {
+        let staged = match sequencer.history() {
+            Some(_) => Staged::from_bytes(body.to_vec().into(), Format::Ply, forced),
+            None => Staged::read_ply(&mut Cursor::new(&body[..]), forced),
+        };
+        match staged {
+            Ok(Some(staged)) => match sequencer.add_staged(key.clone(), staged) {
+                Ok(key) => (StatusCode::OK, format!("Showing {}\n", key)),
+                Err(err) => {
+                    log::warn!("Rejected upload of {}: {}", key, err);
+                    (StatusCode::BAD_REQUEST, format!("{}\n", err))
+                }
+            },
+            Ok(None) => (
+                StatusCode::BAD_REQUEST,
+                format!("{}: not a point cloud, wireframe or mesh\n", key),
+            ),
+            Err(err) => {
+                log::warn!("Rejected upload of {}: {}", key, err);
+                (StatusCode::BAD_REQUEST, format!("{}: {}\n", key, err))
+            }
+        }
+    })
+}
+
+async fn remove<S: Sequencer>(
+    State(state): State<Arc<Push<S>>>,
+    Path(artifact): Path<String>,
+    Query(params): Query<Params>,
+) -> Reply {
+    let key = params.key(artifact);
+    let loaded = state.sequencer.get_artifacts().lock().unwrap().contains_key(&key);
+    if !loaded {
+        return (StatusCode::NOT_FOUND, format!("{} is not shown\n", key));
+    }
+    skip_failed(state.sequencer.remove_as(key.clone()));
+    (StatusCode::OK, format!("Removed {}\n", key))
+}
+
+pub async fn run<S: Sequencer + Send + Sync + 'static>(
+    addr: SocketAddr,
+    sequencer: S,
+    exit: watch::Sender<bool>,
+    paused: watch::Receiver<bool>,
+) {
+    let listener = match tokio::net::TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            log::error!("Cannot serve HTTP on {}: {}", addr, err);
+            return;
+        }
+    };
+    let mut shutdown = exit.subscribe();
+    let state = Arc::new(Push {
+        sequencer,
+        exit,
+        paused,
+    });
+    let app = Router::new()
+        .route("/push/:artifact", post(push::<S>).delete(remove::<S>))
+        .layer(DefaultBodyLimit::max(MAX_BODY))
+        .with_state(state);
+
+    let served = axum::serve(listener, app)
+        .with_graceful_shutdown(async move {
+            shutdown.changed().await.ok();
+        })
+        .await;
+    if let Err(err) = served {
+        log::error!("HTTP server on {}: {}", addr, err);
+    }
+}
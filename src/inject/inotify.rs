@@ -1,30 +1,252 @@
-use crate::Sequencer;
-use inotify::{EventMask, Inotify, WatchMask};
-use std::{fs, path::PathBuf};
+use crate::{
+    error::skip_failed,
+    inject::{file_key, reload_dir, spawn_rescan, wait_unpaused},
+    Sequencer,
+};
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask, Watches};
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
 use tokio::sync::watch;
 
 // INotify will inject into the visualization, all new files that appear.
+// Linux only; the notify feature (on by default) watches through the
+// portable watcher instead, and this needs no extra dependency.
+// Files renamed into the directory count as new; producers that write a
+// temp file and rename() it into place never expose a partial file.  Files
+// renamed out of the directory count as deleted.  A rescan (the R key)
+// adds every file in the directory again, in playback order.
+//
+// inotify watches are not recursive.  With --recursive, every directory
+// below is watched on its own, and a directory created or renamed in later
+// is watched as it appears, with the files already in it added.
 
-pub async fn run(assets_dir: PathBuf, sequencer: impl Sequencer, exit: watch::Sender<bool>) {
-    let mut inotify = Inotify::init().unwrap();
-    inotify
-        .watches()
-        .add(
-            assets_dir.clone(),
-            WatchMask::DELETE | WatchMask::CLOSE_WRITE,
-        )
-        .unwrap();
-
-    // How the heck to cleanly exit inotify::read_events_blocking()?  It
-    // is blocked in the Linux kernel, not tokio, so only a Linux signal
-    // can interrupt which feels a bit heavy for this purpose.  We cannot
-    // drop or close() it, because we don't own it.  So, drop a sentinal
-    // file in the watched directory to signal an exit.
-    let mut sentinel_path = assets_dir.clone();
-    sentinel_path.push("exit_sentinel");
-
-    // Block on our exit watcher, and write the sentinel when it fires.  
-    // This whole task exits only to cleanly terminate the blocking read 
+pub async fn run(
+    assets_dir: PathBuf,
+    recursive: bool,
+    filter: Regex,
+    sequencer: impl Sequencer + Clone + Send + 'static,
+    exit: watch::Sender<bool>,
+    mut paused: watch::Receiver<bool>,
+    rescan: watch::Receiver<()>,
+) {
+    let inotify = match Inotify::init() {
+        Ok(inotify) => inotify,
+        Err(err) => {
+            log::error!("Cannot start inotify: {}", err);
+            return;
+        }
+    };
+    let mut tree = Tree {
+        watches: inotify.watches(),
+        dirs: HashMap::new(),
+        recursive,
+    };
+    if let Err(err) = tree.watch(&assets_dir) {
+        log::error!("Cannot watch {}: {}", assets_dir.display(), err);
+        return;
+    }
+
+    let sentinel_path = spawn_exit_sentinel(&assets_dir, &exit);
+    spawn_rescan(rescan, paused.clone(), &exit, {
+        let (assets_dir, filter, sequencer) = (assets_dir.clone(), filter.clone(), sequencer.clone());
+        move || reload_dir(&assets_dir, recursive, &filter, &sequencer)
+    });
+    let mut exit = exit.subscribe();
+    let runtime = tokio::runtime::Handle::current();
+
+    // Read events that were added with `Watches::add` above.
+    tokio::task::block_in_place(move || {
+        let mut inotify = inotify;
+        let mut buffer = [0; 1024];
+        loop {
+            if runtime.block_on(wait_unpaused(&mut paused, &mut exit)) {
+                return;
+            }
+            let events = match inotify.read_events_blocking(&mut buffer) {
+                Ok(events) => events,
+                Err(err) => {
+                    log::error!("Stopped watching {}: {}", assets_dir.display(), err);
+                    return;
+                }
+            };
+            for event in events {
+                // The directory is gone, or no longer watched.
+                if event.mask.contains(EventMask::IGNORED) {
+                    tree.dirs.remove(&event.wd);
+                    continue;
+                }
+                let (Some(dir), Some(name)) = (tree.dirs.get(&event.wd), event.name) else {
+                    continue;
+                };
+
+                // Check the exit sentinel for a clean exit.
+                if *dir == assets_dir && name == sentinel_path.file_name().unwrap() {
+                    return;
+                }
+
+                let path = dir.join(name);
+
+                if event.mask.contains(EventMask::ISDIR) {
+                    if recursive && event.mask.intersects(EventMask::CREATE | EventMask::MOVED_TO) {
+                        if let Err(err) = tree.watch(&path) {
+                            log::warn!("Cannot watch {}: {}", path.display(), err);
+                        }
+                        reload_dir(&path, true, &filter, &sequencer);
+                    }
+                    continue;
+                }
+
+                skip_failed(match event.mask {
+                    EventMask::CLOSE_WRITE | EventMask::MOVED_TO => sequencer.add(&path),
+                    EventMask::DELETE | EventMask::MOVED_FROM => sequencer.remove(&path),
+                    _ => continue,
+                });
+            }
+        }
+    });
+}
+
+// Watch a single file that the producer overwrites in place, and inject
+// it under a stable key (the file stem) every time it changes.
+//
+// The watch is placed on the parent directory rather than the file
+// itself.  Producers that replace the file with an atomic rename() delete
+// the old inode, which would silently end an inode watch; the directory
+// entry, however, survives and reports the replacement as MOVED_TO.
+pub async fn run_file(
+    path: PathBuf,
+    sequencer: impl Sequencer + Clone + Send + 'static,
+    exit: watch::Sender<bool>,
+    mut paused: watch::Receiver<bool>,
+    rescan: watch::Receiver<()>,
+) {
+    let assets_dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => std::env::current_dir().unwrap(),
+    };
+    let (file_name, key) = match file_key(&path) {
+        Some(named) => named,
+        None => {
+            log::error!("Cannot watch {}: not a file name", path.display());
+            return;
+        }
+    };
+
+    let mut inotify = match Inotify::init() {
+        Ok(inotify) => inotify,
+        Err(err) => {
+            log::error!("Cannot start inotify: {}", err);
+            return;
+        }
+    };
+    let watched = inotify.watches().add(
+        assets_dir.clone(),
+        WatchMask::DELETE | WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::MOVED_FROM,
+    );
+    if let Err(err) = watched {
+        log::error!("Cannot watch {}: {}", assets_dir.display(), err);
+        return;
+    }
+
+    let sentinel_path = spawn_exit_sentinel(&assets_dir, &exit);
+    spawn_rescan(rescan, paused.clone(), &exit, {
+        let (path, key, sequencer) = (path.clone(), key.clone(), sequencer.clone());
+        move || {
+            if path.exists() {
+                skip_failed(sequencer.add_as(key.clone(), &path));
+            }
+        }
+    });
+    let mut exit = exit.subscribe();
+    let runtime = tokio::runtime::Handle::current();
+
+    // Show whatever is already there before the first change arrives.
+    if path.exists() {
+        skip_failed(sequencer.add_as(key.clone(), &path));
+    }
+
+    tokio::task::block_in_place(move || {
+        let mut buffer = [0; 1024];
+        loop {
+            if runtime.block_on(wait_unpaused(&mut paused, &mut exit)) {
+                return;
+            }
+            let events = match inotify.read_events_blocking(&mut buffer) {
+                Ok(events) => events,
+                Err(err) => {
+                    log::error!("Stopped watching {}: {}", path.display(), err);
+                    return;
+                }
+            };
+            for event in events {
+                if event.name == Some(sentinel_path.file_name().unwrap()) {
+                    return;
+                }
+
+                // Ignore everything else happening in the directory.
+                if event.name != Some(file_name.as_os_str()) {
+                    continue;
+                }
+
+                skip_failed(match event.mask {
+                    EventMask::CLOSE_WRITE | EventMask::MOVED_TO => {
+                        sequencer.add_as(key.clone(), &path)
+                    }
+                    EventMask::DELETE | EventMask::MOVED_FROM => sequencer.remove_as(key.clone()),
+                    _ => continue,
+                });
+            }
+        }
+    });
+}
+
+// The watched directories, by watch descriptor, to turn the file names in
+// events back into paths.
+struct Tree {
+    watches: Watches,
+    dirs: HashMap<WatchDescriptor, PathBuf>,
+    recursive: bool,
+}
+
+impl Tree {
+    // Watch `dir`, and with --recursive, every directory below it.  A
+    // directory already watched, perhaps under another name after a
+    // rename, keeps its descriptor and takes the new path.
+    fn watch(&mut self, dir: &Path) -> std::io::Result<()> {
+        let mut mask = WatchMask::DELETE | WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::MOVED_FROM;
+        if self.recursive {
+            mask |= WatchMask::CREATE;
+        }
+        let wd = self.watches.add(dir, mask)?;
+        self.dirs.insert(wd, dir.to_path_buf());
+        if !self.recursive {
+            return Ok(());
+        }
+        let below = walkdir::WalkDir::new(dir).min_depth(1);
+        for entry in below.into_iter().filter_map(|entry| entry.ok()) {
+            if entry.file_type().is_dir() {
+                let wd = self.watches.add(entry.path(), mask)?;
+                self.dirs.insert(wd, entry.into_path());
+            }
+        }
+        Ok(())
+    }
+}
+
+// How the heck to cleanly exit inotify::read_events_blocking()?  It
+// is blocked in the Linux kernel, not tokio, so only a Linux signal
+// can interrupt which feels a bit heavy for this purpose.  We cannot
+// drop or close() it, because we don't own it.  So, drop a sentinal
+// file in the watched directory to signal an exit.
+fn spawn_exit_sentinel(assets_dir: &Path, exit: &watch::Sender<bool>) -> PathBuf {
+    let sentinel_path = assets_dir.join("exit_sentinel");
+
+    // Block on our exit watcher, and write the sentinel when it fires.
+    // This whole task exits only to cleanly terminate the blocking read
     // below.
     tokio::spawn({
         let mut exit = exit.subscribe();
@@ -35,34 +257,35 @@ pub async fn run(assets_dir: PathBuf, sequencer: impl Sequencer, exit: watch::Se
             // Exit started. Touch a file that the other task will see.
             let _ = fs::OpenOptions::new()
                 .create(true)
+                .truncate(true)
                 .write(true)
                 .open(sentinel_path.clone());
 
             // Clean up the sentinel.
-            fs::remove_file(sentinel_path).unwrap();
+            fs::remove_file(sentinel_path).ok();
         }
     });
 
-    // Read events that were added with `Watches::add` above.
-    tokio::task::block_in_place(move || {
-        let mut buffer = [0; 1024];
-        loop {
-            let events = inotify.read_events_blocking(&mut buffer).unwrap();
-            for event in events {
-                // Check the exit sentinel for a clean exit.
-                if event.name == Some(sentinel_path.file_name().unwrap()) {
-                    return;
-                }
+    sentinel_path
+}
 
-                let mut path = assets_dir.clone();
-                path.push(event.name.unwrap());
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inject::tests::{overwrite_injects_each_time, rename_injects_once, rescan_reloads_filtered};
 
-                match event.mask {
-                    EventMask::CLOSE_WRITE => sequencer.add(&path),
-                    EventMask::DELETE => sequencer.remove(&path),
-                    _ => None,
-                };
-            }
-        }
-    });
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rescan_reloads_filtered_files() {
+        rescan_reloads_filtered(run).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn overwritten_file_is_injected_each_time() {
+        overwrite_injects_each_time(run_file).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn renamed_file_is_injected_once() {
+        rename_injects_once(run).await;
+    }
 }
@@ -1,2 +1,303 @@
+pub mod grpc;
+pub mod http;
+#[cfg(target_os = "linux")]
 pub mod inotify;
 pub mod playback;
+pub mod stdin;
+pub mod websocket;
+#[cfg(feature = "notify")]
+pub mod watcher;
+
+// Live directory watching: portable through the notify crate, or else
+// through inotify, which only Linux has.
+#[cfg(feature = "notify")]
+pub use watcher as live;
+#[cfg(all(not(feature = "notify"), target_os = "linux"))]
+pub use inotify as live;
+#[cfg(all(not(feature = "notify"), not(target_os = "linux")))]
+compile_error!("watching directories needs the notify feature outside Linux");
+
+use crate::{error::skip_failed, Key, Sequencer};
+use itertools::Itertools;
+use regex::Regex;
+use std::{
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+};
+use tokio::sync::watch;
+
+// While the window is hidden, injectors may be asked to hold off; the
+// producer's files then wait on disk, or in the inotify queue, until the
+// window shows again.  Returns true if the process exits while waiting.
+async fn wait_unpaused(paused: &mut watch::Receiver<bool>, exit: &mut watch::Receiver<bool>) -> bool {
+    if !*paused.borrow() {
+        return false;
+    }
+    log::debug!("Injection paused");
+    tokio::select! {
+        _ = paused.wait_for(|paused| !paused) => {
+            log::debug!("Injection resumed");
+            false
+        }
+        _ = exit.changed() => true,
+    }
+}
+
+// Run `reload` whenever the window asks for a rescan, once injection is
+// not paused.  The watchers block reading events and cannot be woken for
+// it, so this runs beside them.
+fn spawn_rescan(
+    mut rescan: watch::Receiver<()>,
+    mut paused: watch::Receiver<bool>,
+    exit: &watch::Sender<bool>,
+    reload: impl Fn() + Send + 'static,
+) {
+    let mut exit = exit.subscribe();
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = exit.changed() => return,
+                changed = rescan.changed() => if changed.is_err() { return },
+            }
+            if wait_unpaused(&mut paused, &mut exit).await {
+                return;
+            }
+            tokio::task::block_in_place(&reload);
+        }
+    });
+}
+
+// A single watched file is shown under its stem, with no instance.  None
+// for paths that do not end in a file name, such as "..".
+fn file_key(path: &Path) -> Option<(OsString, Key)> {
+    let key = Key {
+        instance: None,
+        artifact: path.file_stem()?.to_string_lossy().to_string(),
+    };
+    Some((path.file_name()?.to_owned(), key))
+}
+
+// The files in `dir`, and with --recursive, in all directories below it.
+// Entries that cannot be read are skipped.
+fn list_files(dir: &Path, recursive: bool) -> io::Result<Vec<PathBuf>> {
+    if !recursive {
+        return Ok(fs::read_dir(dir)?.filter_map(|entry| Some(entry.ok()?.path())).collect());
+    }
+    // Fail as read_dir() would if the top directory cannot be read.
+    fs::read_dir(dir)?;
+    Ok(walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+        .collect())
+}
+
+// Add every file in a watched directory that matches --filter again, in
+// playback order (the R key).  Also used for directories that appear
+// under a --recursive watch, to catch files written before the watch on
+// them was in place.
+fn reload_dir(assets_dir: &Path, recursive: bool, filter: &Regex, sequencer: &impl Sequencer) {
+    let paths = match list_files(assets_dir, recursive) {
+        Ok(paths) => paths
+            .into_iter()
+            .filter(|path| filter.is_match(&path.to_string_lossy()))
+            .filter_map(|path| Some((Key::from_path(&path)?, path)))
+            .sorted_by(|(a, _), (b, _)| (&a.instance, &a.artifact).cmp(&(&b.instance, &b.artifact)))
+            .collect::<Vec<_>>(),
+        Err(err) => {
+            log::warn!("Cannot rescan {}: {}", assets_dir.display(), err);
+            return;
+        }
+    };
+    log::info!("Reloading {} files from {}", paths.len(), assets_dir.display());
+    for (key, path) in paths {
+        skip_failed(sequencer.add_as(key, &path));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Artifact, Staged, WorldviewError};
+    use std::{
+        collections::HashMap,
+        future::Future,
+        io::BufRead,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+    use tempfile::TempDir;
+    use tokio::task::JoinHandle;
+
+    // A sequencer that only records the files added, for the injectors'
+    // tests.
+    #[derive(Clone, Default)]
+    pub(super) struct Recorder(Arc<Mutex<Vec<PathBuf>>>);
+
+    impl Recorder {
+        // How many times a file of this name was added.
+        pub(super) fn count(&self, name: &str) -> usize {
+            let added = self.0.lock().unwrap();
+            added.iter().filter(|path| path.file_name().unwrap() == name).count()
+        }
+
+        // Wait a while for a file to be added `times` times, as watchers
+        // take their time.  Returns how many times it was.
+        pub(super) async fn wait_for(&self, name: &str, times: usize) -> usize {
+            for _ in 0..100 {
+                if self.count(name) >= times {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            self.count(name)
+        }
+    }
+
+    impl Sequencer for Recorder {
+        fn add(&self, path: &Path) -> Result<Key, WorldviewError> {
+            let key = Key::from_path(path).ok_or_else(|| WorldviewError::NoKey(path.to_path_buf()))?;
+            self.add_as(key, path)
+        }
+        fn add_as(&self, key: Key, path: &Path) -> Result<Key, WorldviewError> {
+            self.0.lock().unwrap().push(path.to_path_buf());
+            Ok(key)
+        }
+        fn add_reader(&self, key: Key, _: &mut dyn BufRead) -> Result<Key, WorldviewError> {
+            Ok(key)
+        }
+        fn add_staged(&self, key: Key, _: Staged) -> Result<Key, WorldviewError> {
+            Ok(key)
+        }
+        fn remove(&self, path: &Path) -> Result<Key, WorldviewError> {
+            Key::from_path(path).ok_or_else(|| WorldviewError::NoKey(path.to_path_buf()))
+        }
+        fn remove_as(&self, key: Key) -> Result<Key, WorldviewError> {
+            Ok(key)
+        }
+        fn get_artifacts(&self) -> Arc<Mutex<HashMap<Key, Artifact>>> {
+            Arc::default()
+        }
+    }
+
+    // A watcher, inotify or notify, running on a temp dir and injecting
+    // into a Recorder.
+    pub(super) struct Watching {
+        dir: TempDir,
+        pub(super) recorder: Recorder,
+        pub(super) rescan: watch::Sender<()>,
+        exit: watch::Sender<bool>,
+        task: JoinHandle<()>,
+    }
+
+    impl Watching {
+        // Start `run` on `dir`, and wait for it to see changes to `probe`:
+        // files written before the watch is in place are missed.  The
+        // probe is not counted.
+        pub(super) async fn start<R, F>(dir: TempDir, probe: &str, run: R) -> Watching
+        where
+            R: FnOnce(PathBuf, Recorder, watch::Sender<bool>, watch::Receiver<bool>, watch::Receiver<()>) -> F,
+            F: Future<Output = ()> + Send + 'static,
+        {
+            let recorder = Recorder::default();
+            let (exit, _) = watch::channel(false);
+            let (_, paused) = watch::channel(false);
+            let (rescan, rescans) = watch::channel(());
+            let task = tokio::spawn(run(dir.path().to_path_buf(), recorder.clone(), exit.clone(), paused, rescans));
+
+            for _ in 0..100 {
+                fs::write(dir.path().join(probe), "").unwrap();
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                if recorder.count(probe) > 0 {
+                    break;
+                }
+            }
+            // Let the events of the last writes settle.
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            recorder.0.lock().unwrap().clear();
+            Watching {
+                dir,
+                recorder,
+                rescan,
+                exit,
+                task,
+            }
+        }
+
+        pub(super) fn path(&self, name: &str) -> PathBuf {
+            self.dir.path().join(name)
+        }
+
+        // The directory outlives the watcher, for inotify's exit sentinel.
+        pub(super) async fn stop(self) {
+            self.exit.send_replace(true);
+            tokio::time::timeout(Duration::from_secs(5), self.task).await.unwrap().unwrap();
+            drop(self.dir);
+        }
+    }
+
+    // The files a rescan adds: those matching --filter, each once.
+    pub(super) async fn rescan_reloads_filtered<R, F>(run: R)
+    where
+        R: FnOnce(PathBuf, bool, Regex, Recorder, watch::Sender<bool>, watch::Receiver<bool>, watch::Receiver<()>) -> F,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["0.kept.ply", "1.kept.ply", "0.left.ply"] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        let filter = Regex::new(r"\.kept\.ply$").unwrap();
+        let watching = Watching::start(dir, "0.probe.ply", |dir, recorder, exit, paused, rescan| {
+            run(dir, false, filter, recorder, exit, paused, rescan)
+        })
+        .await;
+        watching.rescan.send_replace(());
+        let recorder = &watching.recorder;
+        assert_eq!(recorder.wait_for("0.kept.ply", 1).await, 1);
+        assert_eq!(recorder.wait_for("1.kept.ply", 1).await, 1);
+        assert_eq!(recorder.count("0.left.ply"), 0);
+        watching.stop().await;
+    }
+
+    // A watched file written twice is injected twice.
+    pub(super) async fn overwrite_injects_each_time<R, F>(run_file: R)
+    where
+        R: FnOnce(PathBuf, Recorder, watch::Sender<bool>, watch::Receiver<bool>, watch::Receiver<()>) -> F,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let watching = Watching::start(tempfile::tempdir().unwrap(), "live.ply", |dir, recorder, exit, paused, rescan| {
+            run_file(dir.join("live.ply"), recorder, exit, paused, rescan)
+        })
+        .await;
+        for times in 1..=2 {
+            fs::write(watching.path("live.ply"), format!("frame {}", times)).unwrap();
+            assert_eq!(watching.recorder.wait_for("live.ply", times).await, times);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(watching.recorder.count("live.ply"), 2);
+        watching.stop().await;
+    }
+
+    // A file written aside and renamed into the watched directory is
+    // injected once, when it arrives whole.
+    pub(super) async fn rename_injects_once<R, F>(run: R)
+    where
+        R: FnOnce(PathBuf, bool, Regex, Recorder, watch::Sender<bool>, watch::Receiver<bool>, watch::Receiver<()>) -> F,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let filter = Regex::new("").unwrap();
+        let watching = Watching::start(tempfile::tempdir().unwrap(), "0.probe.ply", |dir, recorder, exit, paused, rescan| {
+            run(dir, false, filter, recorder, exit, paused, rescan)
+        })
+        .await;
+        let aside = watching.path("0.cloud.ply.tmp");
+        fs::write(&aside, "whole").unwrap();
+        fs::rename(&aside, watching.path("0.cloud.ply")).unwrap();
+        assert_eq!(watching.recorder.wait_for("0.cloud.ply", 1).await, 1);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(watching.recorder.count("0.cloud.ply"), 1);
+        watching.stop().await;
+    }
+}
@@ -1,60 +1,403 @@
-use crate::{Sequencer, PLY_RE};
+use crate::{
+    error::skip_failed,
+    inject::{list_files, wait_unpaused},
+    Key, Sequencer, Staged,
+};
 use itertools::Itertools;
 use regex::Regex;
-use std::{fs, path::PathBuf, time::Duration};
-use tokio::{sync::watch, time};
+use std::{
+    collections::VecDeque,
+    io,
+    ops::Range,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+use tokio::{sync::watch, task::JoinHandle, time};
 
 // Playback will enumerate a directory of files with delay, simulating
-// some kind of streaming injection.
+// some kind of streaming injection, over and over or just once.
+//
+// Parsing runs ahead of display on the blocking thread pool, up to
+// `threads` files at a time, so large files are decoded concurrently.
+// Results are consumed strictly in order, and only the short upload step
+// goes through the sequencer.
+//
+// Frames are shown at least `delay` apart.  Frames stamped with their
+// capture time ("comment timestamp <seconds>" in the header) are spaced as
+// they were captured instead, sped up by `speed`, so replays keep the
+// original rate.
+//
+// With --recursive, files in the directories below are played too, by
+// the same order.
+//
+// A rescan (the R key) starts a new pass at once, listing the directory
+// again, even after a single pass has finished.
+//
+// --playback-order plays the frames backwards, or forwards and then
+// backwards again.  Played backwards, timestamped frames go back in time,
+// so they are spaced `delay` apart, like frames without timestamps.
+//
+// Space pauses and resumes playback.  While paused, . and , step one
+// frame forward and back, a frame being the files of one instance;
+// stepping back parses the files of the frame before again.  Home starts
+// over from the first frame.  These are checked between frames.
+
+type Parsed = JoinHandle<(PathBuf, io::Result<Option<Staged>>)>;
+
+// What the window asks of playback.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Control {
+    // Pause, or resume if paused.
+    Pause,
+    // Show the next or previous frame, while paused.
+    Forward,
+    Back,
+    // Start over from the first frame.
+    Restart,
+}
+
+// The order frames play in (--playback-order).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Order {
+    // By instance, as sorted.
+    #[default]
+    Forward,
+    // By instance, backwards.
+    Reverse,
+    // Forwards, then back to the first.  Starting over, the first frame is
+    // not shown twice in a row.
+    #[value(name = "pingpong", alias = "ping-pong")]
+    PingPong,
+}
+
+pub struct Options {
+    // Minimum time between frames.
+    pub delay: Duration,
+    // Stop after one pass instead of starting over.
+    pub once: bool,
+    // How much faster than captured timestamped frames are played.
+    pub speed: f64,
+    // Only files whose path matches are played.
+    pub filter: Regex,
+    // Files parsed ahead, concurrently.
+    pub threads: usize,
+    // Also play the files in the directories below.
+    pub recursive: bool,
+    pub order: Order,
+}
 
 pub async fn run(
     assets_dir: PathBuf,
     sequencer: impl Sequencer + Clone,
-    delay: Duration,
-    filter: Regex,
+    options: Options,
     exit: watch::Sender<bool>,
+    mut paused: watch::Receiver<bool>,
+    mut rescan: watch::Receiver<()>,
+    mut control: watch::Receiver<Control>,
 ) {
-    let mut interval = time::interval(delay);
+    let Options {
+        delay,
+        once,
+        speed,
+        filter,
+        threads,
+        recursive,
+        order,
+    } = options;
+    let mut clock = Clock {
+        delay,
+        speed,
+        last: None,
+    };
     let mut exit = exit.subscribe();
-
-    let ply_path_re = Regex::new(PLY_RE).unwrap();
+    // Paused with Space, as opposed to `paused` while the window is
+    // hidden.  It lasts across passes.
+    let mut held = false;
 
     // Iterate through the assets.  Repeat when list is exhausted.
-    loop {
-        for path in fs::read_dir(assets_dir.clone())
-            .expect(&format!("Cannot read dir {}", assets_dir.display()))
-            .map(|entry| entry.unwrap().path())
-            .filter(|path| {
-                // Reject entries that do not match the naming convention.
-                ply_path_re.is_match(path.to_str().unwrap())
-            })
-            .filter(|path| {
-                // Reject entries that do not match user supplied filter.
-                filter.is_match(path.to_str().unwrap())
-            })
-            .sorted()
-        {
-            interval.reset();
+    'pass: loop {
+        rescan.borrow_and_update();
+        let start = Instant::now();
+
+        let sorted: Vec<PathBuf> = match list_files(&assets_dir, recursive) {
+            Ok(paths) => paths
+                .into_iter()
+                .filter(|path| {
+                    // Reject entries whose file name does not match the naming
+                    // convention; directories above it do not count.
+                    Key::from_path(path).is_some()
+                })
+                .filter(|path| {
+                    // Reject entries that do not match user supplied filter.
+                    filter.is_match(&path.to_string_lossy())
+                })
+                .sorted_by_cached_key(|path| {
+                    // Frames play in instance order, numerically where the
+                    // instances are numbers, so 10.x.ply comes after 2.x.ply.
+                    // Within a frame, artifacts play in name order.
+                    let key = Key::from_path(path).map(|key| (key.instance, key.artifact));
+                    (key, path.clone())
+                })
+                .collect(),
+            Err(err) => {
+                log::error!("Cannot read dir {}: {}", assets_dir.display(), err);
+                return;
+            }
+        };
+        let (paths, frames) = arrange(sorted, order, once);
+
+        // Files are parsed in order, ahead of the one shown: the files of
+        // `paths` before `spawned` and after the ones shown so far.
+        let mut parsing: VecDeque<Parsed> = VecDeque::new();
+        let mut spawned = 0;
+        // The next frame to show.
+        let mut frame = 0;
+        let mut files = 0;
+        // While held, a pass still shows its first frame, and a step one
+        // more.
+        let mut step = held;
+        let mut pressed = None;
+        let mut finished = false;
+
+        loop {
+            if control.has_changed().unwrap_or(false) {
+                pressed = Some(*control.borrow_and_update());
+            }
+            match pressed.take() {
+                Some(Control::Pause) => {
+                    held = !held;
+                    log::info!("Playback {}", if held { "paused" } else { "resumed" });
+                }
+                Some(Control::Forward) if held => step = true,
+                // Show the frame before the one on screen again, parsing
+                // it anew; the frames after are parsed again as needed.
+                Some(Control::Back) if held && frame >= 2 => {
+                    frame -= 2;
+                    parsing.clear();
+                    spawned = frames[frame].start;
+                    step = true;
+                }
+                Some(Control::Forward | Control::Back) | None => {}
+                Some(Control::Restart) => continue 'pass,
+            }
+
+            let end = frame == frames.len();
+            if end && !finished {
+                finished = true;
+                log::debug!("Playback pass of {} files took {}ms", files, start.elapsed().as_millis());
+                if !once {
+                    continue 'pass;
+                }
+                log::info!("Playback finished");
+            }
+
+            // Hold the frame on screen until the window closes, or asks for
+            // something else.  Without a window (--headless), nothing can
+            // ask, and playback is over after one pass.
+            if end || (held && !step) {
+                let can_rescan = rescan.has_changed().is_ok();
+                let can_control = control.has_changed().is_ok();
+                if !can_rescan && !can_control {
+                    return;
+                }
+                tokio::select! {
+                    _ = exit.changed() => return,
+                    changed = rescan.changed(), if can_rescan => if changed.is_ok() {
+                        continue 'pass;
+                    },
+                    changed = control.changed(), if can_control => if changed.is_ok() {
+                        pressed = Some(*control.borrow_and_update());
+                    },
+                }
+                continue;
+            }
+
+            // A step is shown at once, not when the clock says.
+            if step {
+                clock.last = None;
+                step = false;
+            }
+            for _ in frames[frame].clone() {
+                while spawned < paths.len() && parsing.len() < threads.max(1) {
+                    parsing.push_back(parse(&sequencer, paths[spawned].clone()));
+                    spawned += 1;
+                }
+                let parsed = parsing.pop_front().unwrap();
+                if wait_unpaused(&mut paused, &mut exit).await
+                    || present(&sequencer, parsed, &mut clock, &mut exit).await
+                {
+                    return;
+                }
+                files += 1;
+            }
+            frame += 1;
+            if rescan.has_changed().unwrap_or(false) {
+                continue 'pass;
+            }
+        }
+    }
+}
+
+// The files of each frame, as ranges of `paths`: those of one instance,
+// next to each other once sorted.
+fn frames(paths: &[PathBuf]) -> Vec<Range<usize>> {
+    let instances: Vec<_> = paths.iter().map(|path| Key::from_path(path).and_then(|key| key.instance)).collect();
+    let mut frames: Vec<Range<usize>> = vec![];
+    for index in 0..paths.len() {
+        match frames.last_mut() {
+            Some(frame) if instances[frame.start] == instances[index] => frame.end = index + 1,
+            _ => frames.push(index..index + 1),
+        }
+    }
+    frames
+}
+
+// The sorted files in the order one pass plays them, and its frames.
+// Within a frame, artifacts still play in name order.  A ping-pong pass
+// plays files twice, so they are listed twice.
+fn arrange(sorted: Vec<PathBuf>, order: Order, once: bool) -> (Vec<PathBuf>, Vec<Range<usize>>) {
+    let frames = frames(&sorted);
+    let played: Vec<Range<usize>> = match order {
+        Order::Forward => return (sorted, frames),
+        Order::Reverse => frames.into_iter().rev().collect(),
+        Order::PingPong => {
+            let back = frames.iter().rev().skip(1);
+            // The next pass starts with the first frame again.
+            let back = back.take(frames.len().saturating_sub(if once { 1 } else { 2 }));
+            frames.iter().chain(back).cloned().collect()
+        }
+    };
+    let mut paths = vec![];
+    let mut frames = vec![];
+    for frame in played {
+        let start = paths.len();
+        paths.extend_from_slice(&sorted[frame]);
+        frames.push(start..paths.len());
+    }
+    (paths, frames)
+}
+
+// Parse one file on the blocking thread pool.
+fn parse(sequencer: &impl Sequencer, path: PathBuf) -> Parsed {
+    let forced = Key::from_path(&path).and_then(|key| sequencer.forced_kind(&key));
+    let keep = sequencer.history().is_some();
+    tokio::task::spawn_blocking(move || {
+        let staged = match keep {
+            true => Staged::from_path_keeping(&path, forced),
+            false => Staged::from_path(&path, forced),
+        };
+        (path, staged)
+    })
+}
+
+// When the next frame is due.
+struct Clock {
+    delay: Duration,
+    speed: f64,
+    // When the last frame was shown, and its timestamp.
+    last: Option<(time::Instant, Option<f64>)>,
+}
+
+impl Clock {
+    // Timestamped frames follow each other by the difference of their
+    // timestamps.  Without both, or when time goes backwards as playback
+    // starts over, they are `delay` apart.
+    fn due(&self, timestamp: Option<f64>) -> time::Instant {
+        match (self.last, timestamp) {
+            (Some((shown, Some(last))), Some(timestamp)) if timestamp >= last => {
+                shown + Duration::from_secs_f64((timestamp - last) / self.speed)
+            }
+            (Some((shown, _)), _) => shown + self.delay,
+            (None, _) => time::Instant::now(),
+        }
+    }
+}
+
+// Wait until one parsed frame is due, then inject it.  Returns true if
+// the process is exiting.
+async fn present(
+    sequencer: &(impl Sequencer + Clone),
+    parsed: Parsed,
+    clock: &mut Clock,
+    exit: &mut watch::Receiver<bool>,
+) -> bool {
+    let (path, staged) = tokio::select! {
+        parsed = parsed => parsed.unwrap(),
+        Ok(_) = exit.changed() => return true,
+    };
+
+    let key = match Key::from_path(&path) {
+        Some(key) => key,
+        None => return false,
+    };
+
+    match staged {
+        Ok(Some(staged)) => {
+            let timestamp = staged.timestamp();
+            tokio::select! {
+                _ = time::sleep_until(clock.due(timestamp)) => {}
+                Ok(_) = exit.changed() => return true,
+            }
+            clock.last = Some((time::Instant::now(), timestamp));
 
             tokio::task::block_in_place({
                 let sequencer = sequencer.clone();
                 move || {
                     // The path is good; inject the artifact.
-                    sequencer.add(&path);
-                    // if sequencer.add(&path).is_none() {
-                    //     continue;
-                    // }
+                    skip_failed(sequencer.add_staged(key, staged));
                 }
             });
+        }
+        Ok(None) => log::debug!("Unknown artifact {}", key),
+        Err(err) => log::warn!("Skipping {}: {}", path.display(), err),
+    }
+    false
+}
 
-            // For each successful injection, implement the delay.
-            tokio::select! {
-                _ = interval.tick() => {}
-                Ok(_) = exit.changed() => {
-                    // Process is exiting.
-                    return
-                }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two frames of two artifacts, then one of a single artifact.
+    fn sorted() -> Vec<PathBuf> {
+        ["0.a.ply", "0.b.ply", "1.a.ply", "1.b.ply", "2.a.ply"].map(PathBuf::from).to_vec()
+    }
+
+    fn names(paths: &[PathBuf]) -> Vec<&str> {
+        paths.iter().map(|path| path.to_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn frames_group_instances() {
+        assert_eq!(frames(&sorted()), [0..2, 2..4, 4..5]);
+    }
+
+    // With --once, forward or in reverse, every file is injected once.
+    #[test]
+    fn once_plays_each_file_once() {
+        for order in [Order::Forward, Order::Reverse] {
+            let (paths, frames) = arrange(sorted(), order, true);
+            for path in sorted() {
+                assert_eq!(paths.iter().filter(|played| **played == path).count(), 1, "{:?}", order);
             }
+            assert_eq!(frames.len(), 3);
+            assert_eq!(frames.last().unwrap().end, paths.len());
         }
     }
+
+    // A ping-pong pass with --once goes out and back once, injecting each
+    // file once each way, the turning frame once, and ends on the first
+    // frame.  Looping, the first frame is left for the next pass.
+    #[test]
+    fn once_pingpongs_once() {
+        let (paths, frames) = arrange(sorted(), Order::PingPong, true);
+        assert_eq!(
+            names(&paths),
+            ["0.a.ply", "0.b.ply", "1.a.ply", "1.b.ply", "2.a.ply", "1.a.ply", "1.b.ply", "0.a.ply", "0.b.ply"]
+        );
+        assert_eq!(frames, [0..2, 2..4, 4..5, 5..7, 7..9]);
+
+        let (paths, _) = arrange(sorted(), Order::PingPong, false);
+        assert_eq!(names(&paths), ["0.a.ply", "0.b.ply", "1.a.ply", "1.b.ply", "2.a.ply", "1.a.ply", "1.b.ply"]);
+    }
 }
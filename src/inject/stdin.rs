@@ -0,0 +1,95 @@
+use crate::{error::skip_failed, inject::wait_unpaused, Key, Sequencer};
+use std::{
+    io::{self, BufRead, Cursor},
+    path::Path,
+    thread,
+};
+use tokio::sync::{mpsc, watch};
+
+// Artifacts piped in on stdin, for quick use from other tools without
+// touching the disk:
+//
+//     generate | worldview -
+//
+// Each frame is the artifact's name on a line of its own, named as the file
+// would be ("12.cloud.ply"; a bare "cloud" has no instance), then the
+// length of the PLY bytes as 4 bytes little-endian, then the bytes.
+//
+// Reading stdin blocks in the kernel, so it runs on a thread of its own,
+// one frame ahead; a fast producer is held back by the pipe filling up.
+// At the end of the input the injector stops and the last frames stay on
+// screen.  On exit the reader thread is left blocked, and ends with the
+// process.
+
+struct Frame {
+    key: Key,
+    ply: Vec<u8>,
+}
+
+// The next frame, or None at the end of the input.
+fn read_frame(stdin: &mut impl BufRead) -> io::Result<Option<Frame>> {
+    let mut name = String::new();
+    if stdin.read_line(&mut name)? == 0 {
+        return Ok(None);
+    }
+    let name = name.trim_end_matches(['\r', '\n']);
+    if name.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "frame without an artifact name"));
+    }
+    let key = Key::from_path(Path::new(name)).unwrap_or_else(|| Key {
+        instance: None,
+        artifact: name.strip_suffix(".ply").unwrap_or(name).to_string(),
+    });
+
+    let mut length = [0; 4];
+    stdin.read_exact(&mut length)?;
+    let mut ply = vec![0; u32::from_le_bytes(length) as usize];
+    stdin.read_exact(&mut ply)?;
+    Ok(Some(Frame { key, ply }))
+}
+
+pub async fn run(
+    sequencer: impl Sequencer + Send + 'static,
+    exit: watch::Sender<bool>,
+    mut paused: watch::Receiver<bool>,
+) {
+    let (sender, mut frames) = mpsc::channel(1);
+    thread::spawn(move || {
+        let mut stdin = io::stdin().lock();
+        loop {
+            let frame = match read_frame(&mut stdin) {
+                Ok(Some(frame)) => frame,
+                Ok(None) => return,
+                // Past a bad frame, the rest of the stream cannot be
+                // trusted to line up.
+                Err(err) => {
+                    log::error!("Cannot read stdin: {}", err);
+                    return;
+                }
+            };
+            if sender.blocking_send(frame).is_err() {
+                return;
+            }
+        }
+    });
+
+    let mut exit = exit.subscribe();
+    loop {
+        if wait_unpaused(&mut paused, &mut exit).await {
+            return;
+        }
+        let frame = tokio::select! {
+            _ = exit.changed() => return,
+            frame = frames.recv() => match frame {
+                Some(frame) => frame,
+                None => {
+                    log::info!("End of stdin");
+                    return;
+                }
+            },
+        };
+        skip_failed(tokio::task::block_in_place(|| {
+            sequencer.add_reader(frame.key, &mut Cursor::new(&frame.ply[..]))
+        }));
+    }
+}
@@ -0,0 +1,220 @@
+use crate::{
+    error::skip_failed,
+    inject::{file_key, reload_dir, spawn_rescan, wait_unpaused},
+    Format, Sequencer,
+};
+use notify::{
+    event::{AccessKind, AccessMode, CreateKind, ModifyKind, RenameMode},
+    Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use tokio::sync::{mpsc, watch};
+
+// The portable watcher, built on the notify crate: inotify on Linux,
+// FSEvents on macOS, ReadDirectoryChangesW on Windows.  Like the inotify
+// injector, it injects new files and removes deleted ones, counting files
+// renamed into and out of the directory as new and deleted.  With
+// --recursive, the directories below are watched too, and a directory that
+// appears has the files already in it added.
+//
+// Only Linux reports when a writer closes a file.  Elsewhere a file is
+// injected on every write, and one caught half written is retried by the
+// sequencer on the next event, until the last write makes it whole.
+const CLOSE_EVENTS: bool = cfg!(target_os = "linux");
+
+pub(crate) enum Change {
+    Written,
+    Removed,
+}
+
+impl Change {
+    pub(crate) fn of(kind: &EventKind, path: &Path) -> Option<Change> {
+        match kind {
+            EventKind::Access(AccessKind::Close(AccessMode::Write)) => Some(Change::Written),
+            // A directory is never closed after writing.
+            EventKind::Create(CreateKind::Folder) => Some(Change::Written),
+            EventKind::Create(_) | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Any) if !CLOSE_EVENTS => {
+                Some(Change::Written)
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::To)) => Some(Change::Written),
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) => Some(Change::Removed),
+            // FSEvents does not say which end of a rename this is.
+            EventKind::Modify(ModifyKind::Name(RenameMode::Any)) => match path.exists() {
+                true => Some(Change::Written),
+                false => Some(Change::Removed),
+            },
+            EventKind::Remove(_) => Some(Change::Removed),
+            _ => None,
+        }
+    }
+}
+
+// Watch `dir`, and if `recursive`, every directory below it, passing each
+// event on to the returned channel.  The watcher stops when dropped.
+pub(crate) fn watch_dir(
+    dir: &Path,
+    recursive: bool,
+) -> notify::Result<(RecommendedWatcher, mpsc::UnboundedReceiver<Event>)> {
+    let (sender, events) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| match event {
+        Ok(event) => {
+            sender.send(event).ok();
+        }
+        Err(err) => log::warn!("Watch error: {}", err),
+    })?;
+    let mode = match recursive {
+        true => RecursiveMode::Recursive,
+        false => RecursiveMode::NonRecursive,
+    };
+    watcher.watch(dir, mode)?;
+    Ok((watcher, events))
+}
+
+// Pass every change to `apply`, until exit.  While paused, events queue
+// up in the channel, to be applied on resume.
+async fn apply_changes(
+    mut events: mpsc::UnboundedReceiver<Event>,
+    exit: watch::Sender<bool>,
+    mut paused: watch::Receiver<bool>,
+    apply: impl Fn(Change, &Path),
+) {
+    let mut exit = exit.subscribe();
+    loop {
+        if wait_unpaused(&mut paused, &mut exit).await {
+            return;
+        }
+        let event = tokio::select! {
+            _ = exit.changed() => return,
+            event = events.recv() => match event {
+                Some(event) => event,
+                None => return,
+            },
+        };
+        for path in &event.paths {
+            if let Some(change) = Change::of(&event.kind, path) {
+                tokio::task::block_in_place(|| apply(change, path));
+            }
+        }
+    }
+}
+
+pub async fn run(
+    assets_dir: PathBuf,
+    recursive: bool,
+    filter: Regex,
+    sequencer: impl Sequencer + Clone + Send + 'static,
+    exit: watch::Sender<bool>,
+    paused: watch::Receiver<bool>,
+    rescan: watch::Receiver<()>,
+) {
+    let (_watcher, events) = match watch_dir(&assets_dir, recursive) {
+        Ok(watching) => watching,
+        Err(err) => {
+            log::error!("Cannot watch {}: {}", assets_dir.display(), err);
+            return;
+        }
+    };
+    spawn_rescan(rescan, paused.clone(), &exit, {
+        let (assets_dir, filter, sequencer) = (assets_dir.clone(), filter.clone(), sequencer.clone());
+        move || reload_dir(&assets_dir, recursive, &filter, &sequencer)
+    });
+
+    apply_changes(events, exit, paused, |change, path| {
+        // The watch covers a new directory only once it exists, so files
+        // written into it before then are added here.
+        if recursive && path.is_dir() {
+            if let Change::Written = change {
+                reload_dir(path, true, &filter, &sequencer);
+            }
+            return;
+        }
+        // Everything but PLY, OBJ and STL files is left alone.
+        if Format::of_path(path).is_none() {
+            return;
+        }
+        skip_failed(match change {
+            Change::Written => sequencer.add(path),
+            Change::Removed => sequencer.remove(path),
+        });
+    })
+    .await
+}
+
+// Watch a single file that the producer overwrites in place, and inject
+// it under a stable key (the file stem) every time it changes.  As for
+// inotify, the watch is on the parent directory, so that replacing the
+// file with an atomic rename() does not end it.
+pub async fn run_file(
+    path: PathBuf,
+    sequencer: impl Sequencer + Clone + Send + 'static,
+    exit: watch::Sender<bool>,
+    paused: watch::Receiver<bool>,
+    rescan: watch::Receiver<()>,
+) {
+    let assets_dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => std::env::current_dir().unwrap(),
+    };
+    let (file_name, key) = match file_key(&path) {
+        Some(named) => named,
+        None => {
+            log::error!("Cannot watch {}: not a file name", path.display());
+            return;
+        }
+    };
+
+    let (_watcher, events) = match watch_dir(&assets_dir, false) {
+        Ok(watching) => watching,
+        Err(err) => {
+            log::error!("Cannot watch {}: {}", assets_dir.display(), err);
+            return;
+        }
+    };
+    spawn_rescan(rescan, paused.clone(), &exit, {
+        let (path, key, sequencer) = (path.clone(), key.clone(), sequencer.clone());
+        move || {
+            if path.exists() {
+                skip_failed(sequencer.add_as(key.clone(), &path));
+            }
+        }
+    });
+
+    // Show whatever is already there before the first change arrives.
+    if path.exists() {
+        skip_failed(sequencer.add_as(key.clone(), &path));
+    }
+
+    apply_changes(events, exit, paused, |change, changed| {
+        // Ignore everything else happening in the directory.
+        if changed.file_name() != Some(file_name.as_os_str()) {
+            return;
+        }
+        skip_failed(match change {
+            Change::Written => sequencer.add_as(key.clone(), &path),
+            Change::Removed => sequencer.remove_as(key.clone()),
+        });
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inject::tests::{overwrite_injects_each_time, rename_injects_once, rescan_reloads_filtered};
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rescan_reloads_filtered_files() {
+        rescan_reloads_filtered(run).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn overwritten_file_is_injected_each_time() {
+        overwrite_injects_each_time(run_file).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn renamed_file_is_injected_once() {
+        rename_injects_once(run).await;
+    }
+}
@@ -0,0 +1,207 @@
+use crate::{error::skip_failed, inject::wait_unpaused, Format, Instance, Key, Sequencer, Staged};
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use std::{io::Cursor, net::SocketAddr, sync::Arc};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::watch,
+};
+use tokio_tungstenite::tungstenite::{protocol::WebSocketConfig, Message};
+
+// A WebSocket server, for producers that stream frames but cannot speak
+// gRPC, such as a web page:
+//
+//     const socket = new WebSocket("ws://localhost:8765");
+//     const header = JSON.stringify({artifact: "cloud", instance: 12});
+//     socket.send(new Blob([header + "\n", ply]));
+//
+// Each binary message is one frame: a JSON header line naming the
+// artifact, and optionally its instance, as "12.cloud.ply" would, then
+// the PLY bytes.  Text messages are commands; "clear" removes every
+// artifact shown.  Each message is answered with a text message saying
+// what came of it, starting with "error:" when nothing on screen changed.
+//
+// Any number of clients may be connected.  Each is read a message at a
+// time, and only after the previous one is shown, so a fast producer is
+// held back by TCP rather than queued here.  While injection is paused,
+// clients are not read at all.
+
+// Larger than any frame a producer should send.  tungstenite's default,
+// 64 MiB, is too small for dense point clouds.
+const MAX_FRAME: usize = 1 << 30;
+
+struct Feed<S> {
+    sequencer: S,
+    exit: watch::Sender<bool>,
+    paused: watch::Receiver<bool>,
+}
+
+#[derive(Deserialize)]
+struct Header {
+    artifact: String,
+    instance: Option<InstanceName>,
+}
+
+// The instance may be given as a number or a string; 12 and "12" are
+// the same frame.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum InstanceName {
+    Number(u64),
+    Name(String),
+}
+
+impl Header {
+    fn key(self) -> Key {
+        let instance = self.instance.map(|instance| match instance {
+            InstanceName::Number(number) => Instance::from(number.to_string().as_str()),
+            InstanceName::Name(name) => Instance::from(name.as_str()),
+        });
+        Key {
+            instance,
+            artifact: self.artifact,
+        }
+    }
+}
+
+impl<S: Sequencer> Feed<S> {
+    // Show one frame, if it parses.
+    fn show(&self, message: &[u8]) -> Result<String, String> {
+        let newline = message
+            .iter()
+            .position(|byte| *byte == b'\n')
+            .ok_or("expected a JSON header line before the PLY bytes")?;
+        let key = match serde_json::from_slice::<Header>(&message[..newline]) {
+            Ok(header) if header.artifact.is_empty() => return Err("frame without an artifact name".to_string()),
+            Ok(header) => header.key(),
+            Err(err) => return Err(format!("bad header: {}", err)),
+        };
+
+        let ply = &message[newline + 1..];
+        let sequencer = &self.sequencer;
+        let forced = sequencer.forced_kind(&key);
+        let staged = match sequencer.history() {
+            Some(_) => Staged::from_bytes(ply.to_vec().into(), Format::Ply, forced),
+            None => Staged::read_ply(&mut Cursor::new(ply), forced),
+        };
+        match staged {
+            Ok(Some(staged)) => match sequencer.add_staged(key.clone(), staged) {
+                Ok(key) => Ok(format!("Showing {}", key)),
+                Err(err) => {
+                    log::warn!("Rejected frame of {}: {}", key, err);
+                    Err(err.to_string())
+                }
+            },
+            Ok(None) => Err(format!("{}: not a point cloud, wireframe or mesh", key)),
+            Err(err) => {
+                log::warn!("Rejected frame of {}: {}", key, err);
+                Err(format!("{}: {}", key, err))
+            }
+        }
+    }
+
+    fn command(&self, command: &str) -> Result<String, String> {
+        match command.trim() {
+            "clear" => {
+                let keys: Vec<Key> = self.sequencer.get_artifacts().lock().unwrap().keys().cloned().collect();
+                for key in &keys {
+                    skip_failed(self.sequencer.remove_as(key.clone()));
+                }
+                Ok(format!("Removed {} artifacts", keys.len()))
+            }
+            command => Err(format!("unknown command '{}'; the only one is \"clear\"", command)),
+        }
+    }
+}
+
+async fn serve<S: Sequencer + Send + Sync + 'static>(feed: Arc<Feed<S>>, stream: TcpStream, peer: SocketAddr) {
+    let config = WebSocketConfig {
+        max_message_size: Some(MAX_FRAME),
+        max_frame_size: Some(MAX_FRAME),
+        ..Default::default()
+    };
+    let mut socket = match tokio_tungstenite::accept_async_with_config(stream, Some(config)).await {
+        Ok(socket) => socket,
+        Err(err) => {
+            log::warn!("{}: not a WebSocket client: {}", peer, err);
+            return;
+        }
+    };
+    log::info!("{} connected", peer);
+    let mut exit = feed.exit.subscribe();
+    let mut paused = feed.paused.clone();
+    let (mut accepted, mut rejected) = (0, 0);
+
+    loop {
+        if wait_unpaused(&mut paused, &mut exit).await {
+            return;
+        }
+        let message = tokio::select! {
+            _ = exit.changed() => return,
+            message = socket.next() => message,
+        };
+        let reply = match message {
+            Some(Ok(Message::Binary(frame))) => {
+                let shown = tokio::task::block_in_place(|| feed.show(&frame));
+                match shown {
+                    Ok(_) => accepted += 1,
+                    Err(_) => rejected += 1,
+                }
+                shown
+            }
+            Some(Ok(Message::Text(command))) => tokio::task::block_in_place(|| feed.command(&command)),
+            // tungstenite answers pings itself, and a close ends the
+            // stream next.
+            Some(Ok(_)) => continue,
+            None => break,
+            // Most likely the client went away mid-message.
+            Some(Err(err)) => {
+                log::warn!("{} disconnected: {}", peer, err);
+                return;
+            }
+        };
+        let reply = reply.unwrap_or_else(|err| format!("error: {}", err));
+        if let Err(err) = socket.send(Message::Text(reply)).await {
+            log::warn!("{} disconnected: {}", peer, err);
+            return;
+        }
+    }
+
+    log::info!("{} done: {} frames shown, {} rejected", peer, accepted, rejected);
+}
+
+pub async fn run<S: Sequencer + Send + Sync + 'static>(
+    addr: SocketAddr,
+    sequencer: S,
+    exit: watch::Sender<bool>,
+    paused: watch::Receiver<bool>,
+) {
+    let listener = match TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            log::error!("Cannot serve WebSocket on {}: {}", addr, err);
+            return;
+        }
+    };
+    let mut shutdown = exit.subscribe();
+    let feed = Arc::new(Feed {
+        sequencer,
+        exit,
+        paused,
+    });
+
+    // Clients are served concurrently; each ends on exit by itself.
+    loop {
+        let (stream, peer) = tokio::select! {
+            _ = shutdown.changed() => return,
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    log::warn!("Cannot accept a WebSocket client: {}", err);
+                    continue;
+                }
+            },
+        };
+        tokio::spawn(serve(feed.clone(), stream, peer));
+    }
+}
@@ -0,0 +1,47 @@
+use crate::{read_header, ArtifactKind, Element};
+use std::{fs::File, io, io::BufReader, path::Path};
+
+// Explain how worldview sees a PLY file, without opening a window: the
+// header contents and which artifact type, if any, it would be shown as.
+// This uses the same detection as injection, so a file that renders as
+// nothing can be diagnosed here.
+pub fn inspect(path: &Path) -> io::Result<()> {
+    let mut f = BufReader::new(File::open(path)?);
+    let header = read_header(&mut f)?;
+
+    println!("{}", path.display());
+    println!("  format {} {}", header.encoding, header.version);
+    for comment in &header.comments {
+        println!("  comment {}", comment);
+    }
+    for obj_info in &header.obj_infos {
+        println!("  obj_info {}", obj_info);
+    }
+
+    for (name, element) in &header.elements {
+        let note = match Element::from(name) {
+            Some(_) => "",
+            None => " (unknown element, skipping)",
+        };
+        println!("  element {} x{}{}", name, element.count, note);
+        for (name, property) in &element.properties {
+            println!("    {:?} {}", property.data_type, name);
+        }
+    }
+
+    match ArtifactKind::resolve(&header, None) {
+        Ok(kind) => {
+            let elements = header
+                .elements
+                .keys()
+                .filter(|name| Element::from(name).is_some())
+                .map(|name| name.as_str())
+                .collect::<Vec<_>>()
+                .join(" + ");
+            println!("  {} -> {:?}", elements, kind);
+        }
+        Err(reason) => println!("  not shown: {}", reason),
+    }
+
+    Ok(())
+}
@@ -1,18 +1,123 @@
-use std::fmt;
+use crate::{OBJ_RE, PLY_RE, STL_RE};
+use regex::Regex;
+use std::{fmt, path::Path, sync::OnceLock};
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Key {
-    pub instance: Option<u32>, // Frame number, or tile hash
+    pub instance: Option<Instance>, // Frame number, or tile hash
     pub artifact: String,
 }
 
+// Producers number their frames, or name them by a hash or tile ID.
+// Numbers sort numerically, and before any names.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Instance {
+    Number(u32),
+    Name(String),
+}
+
+impl Key {
+    // Parse the key out of a file name following the PLY_RE convention,
+    // i.e. "<instance>.<artifact>.ply", or OBJ_RE or STL_RE, where the
+    // instance is optional.  STL files name nothing inside them, so the
+    // artifact comes from the file name alone.
+    pub fn from_path(path: &Path) -> Option<Key> {
+        static PLY_PATH_RE: OnceLock<Regex> = OnceLock::new();
+        static OBJ_PATH_RE: OnceLock<Regex> = OnceLock::new();
+        static STL_PATH_RE: OnceLock<Regex> = OnceLock::new();
+        let ply = PLY_PATH_RE.get_or_init(|| Regex::new(PLY_RE).expect("invalid regex"));
+        let obj = OBJ_PATH_RE.get_or_init(|| Regex::new(OBJ_RE).expect("invalid regex"));
+        let stl = STL_PATH_RE.get_or_init(|| Regex::new(STL_RE).expect("invalid regex"));
+
+        let filename = path.file_name()?.to_str()?;
+        let capture = ply.captures(filename).or_else(|| obj.captures(filename)).or_else(|| stl.captures(filename))?;
+        Some(Key {
+            instance: capture.name("instance").map(|instance| Instance::from(instance.as_str())),
+            artifact: capture["artifact"].to_string(),
+        })
+    }
+}
+
+impl From<&str> for Instance {
+    fn from(s: &str) -> Self {
+        match s.parse::<u32>() {
+            Ok(u) => Instance::Number(u),
+            Err(_) => Instance::Name(s.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Instance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instance::Number(u) => write!(f, "{}", u),
+            Instance::Name(name) => write!(f, "{}", name),
+        }
+    }
+}
+
 impl fmt::Display for Key {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self.instance {
-            Some(u) => write!(f, "{}[{}]", self.artifact, u),
+        match &self.instance {
+            Some(instance) => write!(f, "{}[{}]", self.artifact, instance),
             None => write!(f, "{}", self.artifact)
         }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    fn key(path: &str) -> Option<Key> {
+        Key::from_path(Path::new(path))
+    }
+
+    #[test]
+    fn from_path() {
+        assert_eq!(
+            key("/data/12.cloud.ply"),
+            Some(Key {
+                instance: Some(Instance::Number(12)),
+                artifact: "cloud".to_string(),
+            })
+        );
+        assert_eq!(
+            key("a3f9.front.mesh.ply"),
+            Some(Key {
+                instance: Some(Instance::Name("a3f9".to_string())),
+                artifact: "front.mesh".to_string(),
+            })
+        );
+        assert_eq!(
+            key("cube.obj"),
+            Some(Key {
+                instance: None,
+                artifact: "cube".to_string(),
+            })
+        );
+        assert_eq!(
+            key("7.bracket.STL"),
+            Some(Key {
+                instance: Some(Instance::Number(7)),
+                artifact: "bracket".to_string(),
+            })
+        );
+        // PLY files need an instance.
+        assert_eq!(key("cloud.ply"), None);
+        assert_eq!(key("notes.txt"), None);
+    }
+
+    #[test]
+    fn numbers_sort_numerically_before_names() {
+        let mut instances: Vec<Instance> = ["10", "b", "9", "a", "100", "2"].into_iter().map(Instance::from).collect();
+        instances.sort();
+        let sorted: Vec<String> = instances.iter().map(Instance::to_string).collect();
+        assert_eq!(sorted, ["2", "9", "10", "100", "a", "b"]);
+    }
+
+    #[test]
+    fn leading_zeros_are_the_same_frame() {
+        assert_eq!(Instance::from("007"), Instance::from("7"));
+    }
+}
@@ -0,0 +1,704 @@
+use clap::Subcommand;
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    future::Future,
+    net::SocketAddr,
+    num::ParseIntError,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::{mpsc, watch};
+use winit::event_loop::{EventLoop, EventLoopProxy};
+
+mod artifact;
+mod camera;
+mod capture;
+mod colormap;
+mod element;
+mod error;
+mod events;
+mod fog;
+mod headless;
+mod history;
+pub mod inject;
+mod inspect;
+mod key;
+mod model;
+mod obj;
+mod overlay;
+mod palette;
+mod pipeline;
+mod pool;
+mod profile;
+mod progress;
+mod reference;
+pub mod sequence;
+mod settings;
+mod shortcut;
+mod stl;
+mod texture;
+mod transform;
+mod upload;
+mod viewer;
+mod vram;
+mod window;
+
+pub use artifact::{
+    Artifact, ArtifactKind, ArtifactUniform, AlphaOverride, ColorOverride, EdgeColor, Format, KindOverride, RenderArtifact, RenderMode, Staged,
+};
+pub use capture::Orbit;
+pub use colormap::{Colormap, ColormapAxis};
+pub use camera::{Camera, CameraController, CameraUniform, Projection, ProjectionKind, SavedView, UpAxis, View};
+pub use element::{read_header, Element, IntoElement};
+pub use error::WorldviewError;
+pub use fog::{Fog, FogUniform};
+pub use history::History;
+pub use inject::{grpc, http, live, playback, stdin, websocket};
+#[cfg(target_os = "linux")]
+pub use inject::inotify;
+pub use inspect::inspect;
+pub use key::{Instance, Key};
+pub use overlay::Corner;
+pub use sequence::{Sequencer, SequencerKind};
+pub use settings::Settings;
+pub use texture::Texture;
+pub use transform::Transforms;
+pub use viewer::Viewer;
+pub use window::{Backend, WindowState};
+
+// Visualized artifacts (PLY files, or OBJ and STL from files) must come from somewhere, and we have
+// different use cases.  For now, we support dependency injection from
+// the filesystem, either as "playback" or watching live (portably through
+// the notify crate, or through Linux inotify), over the network from a
+// gRPC server, one-shot HTTP uploads or a WebSocket stream, and piped
+// in on stdin.
+#[derive(Clone, Debug, Subcommand)]
+pub enum DependencyInjector {
+    /// Worldview: Enumerate pre-existing directory
+    Playback {
+        /// Playback directory of PLY, OBJ and STL files
+        path: PathBuf,
+        /// Inject a minimum delay between each frame (milliseconds).
+        /// Frames stamped "comment timestamp SECONDS" are spaced as they
+        /// were captured instead.
+        #[clap(value_parser = parse_milliseconds, default_value="100")]
+        delay: Duration,
+        /// Play the directory once, then hold the last frame, instead of
+        /// starting over.
+        #[clap(long)]
+        once: bool,
+        /// Play timestamped frames this many times faster than they were
+        /// captured.
+        #[clap(long, value_parser = parse_speed, default_value = "1")]
+        speed: f64,
+        /// Play the frames forward, in reverse, or forward and back again
+        /// (pingpong).
+        #[clap(long = "playback-order", value_name = "ORDER", value_enum, default_value = "forward")]
+        order: playback::Order,
+    },
+    /// Worldview: Watch a directory live for new and deleted files
+    /// (default)
+    Notify { path: Option<PathBuf> },
+    /// Worldview: Serve gRPC, for producers to push PLY frames over the
+    /// network (proto/worldview.proto)
+    Grpc {
+        /// Address to listen on
+        #[clap(default_value = "127.0.0.1:50051")]
+        addr: SocketAddr,
+    },
+    /// Worldview: Serve HTTP, taking one PLY file per request: POST
+    /// /push/NAME shows the body, DELETE /push/NAME removes it
+    Http {
+        /// Address to listen on
+        #[clap(default_value = "127.0.0.1:8080")]
+        addr: SocketAddr,
+    },
+    /// Worldview: Serve WebSocket, for producers such as web pages to
+    /// stream PLY frames, each a JSON header line {"artifact": NAME,
+    /// "instance": N} then the PLY bytes; the text message "clear"
+    /// removes everything shown
+    #[clap(name = "websocket", alias = "ws")]
+    WebSocket {
+        /// Address to listen on
+        #[clap(default_value = "127.0.0.1:8765")]
+        addr: SocketAddr,
+    },
+    /// Worldview: Read PLY frames piped in on stdin, each an artifact name
+    /// line, a 4-byte little-endian length, then the PLY bytes
+    #[clap(alias = "-")]
+    Stdin,
+}
+
+// Everything needed to run the viewer.  The binary fills this from the
+// command line; library users build it directly.
+#[derive(Clone, Debug)]
+pub struct WorldviewConfig {
+    /// Enabled artifact types.  An entry NAME:vertices also loads only
+    /// the vertices of NAME's files, as a point cloud.  Default: no
+    /// filter.
+    pub filter: Option<Vec<String>>,
+    /// Watch a single file that is overwritten in place, instead of a
+    /// directory.  Takes precedence over the injector.
+    pub watch_file: Option<PathBuf>,
+    /// Where artifacts come from.  Default: inotify on the CWD.
+    pub injector: Option<DependencyInjector>,
+    /// How many files playback parses ahead, concurrently.
+    pub threads: usize,
+    /// Which world axis points up.  Default: Y.
+    pub up: UpAxis,
+    /// Redraw every frame instead of only when something changes.
+    pub continuous: bool,
+    /// Artifact types forced instead of detected from the PLY header.
+    pub kind_overrides: Vec<KindOverride>,
+    /// How wireframe edges are colored.  Default: uniform.
+    pub edge_color: EdgeColor,
+    /// Draw the edges of filled meshes over them.
+    pub edges: bool,
+    /// Draw a grid on the ground plane, with lines this far apart.
+    /// Default: no grid.
+    pub grid: Option<f32>,
+    /// How far the grid reaches from the origin along each axis.
+    /// Default: 10.
+    pub grid_extent: f32,
+    /// How far filled meshes are pushed back in depth, for the lines on
+    /// them to show.  Default: 2.
+    pub depth_bias: i32,
+    /// Draw the coordinate axes at the origin.
+    pub axes: bool,
+    /// Decimate point clouds to at most this many points.  Default: all.
+    pub max_points: Option<usize>,
+    /// Show a second pane side by side, fed from this path by the same
+    /// kind of injector.  Default: a single pane.
+    pub split: Option<PathBuf>,
+    /// Color point clouds by height through this colormap.  Default:
+    /// off, each artifact in one color.
+    pub colormap: Option<Colormap>,
+    /// The coordinate --colormap colors by.  Default: z.
+    pub colormap_axis: ColormapAxis,
+    /// Where the colormap legend is shown, if at all.  Default: top right.
+    pub legend: Option<Corner>,
+    /// Vertical field of view in degrees, 10 to 120.  Default: 45.
+    pub fov: f32,
+    /// Width of the squares drawn for points, in pixels, 1 to 32.
+    /// Default: 2.
+    pub point_size: f32,
+    /// Clip planes, as distances from the camera.  Default: 0.1 to 100.
+    pub near: f32,
+    pub far: f32,
+    /// Fade fragments into fog with distance.  Default: no fog.
+    pub fog: Option<Fog>,
+    /// Display settings: filter, colors, background and per-artifact
+    /// placement in the world.  Default: show everything as is.
+    pub settings: Settings,
+    /// Where the settings came from, to reload them when it changes.
+    pub settings_path: Option<PathBuf>,
+    /// Merge mesh vertices closer than this.  Default: no welding.
+    pub weld: Option<f32>,
+    /// Time each artifact's draw calls on the GPU, if the adapter can.
+    pub profile: bool,
+    /// Graphics APIs to try.  Default: those in WGPU_BACKEND, or all.
+    pub backends: Vec<Backend>,
+    /// Stop injecting while the window is minimized or covered, instead
+    /// of loading in the background.
+    pub pause_hidden: bool,
+    /// Move the first vertex loaded, and all the others with it, to the
+    /// origin, to keep precision far from it.
+    pub recenter: bool,
+    /// Image draped over meshes with texture coordinates.  Default:
+    /// meshes are flat colored.
+    pub texture: Option<Texture>,
+    /// Evict the oldest artifacts to keep their GPU buffers under this
+    /// many bytes.  Default: no limit.
+    pub vram_limit: Option<u64>,
+    /// Capture a turntable image sequence, then exit.  Default: run
+    /// interactively.
+    pub orbit: Option<Orbit>,
+    /// Where the S key saves screenshots.  Default: the CWD.
+    pub screenshot_dir: PathBuf,
+    /// Keep this many recently injected files in memory, to step back
+    /// through.  Default: 0, none.
+    pub history: usize,
+    /// Open the window on this monitor, by index.  Default: wherever the
+    /// OS puts it.
+    pub monitor: Option<usize>,
+    /// Size the window and overlay by this scale factor, instead of the
+    /// monitor's.  Default: the monitor's.
+    pub scale: Option<f32>,
+    /// Window size in pixels, before the scale factor.  Default: up to
+    /// the OS, or 1024x768 if either or the scale is given.
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Window title.  Default: the platform's.
+    pub title: Option<String>,
+    /// Print every injection and removal to stdout as a line of JSON.
+    pub events_out: bool,
+    /// Artifact names drawn first, in this order; the rest follow by
+    /// name.  Default: all by name.
+    pub layer_order: Vec<String>,
+    /// Opacity by artifact name, blending the artifact over what is
+    /// drawn before it.  Default: the alpha of the artifact's color.
+    pub alphas: Vec<AlphaOverride>,
+    /// Colors by artifact name, over the settings file's.  Default: from
+    /// the palette.
+    pub colors: Vec<ColorOverride>,
+    /// Color artifacts without a configured color by a hue hashed from
+    /// their name, instead of one color per artifact type.  Default: on.
+    pub palette: bool,
+    /// How fast dragging the mouse turns the camera.  Default: 0.5.
+    pub sensitivity: f32,
+    /// Reverse the horizontal or vertical turn of a mouse drag.
+    pub invert_x: bool,
+    pub invert_y: bool,
+    /// Ease the camera into each turn and zoom: the share of the motion
+    /// still to come after 1/60 s, below 1.  Default: none.
+    pub smooth: Option<f32>,
+    /// Whether a new instance of an artifact replaces the one shown, or
+    /// joins it.  Default: replace.
+    pub sequencer: SequencerKind,
+    /// How many instances of each artifact the ring buffer sequencer
+    /// keeps.  Default: 30.
+    pub ring_size: usize,
+    /// Also watch or play the directories below the given one, including
+    /// those created later.
+    pub recursive: bool,
+    /// Render without a window, saving a numbered PNG into this directory
+    /// whenever artifacts come or go, and exit once the injectors are
+    /// done.  Default: open a window.
+    pub headless: Option<PathBuf>,
+    /// JSON file of a saved view, to start in, and for the V key to save
+    /// to and Shift+V to restore.  Default: the keys use camera.json in
+    /// the CWD, and the view starts as usual.
+    pub camera: Option<PathBuf>,
+}
+
+impl Default for WorldviewConfig {
+    fn default() -> Self {
+        Self {
+            filter: None,
+            watch_file: None,
+            injector: None,
+            threads: 8,
+            up: UpAxis::default(),
+            continuous: false,
+            kind_overrides: vec![],
+            edge_color: EdgeColor::default(),
+            edges: false,
+            grid: None,
+            grid_extent: reference::DEFAULT_GRID_EXTENT,
+            depth_bias: pipeline::DEFAULT_DEPTH_BIAS,
+            axes: false,
+            max_points: None,
+            split: None,
+            colormap: None,
+            colormap_axis: ColormapAxis::default(),
+            legend: Some(Corner::default()),
+            fov: camera::DEFAULT_FOVY,
+            point_size: pipeline::point_cloud::DEFAULT_POINT_SIZE,
+            near: camera::DEFAULT_ZNEAR,
+            far: camera::DEFAULT_ZFAR,
+            fog: None,
+            settings: Settings::default(),
+            settings_path: None,
+            weld: None,
+            profile: false,
+            backends: vec![],
+            pause_hidden: false,
+            recenter: false,
+            texture: None,
+            vram_limit: None,
+            orbit: None,
+            screenshot_dir: PathBuf::from("."),
+            history: 0,
+            monitor: None,
+            scale: None,
+            width: None,
+            height: None,
+            title: None,
+            events_out: false,
+            layer_order: vec![],
+            alphas: vec![],
+            colors: vec![],
+            palette: true,
+            sensitivity: camera::DEFAULT_SENSITIVITY,
+            invert_x: false,
+            invert_y: false,
+            smooth: None,
+            sequencer: SequencerKind::default(),
+            ring_size: 30,
+            recursive: false,
+            headless: None,
+            camera: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum InjectionEvent {
+    Add(Key),
+    Remove(Key),
+    // The --config file was edited.
+    Settings(Box<Settings>),
+}
+
+// How the sequencers tell the viewer that something changed: through the
+// winit event loop, or without a window, a channel to the headless loop.
+#[derive(Clone)]
+pub enum EventProxy {
+    Window(EventLoopProxy<InjectionEvent>),
+    Headless(mpsc::UnboundedSender<InjectionEvent>),
+}
+
+impl EventProxy {
+    // Fails, returning the event, once the viewer has stopped.
+    pub fn send_event(&self, event: InjectionEvent) -> Result<(), InjectionEvent> {
+        match self {
+            EventProxy::Window(proxy) => proxy.send_event(event).map_err(|err| err.0),
+            EventProxy::Headless(sender) => sender.send(event).map_err(|err| err.0),
+        }
+    }
+}
+
+// Where the viewer shows artifacts: a window, or with --headless, PNG
+// files fed by the channel.  There is only ever one, so its size does
+// not matter.
+#[allow(clippy::large_enum_variant)]
+enum Display {
+    Window(EventLoop<InjectionEvent>),
+    Headless(mpsc::UnboundedSender<InjectionEvent>, mpsc::UnboundedReceiver<InjectionEvent>),
+}
+
+impl Display {
+    fn proxy(&self) -> EventProxy {
+        match self {
+            Display::Window(event_loop) => EventProxy::Window(event_loop.create_proxy()),
+            Display::Headless(sender, _) => EventProxy::Headless(sender.clone()),
+        }
+    }
+}
+
+pub type ArtifactsLock = Arc<Mutex<HashMap<Key, Artifact>>>;
+pub const PLY_RE: &str = r"(?<instance>[A-Za-z0-9]+)\.(?<artifact>.+)\.ply";
+// OBJ files are named the same way, but meshing tools seldom number
+// their output, so the instance may be left out: "cube.obj".
+pub const OBJ_RE: &str = r"^(?:(?<instance>[A-Za-z0-9]+)\.)?(?<artifact>.+)\.obj$";
+// And so are STL files, whatever the case of the extension: "bracket.STL".
+pub const STL_RE: &str = r"^(?:(?<instance>[A-Za-z0-9]+)\.)?(?<artifact>.+)\.(?:stl|STL)$";
+
+async fn run_dependency_injection<S: Sequencer + Clone + Send + Sync + 'static>(
+    config: &WorldviewConfig,
+    sequencer: S,
+    exit: watch::Sender<bool>,
+    paused: watch::Receiver<bool>,
+    rescan: watch::Receiver<()>,
+    control: watch::Receiver<playback::Control>,
+) {
+    let cwd = std::env::current_dir().unwrap();
+
+    // Set up a command-line configureable filter, to inject only
+    // some artifacts into the renderer.  That can significantly speed up
+    // and de-clutter the display, if calculations are dropping too many
+    // types of artifacts.
+    let filter = Regex::new(&format!(
+        "({})",
+        config.filter.clone().unwrap_or(vec![]).join("|")
+    ))
+    .unwrap();
+
+    if let Some(path) = config.watch_file.clone() {
+        log::info!("Notify from file {}", path.display());
+        return live::run_file(path, sequencer, exit, paused, rescan).await;
+    }
+
+    match config.injector.clone() {
+        Some(DependencyInjector::Playback { path, delay, once, speed, order }) => {
+            log::info!(
+                "Playback from {}; min refresh {}ms",
+                path.display(),
+                delay.as_millis()
+            );
+            let options = playback::Options {
+                delay,
+                once,
+                speed,
+                filter,
+                threads: config.threads,
+                recursive: config.recursive,
+                order,
+            };
+            playback::run(path, sequencer, options, exit, paused, rescan, control).await
+        }
+        Some(DependencyInjector::Notify { path }) => {
+            let path = path.clone().unwrap_or(cwd);
+            log::info!("Notify from {}", path.display());
+            live::run(path, config.recursive, filter, sequencer, exit, paused, rescan).await
+        }
+        // Network injectors have no directory to load again.
+        Some(DependencyInjector::Grpc { addr }) => {
+            log::info!("gRPC on {}", addr);
+            grpc::run(addr, sequencer, exit, paused).await
+        }
+        Some(DependencyInjector::Http { addr }) => {
+            log::info!("HTTP on {}", addr);
+            http::run(addr, sequencer, exit, paused).await
+        }
+        Some(DependencyInjector::WebSocket { addr }) => {
+            log::info!("WebSocket on {}", addr);
+            websocket::run(addr, sequencer, exit, paused).await
+        }
+        Some(DependencyInjector::Stdin) => {
+            log::info!("Reading stdin");
+            stdin::run(sequencer, exit, paused).await
+        }
+        None => {
+            log::info!("Notify from CWD ({})", cwd.display());
+            live::run(cwd, config.recursive, filter, sequencer, exit, paused, rescan).await
+        }
+    }
+}
+
+impl WorldviewConfig {
+    // Filter entries may name an element after the artifact, "cloud:vertices",
+    // to restrict which elements of the file are read.  The file filter
+    // sees only the artifact name.  Vertices alone are a point cloud, so
+    // they force that type, as --as pointcloud:cloud would, and the facets
+    // are never parsed; an explicit --as still wins.  Faces need their
+    // vertices, so "cloud:faces" loads the whole file.  Entries whose
+    // suffix is not an element are left to the regex.
+    fn resolve_element_filters(mut self) -> WorldviewConfig {
+        let filter = match self.filter.take() {
+            Some(filter) => filter,
+            None => return self,
+        };
+        let mut names = Vec::with_capacity(filter.len());
+        for entry in filter {
+            let (name, element) = match entry.rsplit_once(':') {
+                Some((name, element)) => (name, Element::from(element)),
+                None => (entry.as_str(), None),
+            };
+            match element {
+                Some(Element::Vertex) => self.kind_overrides.push(KindOverride {
+                    kind: ArtifactKind::PointCloud,
+                    artifact: Some(name.to_string()),
+                }),
+                Some(Element::Facet) => {}
+                None => {
+                    names.push(entry);
+                    continue;
+                }
+            }
+            names.push(name.to_string());
+        }
+        self.filter = Some(names);
+        self
+    }
+}
+
+// The configuration for the second pane in split mode: the same kind of
+// injector as the first pane, reading from the split path instead.  The
+// split path is a directory, so next to a network server it is watched.
+fn split_config(config: &WorldviewConfig, path: PathBuf) -> WorldviewConfig {
+    let injector = match &config.injector {
+        Some(DependencyInjector::Playback { delay, once, speed, order, .. }) => DependencyInjector::Playback {
+            path,
+            delay: *delay,
+            once: *once,
+            speed: *speed,
+            order: *order,
+        },
+        _ => DependencyInjector::Notify { path: Some(path) },
+    };
+    WorldviewConfig {
+        watch_file: None,
+        injector: Some(injector),
+        split: None,
+        ..config.clone()
+    }
+}
+
+// Open the viewer and run until the window closes.  Must be called from
+// the main thread, inside a multi-threaded tokio runtime.
+pub async fn run(config: WorldviewConfig) {
+    run_with(config, |_| async {}).await
+}
+
+// Like run(), but also spawn a producer that pushes artifacts directly
+// from memory through the Viewer handle, alongside the configured
+// injector.
+pub async fn run_with<F, Fut>(config: WorldviewConfig, producer: F)
+where
+    F: FnOnce(Viewer) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let config = config.resolve_element_filters();
+    if config.recenter {
+        model::origin::enable();
+    }
+
+    // Connect to operating system window management (via winit).  The
+    // InjectionEvent will be sent to the GUI thread, from the dependency
+    // injection thread, to trigger Vulcan refresh.  Headless, there is no
+    // window, and the GPU comes up first instead, so that nothing injected
+    // finds it missing.
+    let display = match config.headless {
+        None => Display::Window(EventLoop::<InjectionEvent>::with_user_event().build().unwrap()),
+        Some(_) => {
+            if let Err(err) = window::start_offscreen_gpu(&config.backends, config.profile).await {
+                log::error!("{}", err);
+                std::process::exit(1);
+            }
+            let (sender, events) = mpsc::unbounded_channel();
+            Display::Headless(sender, events)
+        }
+    };
+
+    // The policy when (or if) artifacts get ejected is implemented in the
+    // sequencer: "replace" (just show the newest instance of each
+    // artifact), "accumulate" (show all the artifacts from all time) or
+    // "ring-buffer" (show the newest few of each).
+    // It seems to be impossible to use dynamic dispatch into a tokio
+    // thread ('static + Send), so use static dispatch for the sequencer:
+    // the rest of the viewer is generic over it.
+    match config.sequencer {
+        SequencerKind::Replace => run_sequenced(config, producer, display, sequence::Replace::new).await,
+        SequencerKind::Accumulate => {
+            run_sequenced(config, producer, display, sequence::Accumulate::new).await
+        }
+        SequencerKind::RingBuffer => {
+            run_sequenced(config, producer, display, sequence::RingBuffer::new).await
+        }
+    }
+}
+
+async fn run_sequenced<S, F, Fut>(
+    config: WorldviewConfig,
+    producer: F,
+    display: Display,
+    new_sequencer: fn(ArtifactsLock, EventProxy, &WorldviewConfig) -> S,
+) where
+    S: Sequencer + Clone + Send + Sync + 'static,
+    F: FnOnce(Viewer) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    // Provide a signal for all threads to monitor for clean process exit.
+    let (exit, _) = watch::channel(false);
+
+    // The window pauses injection through this while it is hidden, if
+    // configured to.
+    let (paused, _) = watch::channel(false);
+
+    // The window asks the injectors to load everything again through
+    // this (the R key).
+    let (rescan, _) = watch::channel(());
+
+    // The window steers playback through this (Space, the . and , keys
+    // and Home).  Injectors subscribe, so never see the first value.
+    let (control, _) = watch::channel(playback::Control::Restart);
+
+    // Artifacts are the producer / consumer queue where the dependency
+    // injector (producer) feeds the GUI thread (consumer).
+    let artifacts = Arc::new(Mutex::new(HashMap::<Key, Artifact>::new()));
+
+    // Every injector and the producer hold a clone of this while they
+    // run; once all are dropped, headless rendering has seen everything.
+    let (injecting, injected) = mpsc::channel::<()>(1);
+
+    let sequencer = new_sequencer(artifacts.clone(), display.proxy(), &config);
+    // Old frames are shown again through the same sequencer.
+    let mut replay_tasks = vec![];
+    let history = sequencer.history();
+    if let Some(history) = history.clone() {
+        replay_tasks.push(tokio::spawn(history::replay(history, sequencer.clone(), exit.clone())));
+    }
+    let producer_task = tokio::spawn({
+        let producing = producer(Viewer::new(sequencer.clone(), exit.subscribe()));
+        let injecting = injecting.clone();
+        async move {
+            producing.await;
+            drop(injecting);
+        }
+    });
+    // Settings edits are applied live.
+    let settings_task = config.settings_path.clone().map(|path| {
+        tokio::spawn(settings::watch(path, display.proxy(), exit.clone()))
+    });
+
+    let injector_task = tokio::spawn({
+        let exit = exit.clone();
+        let config = config.clone();
+        let paused = paused.subscribe();
+        let rescan = rescan.subscribe();
+        let control = control.subscribe();
+        let injecting = injecting.clone();
+        async move {
+            run_dependency_injection(&config, sequencer, exit, paused, rescan, control).await;
+            drop(injecting);
+        }
+    });
+
+    // In split mode, a second pane gets its own artifacts, sequencer and
+    // injector, so two datasets can be compared side by side.
+    let mut panes = vec![(artifacts, history)];
+    let mut split_task = None;
+    if let Some(path) = config.split.clone() {
+        let artifacts = Arc::new(Mutex::new(HashMap::<Key, Artifact>::new()));
+        let config = split_config(&config, path);
+        let sequencer = new_sequencer(artifacts.clone(), display.proxy(), &config);
+        let history = sequencer.history();
+        if let Some(history) = history.clone() {
+            replay_tasks.push(tokio::spawn(history::replay(history, sequencer.clone(), exit.clone())));
+        }
+        let exit = exit.clone();
+        let paused = paused.subscribe();
+        let rescan = rescan.subscribe();
+        let control = control.subscribe();
+        let injecting = injecting.clone();
+        split_task = Some(tokio::spawn(async move {
+            run_dependency_injection(&config, sequencer, exit, paused, rescan, control).await;
+            drop(injecting);
+        }));
+        panes.push((artifacts, history));
+    }
+
+    // Graphics must run on the main thread.  Do not attempt to fight this;
+    // the requirement is long baked into some operating systems (i.e.,
+    // Linux).  On exit, this future will return cleanly when the window
+    // closes via operating system event, or user keypress.  The GPU is
+    // released only after the other threads below have exited.
+    drop(injecting);
+    let shutdown = async move {
+        log::info!("Exit");
+
+        // Windows are closed, but all other threads need to exit as well.
+        // Headless, they may all be done already, with none left to tell.
+        exit.send_replace(true);
+        injector_task.await.unwrap();
+        if let Some(split_task) = split_task {
+            split_task.await.unwrap();
+        }
+        producer_task.await.unwrap();
+        if let Some(settings_task) = settings_task {
+            settings_task.await.unwrap();
+        }
+        for replay_task in replay_tasks {
+            replay_task.await.unwrap();
+        }
+    };
+    match display {
+        Display::Window(event_loop) => window::run(panes, event_loop, &config, paused, rescan, control, shutdown).await,
+        Display::Headless(_, events) => headless::run(panes, events, injected, &config, paused, rescan, control, shutdown).await,
+    }
+}
+
+fn parse_milliseconds(s: &str) -> Result<Duration, ParseIntError> {
+    s.parse().map(Duration::from_millis)
+}
+
+fn parse_speed(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(speed) if speed > 0.0 && speed.is_finite() => Ok(speed),
+        Ok(_) => Err("speed must be positive".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
@@ -1,157 +1,544 @@
-use clap::{Parser, Subcommand};
-use regex::Regex;
+use clap::{parser::ValueSource, ArgAction, CommandFactory, FromArgMatches, Parser, ValueEnum};
 use std::{
-    collections::HashMap,
-    num::ParseIntError,
-    path::PathBuf,
-    sync::{Arc, Mutex},
-    time::Duration,
+    ffi::OsString,
+    io::Write,
+    path::{Path, PathBuf},
 };
-use tokio::sync::watch;
-use winit::event_loop::EventLoop;
-
-mod artifact;
-mod camera;
-mod element;
-mod inject;
-mod key;
-mod model;
-mod pipeline;
-mod sequence;
-mod window;
-
-pub use artifact::{Artifact, ArtifactUniform, RenderArtifact};
-pub use camera::{Camera, CameraController, CameraUniform, Projection};
-pub use element::{Element, IntoElement};
-pub use inject::{inotify, playback};
-pub use key::Key;
-pub use sequence::Sequencer;
-pub use window::WindowState;
-
-// Visualized artifacts (PLY files) must come from somewhere, and we have
-// different use cases.  For now, we support dependency injection from
-// the filesystem, either as "playback" or using Linux inotify.  Future
-// extensions could be gRPC or HTTP/2 servers, or a portable inotify
-// replacement (good for Mac and non-Linux platforms).
-#[derive(Clone, Subcommand)]
-enum DependencyInjector {
-    /// Worldview: Enumerate pre-existing directory
-    Playback {
-        /// Playback directory of PLY files
-        path: PathBuf,
-        /// Inject a minimum delay between each frame (milliseconds)
-        #[clap(value_parser = parse_milliseconds, default_value="100")]
-        delay: Duration,
-    },
-    /// Worldview: Watch live Linux filesystem via inotify (default)
-    Notify { path: Option<PathBuf> },
-}
+use worldview::{Backend, Colormap, ColormapAxis, Corner, AlphaOverride, ColorOverride, DependencyInjector, EdgeColor, Fog, KindOverride, Orbit, SequencerKind, Settings, Texture, Transforms, UpAxis, WorldviewConfig};
 
 #[derive(Parser)]
 struct Cli {
-    /// Comma separated list of enabled artifact types.  Default: no filter.
+    /// Comma separated list of enabled artifact types.  Append
+    /// :vertices to a name to load only the vertices of its files, as a
+    /// point cloud, e.g. mesh:vertices.  Default: no filter.
     #[clap(short, long, value_delimiter = ',')]
     filter: Option<Vec<String>>,
+    /// Watch a single file that is overwritten in place, instead of a
+    /// directory.  Takes precedence over the injector subcommand.
+    #[clap(long)]
+    watch_file: Option<PathBuf>,
+    /// Worker threads, also the number of files parsed concurrently
+    /// during playback.
+    #[clap(long, default_value = "8")]
+    threads: usize,
+    /// World axis that points up; robotics data is usually Z-up.
+    #[clap(long, value_enum, default_value = "y")]
+    up: UpAxis,
+    /// Redraw every frame instead of only when something changes.
+    /// Toggle at runtime with the C key.
+    #[clap(long)]
+    continuous: bool,
+    /// Show artifacts as pointcloud, wireframe or mesh instead of the
+    /// type detected from the PLY header.  Append :NAME to override
+    /// only that artifact.  May be repeated.  A mesh shown as
+    /// pointcloud loads faster, because its facets are never read.
+    /// Without --as, an artifact named with a .mesh, .wireframe or
+    /// .pointcloud suffix ("3.bunny.mesh.ply") is shown as that, and then
+    /// a "comment kind KIND" line in the PLY header decides.
+    #[clap(long = "as", value_name = "KIND[:NAME]")]
+    kind_overrides: Vec<KindOverride>,
+    /// Color wireframe edges uniformly, by length (short blue, long red)
+    /// or with a hue per facet.
+    #[clap(long, value_enum, default_value = "uniform")]
+    edge_color_by: EdgeColor,
+    /// Draw the edges of filled meshes over them, in black or white,
+    /// whichever stands out.  Toggle at runtime with the W key.
+    #[clap(long)]
+    edges: bool,
+    /// Draw a grid on the ground plane with lines SPACING (default 1)
+    /// apart, to judge scale by.  Toggle at runtime with the # key.
+    #[clap(long, value_name = "SPACING", num_args = 0..=1, default_missing_value = "1")]
+    grid: Option<f32>,
+    /// How far the grid reaches from the origin along each axis.
+    #[clap(long, value_name = "DISTANCE", default_value = "10")]
+    grid_extent: f32,
+    /// Draw the coordinate axes at the origin, x red, y green and z
+    /// blue, one grid spacing long.  Toggle at runtime with the A key.
+    #[clap(long)]
+    axes: bool,
+    /// How far filled meshes are pushed back in depth, so that their
+    /// edges (--edges) and a grid on the same plane (--grid) show on them
+    /// instead of flickering.  Raise it if they still do; 0 turns it off.
+    #[clap(long, value_name = "BIAS", default_value = "2")]
+    depth_bias: i32,
+    /// Replace the shown instance of an artifact with each newer one, or
+    /// accumulate every instance on screen, as for a trajectory or a
+    /// scan building up.
+    /// ring-buffer (or ring) keeps the newest --ring-size instances of
+    /// each.
+    #[clap(long, value_enum, default_value = "replace")]
+    sequencer: SequencerKind,
+    /// How many instances of each artifact --sequencer ring-buffer keeps.
+    #[clap(long, value_name = "N", default_value = "30")]
+    ring_size: usize,
+    /// Also watch or play back the directories below, such as one
+    /// folder per frame.  Folders created later are watched too.
+    #[clap(long)]
+    recursive: bool,
+    /// Uniformly subsample point clouds larger than this, to keep
+    /// huge clouds interactive.
+    #[clap(long, value_name = "N")]
+    max_points: Option<usize>,
+    /// Split the window, showing artifacts from this second path in a
+    /// pane beside the first.  Press L to unlink the cameras.
+    #[clap(long, value_name = "PATH")]
+    split: Option<PathBuf>,
+    /// Color point clouds by height, for terrain and LIDAR scans.  The M
+    /// key switches between this and the usual colors, with viridis if
+    /// no colormap was given.
+    #[clap(long, value_enum)]
+    colormap: Option<Colormap>,
+    /// The coordinate points are colored by, as in the file.
+    #[clap(long, value_enum, default_value = "z")]
+    colormap_axis: ColormapAxis,
+    /// Corner for the colormap legend.  Toggle it with the G key.
+    #[clap(long, value_enum, default_value = "top-right")]
+    legend: Corner,
+    /// Start with the colormap legend hidden.
+    #[clap(long)]
+    no_legend: bool,
+    /// Vertical field of view in degrees, 10 to 120.  Adjust at runtime
+    /// with the [ and ] keys.
+    #[clap(long, default_value = "45")]
+    fov: f32,
+    /// Width of the squares drawn for points, in pixels, 1 to 32.
+    /// Adjust at runtime with the + and - keys.
+    #[clap(long, value_name = "PX", default_value = "2")]
+    point_size: f32,
+    /// How fast dragging the mouse turns the camera.  Step through
+    /// presets at runtime with the P key.
+    #[clap(long, default_value = "0.5")]
+    sensitivity: f32,
+    /// Reverse the horizontal turn of a mouse drag.
+    #[clap(long)]
+    invert_x: bool,
+    /// Reverse the vertical turn of a mouse drag.
+    #[clap(long)]
+    invert_y: bool,
+    /// Ease the camera into turns and zooms instead of following the
+    /// mouse exactly, for smooth recordings.  FACTOR, below 1, is the
+    /// share of the motion still to come after 1/60 s; try 0.8.
+    #[clap(long, value_name = "FACTOR")]
+    smooth: Option<f32>,
+    /// Near clip plane: nothing closer to the camera is drawn.  Raise it
+    /// for large outdoor scenes, lower it to inspect small detail.  Depth
+    /// is linear, so filled meshes resolve best with far / near modest.
+    #[clap(long, default_value = "0.1")]
+    near: f32,
+    /// Far clip plane: nothing further from the camera is drawn.  Raise
+    /// it for large outdoor scenes.
+    #[clap(long, default_value = "100")]
+    far: f32,
+    /// Fade fragments linearly into fog between two distances from the
+    /// camera, as near,far or near,far,r,g,b.  Without a color, they fade
+    /// into the background.
+    #[clap(long, value_name = "NEAR,FAR[,R,G,B]")]
+    fog: Option<Fog>,
+    /// TOML file placing artifacts in the world: a table per artifact
+    /// name, with optional translation = [x, y, z] and rotation =
+    /// [x, y, z, w] (quaternion).
+    #[clap(long, value_name = "TOML")]
+    transforms: Option<PathBuf>,
+    /// TOML file of display settings, applied live whenever it is
+    /// saved: filter = [names], background = [r, g, b], a [colors]
+    /// table of [r, g, b(, a)] and a [transforms] table as for
+    /// --transforms.  A [flags] table gives defaults for the flags here,
+    /// by long name, read once at start.  Default: worldview.toml, if
+    /// the working directory has one.
+    #[clap(long, value_name = "TOML", conflicts_with = "transforms")]
+    config: Option<PathBuf>,
+    /// Merge mesh and wireframe vertices closer than EPSILON (default
+    /// 1e-6) into one shared vertex.  Shrinks triangle soup, but changes shading.
+    #[clap(long, value_name = "EPSILON", num_args = 0..=1, default_missing_value = "1e-6")]
+    weld: Option<f32>,
+    /// Measure GPU time per artifact, shown bottom left and logged
+    /// every second.  Needs adapter support for timestamp queries.
+    #[clap(long)]
+    profile: bool,
+    /// Comma separated graphics APIs to try: vulkan, metal, dx12 or gl.
+    /// Overrides WGPU_BACKEND.  Default: every one this platform has.
+    #[clap(long = "backend", value_name = "API", value_enum, value_delimiter = ',')]
+    backends: Vec<Backend>,
+    /// Stop loading new files while the window is minimized or covered,
+    /// and catch up when it shows again.
+    #[clap(long)]
+    pause_hidden: bool,
+    /// Move the first vertex loaded to the origin, and everything else
+    /// with it, before narrowing to f32.  For geospatial data far from
+    /// its origin, such as UTM coordinates.
+    #[clap(long)]
+    recenter: bool,
+    /// PNG image to drape over meshes that have texture coordinates
+    /// (u/v or s/t vertex properties).  Press M for the flat mesh.
+    #[clap(long, value_name = "PNG")]
+    texture: Option<PathBuf>,
+    /// Keep artifact GPU buffers under this many MiB, evicting the
+    /// oldest artifacts to make room for new ones.
+    #[clap(long, value_name = "MB")]
+    vram_limit: Option<u64>,
+    /// Keep the last N injected files in memory, to step back through
+    /// with the Left and Right keys; End goes back to live.  Injection
+    /// pauses meanwhile.
+    #[clap(long, value_name = "N", default_value = "0")]
+    history: usize,
+    /// Open on this monitor, counting from 0.
+    #[clap(long, value_name = "INDEX")]
+    monitor: Option<usize>,
+    /// Open the window at this scale factor instead of the monitor's:
+    /// 1024x768 pixels times the factor, with the overlay to match.
+    /// Screenshots are then the same size on every machine.
+    #[clap(long, value_name = "FACTOR")]
+    scale: Option<f32>,
+    /// Open the window this many pixels wide, times --scale, if given.
+    /// Also the width of --headless frames.  Default: the OS picks, or
+    /// 1024 with --scale or --height.
+    #[clap(long, value_name = "PX")]
+    width: Option<u32>,
+    /// Open the window this many pixels high, as for --width.  Default:
+    /// the OS picks, or 768 with --scale or --width.
+    #[clap(long, value_name = "PX")]
+    height: Option<u32>,
+    /// Window title.  Default: the platform's.
+    #[clap(long)]
+    title: Option<String>,
+    /// Print every artifact shown or removed to stdout, as
+    /// newline-delimited JSON, for tools driving worldview.
+    #[clap(long)]
+    events_out: bool,
+    /// Comma separated artifact names to draw first, in this order, so
+    /// translucent artifacts blend over the right ones.  The rest are
+    /// drawn after, by name; translucent artifacts last.
+    #[clap(long, value_name = "NAMES", value_delimiter = ',')]
+    layer_order: Vec<String>,
+    /// See artifact NAME through, with an opacity from 0 to 1.  May be
+    /// repeated.  Translucent artifacts are drawn after the opaque ones
+    /// in their layer, farthest first, so they blend in the right order
+    /// as long as they do not overlap themselves or each other.
+    #[clap(long = "alpha", value_name = "NAME=ALPHA")]
+    alphas: Vec<AlphaOverride>,
+    /// Color artifact NAME, with values from 0 to 1.  May be repeated.
+    /// Artifacts without a color get a hue hashed from their name,
+    /// the same on every run.  Wins over a [colors] entry in --config.
+    #[clap(long = "color", value_name = "NAME=R,G,B[,A]")]
+    colors: Vec<ColorOverride>,
+    /// Color all point clouds green, meshes blue and wireframes black,
+    /// instead of a distinct color per artifact name.  Colors given with
+    /// --color or --config still apply.
+    #[clap(long)]
+    no_palette: bool,
+    /// Capture a turntable: turn the camera DEG degrees about the view
+    /// center every frame, save each frame as a numbered PNG, then exit.
+    #[clap(long, value_name = "DEG", allow_negative_numbers = true)]
+    orbit: Option<f32>,
+    /// Frames to capture with --orbit.  Default: one full turn.
+    #[clap(long, value_name = "N", requires = "orbit")]
+    frames: Option<u32>,
+    /// Render without a window, for CI and thumbnails: save a frame to
+    /// --out-dir, framed around everything shown, whenever artifacts come
+    /// or go, and exit once the injector is done (e.g. playback --once).
+    #[clap(long, conflicts_with = "orbit")]
+    headless: bool,
+    /// Where --orbit and --headless save frame_00000.png and on.
+    #[clap(long, value_name = "DIR", default_value = ".")]
+    out_dir: PathBuf,
+    /// Where the S key saves screenshots, named by the time taken.
+    #[clap(long, value_name = "DIR", default_value = ".")]
+    screenshot_dir: PathBuf,
+    /// Start in the view saved in this JSON file, which the V key then
+    /// saves to and Shift+V restores from.  Default: camera.json, and
+    /// the usual start.  --headless keeps this view instead of framing.
+    #[clap(long, value_name = "JSON")]
+    camera: Option<PathBuf>,
+    /// More logging; repeat for more (-vv for trace).
+    #[clap(short, long, action = ArgAction::Count)]
+    verbose: u8,
+    /// Less logging; repeat for less.
+    #[clap(short, long, action = ArgAction::Count, conflicts_with = "verbose")]
+    quiet: u8,
+    /// Log as plain text, or as one JSON object per line.
+    #[clap(long, value_enum, default_value = "plain")]
+    log_format: LogFormat,
+    /// Describe how a PLY file would be shown, then exit without
+    /// opening a window.
+    #[clap(long)]
+    inspect: Option<PathBuf>,
     #[command(subcommand)]
     injector: Option<DependencyInjector>,
 }
 
-#[derive(Debug)]
-pub enum InjectionEvent {
-    Add(Key),
-    Remove(Key),
+#[derive(Clone, Copy, ValueEnum)]
+enum LogFormat {
+    Plain,
+    Json,
+}
+
+impl From<Cli> for WorldviewConfig {
+    fn from(cli: Cli) -> Self {
+        WorldviewConfig {
+            filter: cli.filter,
+            watch_file: cli.watch_file,
+            injector: cli.injector,
+            threads: cli.threads,
+            up: cli.up,
+            continuous: cli.continuous,
+            kind_overrides: cli.kind_overrides,
+            edge_color: cli.edge_color_by,
+            edges: cli.edges,
+            grid: cli.grid,
+            grid_extent: cli.grid_extent,
+            depth_bias: cli.depth_bias,
+            axes: cli.axes,
+            max_points: cli.max_points,
+            split: cli.split,
+            colormap: cli.colormap,
+            colormap_axis: cli.colormap_axis,
+            legend: (!cli.no_legend).then_some(cli.legend),
+            fov: cli.fov,
+            point_size: cli.point_size,
+            sensitivity: cli.sensitivity,
+            invert_x: cli.invert_x,
+            invert_y: cli.invert_y,
+            smooth: cli.smooth,
+            sequencer: cli.sequencer,
+            ring_size: cli.ring_size,
+            recursive: cli.recursive,
+            near: cli.near,
+            far: cli.far,
+            fog: cli.fog,
+            // Loaded in main(), where a bad file can be reported.
+            settings: Settings::default(),
+            settings_path: cli.config,
+            texture: None,
+            weld: cli.weld,
+            profile: cli.profile,
+            backends: cli.backends,
+            pause_hidden: cli.pause_hidden,
+            recenter: cli.recenter,
+            vram_limit: cli.vram_limit.map(|mb| mb * 1024 * 1024),
+            orbit: cli.orbit.map(|degrees| Orbit {
+                degrees,
+                frames: cli
+                    .frames
+                    .unwrap_or_else(|| (360.0 / degrees.abs()).round().max(1.0) as u32),
+                out_dir: cli.out_dir.clone(),
+            }),
+            headless: cli.headless.then_some(cli.out_dir),
+            screenshot_dir: cli.screenshot_dir,
+            camera: cli.camera,
+            history: cli.history,
+            monitor: cli.monitor,
+            scale: cli.scale,
+            width: cli.width,
+            height: cli.height,
+            title: cli.title,
+            events_out: cli.events_out,
+            layer_order: cli.layer_order,
+            alphas: cli.alphas,
+            colors: cli.colors,
+            palette: !cli.no_palette,
+        }
+    }
 }
 
-pub type ArtifactsLock = Arc<Mutex<HashMap<Key, Artifact>>>;
-const PLY_RE: &'static str = r"(?<instance>[0-9]+)\.(?<artifact>.+)\.ply";
-
-async fn run_dependency_injection<S: Sequencer + Clone>(
-    cli: &Cli,
-    sequencer: S,
-    exit: watch::Sender<bool>,
-) {
-    let cwd = std::env::current_dir().unwrap();
-
-    // Set up a command-line configureable filter, to inject only
-    // some artifacts into the renderer.  That can significantly speed up
-    // and de-clutter the display, if calculations are dropping too many
-    // types of artifacts.
-    let filter = Regex::new(&format!(
-        "({})",
-        cli.filter.clone().unwrap_or(vec![]).join("|")
-    ))
-    .unwrap();
-
-    match cli.injector.clone() {
-        Some(DependencyInjector::Playback { path, delay }) => {
-            log::info!(
-                "Playback from {}; min refresh {}ms",
-                path.display(),
-                delay.as_millis()
-            );
-            playback::run(path, sequencer, delay, filter, exit).await
+// Looked for in the working directory when there is no --config.
+const DEFAULT_CONFIG: &str = "worldview.toml";
+
+// The command line, with defaults from the [flags] of the --config file
+// for flags it does not give:
+//
+//     [flags]
+//     sequencer = "accumulate"            # --sequencer accumulate
+//     edges = true                        # --edges
+//     color = ["a=1,0,0", "b=0,0,1"]      # --color a=1,0,0 --color b=0,0,1
+//
+// A flag given on the command line replaces the file's entirely, even a
+// repeated one.  The file's are passed before the command line's, so
+// a mistake in them is reported as clap would any other.
+fn parse() -> Cli {
+    let given = Cli::command().get_matches();
+    let (path, implicit) = match given.get_one::<PathBuf>("config") {
+        Some(path) => (path.clone(), false),
+        None if given.value_source("transforms").is_none() && Path::new(DEFAULT_CONFIG).is_file() => {
+            (PathBuf::from(DEFAULT_CONFIG), true)
         }
-        Some(DependencyInjector::Notify { path }) => {
-            let path = path.clone().unwrap_or(cwd);
-            log::info!("Notify from {}", path.display());
-            inotify::run(path, sequencer, exit).await
+        None => return Cli::from_arg_matches(&given).unwrap_or_else(|err| err.exit()),
+    };
+    let flags = Settings::flags_from_path(&path).unwrap_or_else(|err| {
+        eprintln!("{}: {}", path.display(), err);
+        std::process::exit(1);
+    });
+
+    let mut args: Vec<OsString> = std::env::args_os().take(1).collect();
+    if implicit {
+        args.extend(["--config".into(), path.clone().into()]);
+    }
+    let command = Cli::command();
+    for (name, value) in &flags {
+        let long = name.replace('_', "-");
+        if long == "config" {
+            eprintln!("{}: [flags] cannot name another --config", path.display());
+            std::process::exit(1);
         }
-        None => {
-            log::info!("Notify from CWD ({})", cwd.display());
-            inotify::run(cwd, sequencer, exit).await
+        let arg = command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(&long))
+            .unwrap_or_else(|| {
+                eprintln!("{}: [flags] has {}, but there is no --{}", path.display(), name, long);
+                std::process::exit(1);
+            });
+        if given.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine) {
+            continue;
         }
+        flag_args(arg, value, &mut args).unwrap_or_else(|err| {
+            eprintln!("{}: [flags] {}", path.display(), err);
+            std::process::exit(1);
+        });
     }
+    args.extend(std::env::args_os().skip(1));
+    Cli::try_parse_from(args).unwrap_or_else(|err| {
+        eprintln!("With the [flags] of {}:", path.display());
+        err.exit()
+    })
 }
 
-#[tokio::main(worker_threads = 8)]
-async fn main() {
-    let cli = Cli::parse();
-    env_logger::builder()
-        .filter_level(log::LevelFilter::Info)
-        .filter_module("wgpu_hal", log::LevelFilter::Error)
-        .filter_module("worldview", log::LevelFilter::Debug)
-        .format_timestamp(None)
-        .init();
+// The arguments a [flags] entry stands for.  Switches are true or false,
+// counted ones (-v) may be a count, and a list repeats the flag.
+fn flag_args(arg: &clap::Arg, value: &toml::Value, args: &mut Vec<OsString>) -> Result<(), String> {
+    let flag = format!("--{}", arg.get_long().unwrap_or_default());
+    match (value, arg.get_action()) {
+        (toml::Value::Array(values), _) => {
+            for value in values {
+                flag_args(arg, value, args)?;
+            }
+        }
+        (toml::Value::Boolean(true), ArgAction::SetTrue | ArgAction::Count) => args.push(flag.into()),
+        (toml::Value::Boolean(false), ArgAction::SetTrue | ArgAction::Count) => {}
+        (toml::Value::Integer(count), ArgAction::Count) => {
+            args.extend(std::iter::repeat_n(flag.into(), (*count).max(0) as usize))
+        }
+        (_, ArgAction::SetTrue) => return Err(format!("{} is true or false", flag)),
+        (_, ArgAction::Count) => return Err(format!("{} is true, false or a count", flag)),
+        // Joined by "=", so values starting with "-" stay values.
+        (toml::Value::String(value), _) => args.push(format!("{}={}", flag, value).into()),
+        (toml::Value::Integer(_) | toml::Value::Float(_), _) => args.push(format!("{}={}", flag, value).into()),
+        (value, _) => return Err(format!("{} cannot be a {}", flag, value.type_str())),
+    }
+    Ok(())
+}
 
-    // Connect to operating system window management (via winit).  The
-    // InjectionEvent will be sent to the GUI thread, from the dependency
-    // injection thread, to trigger Vulcan refresh.
-    let event_loop = EventLoop::<InjectionEvent>::with_user_event()
-        .build()
-        .unwrap();
-
-    // Provide a signal for all threads to monitor for clean process exit.
-    let (exit, _) = watch::channel(false);
-
-    // Artifacts are the producer / consumer queue where the dependency
-    // injector (producer) feeds the GUI thread (consumer).
-    let artifacts = Arc::new(Mutex::new(HashMap::<Key, Artifact>::new()));
-
-    // The policy when (or if) artifacts get ejected are implemented in
-    // the sequencer.  Policies might be "replace" (just show the newest
-    // artifact) or "accumulate" (show all the artifacts from all time).
-    // It seems to be impossible to use dynamic dispatch into a tokio
-    // thread ('static + Send), so use static dispatch for the sequencer
-    // here.
-    let sequencer = sequence::Replace::new(artifacts.clone(), event_loop.create_proxy());
-    let injector_task = tokio::spawn({
-        let exit = exit.clone();
-        async move { run_dependency_injection(&cli, sequencer, exit).await }
-    });
+fn main() {
+    let cli = parse();
+    if let Some(path) = cli.inspect {
+        if let Err(err) = worldview::inspect(&path) {
+            eprintln!("{}: {}", path.display(), err);
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    // Graphics must run on the main thread.  Do not attempt to fight this;
-    // the requirement is long baked into some operating systems (i.e.,
-    // Linux).  On exit, this future will return cleanly when the window
-    // closes via operating system event, or user keypress.
-    window::run(artifacts.clone(), event_loop).await;
+    init_logging(&cli);
 
-    log::info!("Exit");
+    if cli.scale.is_some_and(|scale| scale.is_nan() || scale <= 0.0) {
+        eprintln!("--scale must be above 0");
+        std::process::exit(1);
+    }
+    if cli.width == Some(0) || cli.height == Some(0) {
+        eprintln!("--width and --height must be at least 1");
+        std::process::exit(1);
+    }
+    if cli.smooth.is_some_and(|smooth| !(0.0..1.0).contains(&smooth)) {
+        eprintln!("--smooth must be at least 0 and below 1");
+        std::process::exit(1);
+    }
+    let extent = cli.grid_extent;
+    if cli.grid.is_some_and(|spacing| spacing.is_nan() || spacing <= 0.0) || extent.is_nan() || extent <= 0.0 {
+        eprintln!("--grid and --grid-extent must be above 0");
+        std::process::exit(1);
+    }
+    if cli.depth_bias < 0 {
+        eprintln!("--depth-bias must be at least 0");
+        std::process::exit(1);
+    }
+    if cli.ring_size == 0 {
+        eprintln!("--ring-size must be at least 1");
+        std::process::exit(1);
+    }
+    if cli.max_points == Some(0) {
+        eprintln!("--max-points must be at least 1");
+        std::process::exit(1);
+    }
+    if !(cli.near > 0.0 && cli.far > cli.near) {
+        eprintln!("--near must be above 0, and --far above --near");
+        std::process::exit(1);
+    }
 
-    // Windows are closed, but all other threads need to exit as well.
-    exit.send(true).unwrap();
-    injector_task.await.unwrap();
+    let settings = match (&cli.config, &cli.transforms) {
+        (Some(path), _) => Settings::from_path(path).map_err(|err| (path, err)),
+        (None, Some(path)) => Transforms::from_path(path)
+            .map(Settings::from_transforms)
+            .map_err(|err| (path, err)),
+        (None, None) => Ok(Settings::default()),
+    }
+    .unwrap_or_else(|(path, err)| {
+        eprintln!("{}: {}", path.display(), err);
+        std::process::exit(1);
+    });
+    let texture = cli.texture.as_ref().map(|path| {
+        Texture::from_path(path).unwrap_or_else(|err| {
+            eprintln!("{}: {}", path.display(), err);
+            std::process::exit(1);
+        })
+    });
+    let threads = cli.threads.max(1);
+    let config = WorldviewConfig {
+        settings,
+        texture,
+        ..cli.into()
+    };
+
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(threads)
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(worldview::run(config));
 }
 
-fn parse_milliseconds(s: &str) -> Result<Duration, ParseIntError> {
-    s.parse().map(Duration::from_millis)
+// By default worldview logs at debug and everything else at info.  Each
+// -v or -q moves both up or down a level.  RUST_LOG, if set, overrides
+// all of this, including the wgpu_hal filter.
+fn init_logging(cli: &Cli) {
+    const LEVELS: [log::LevelFilter; 6] = [
+        log::LevelFilter::Off,
+        log::LevelFilter::Error,
+        log::LevelFilter::Warn,
+        log::LevelFilter::Info,
+        log::LevelFilter::Debug,
+        log::LevelFilter::Trace,
+    ];
+    let level = |default: isize| {
+        let index = default + cli.verbose as isize - cli.quiet as isize;
+        LEVELS[index.clamp(0, LEVELS.len() as isize - 1) as usize]
+    };
+
+    let mut builder = env_logger::builder();
+    builder
+        .filter_level(level(3))
+        .filter_module("wgpu_hal", log::LevelFilter::Error)
+        .filter_module("worldview", level(4))
+        .format_timestamp(None)
+        .parse_default_env();
+
+    if let LogFormat::Json = cli.log_format {
+        builder.format(|buf, record| {
+            let line = serde_json::json!({
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            });
+            writeln!(buf, "{}", line)
+        });
+    }
+
+    builder.init();
 }
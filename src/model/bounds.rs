@@ -0,0 +1,57 @@
+use crate::model::PlainVertex;
+use cgmath::{Matrix4, Point3, Transform};
+
+// Axis aligned bounding box of an artifact's vertices, kept when they are
+// staged, so the camera can frame what is loaded (the F key).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Bounds {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Bounds {
+    // None if there are no vertices, or none with finite coordinates.
+    pub fn of(vertices: &[PlainVertex]) -> Option<Bounds> {
+        vertices
+            .iter()
+            .map(|vertex| Point3::from(vertex.position))
+            .filter(|point| point.x.is_finite() && point.y.is_finite() && point.z.is_finite())
+            .map(|point| Bounds { min: point, max: point })
+            .reduce(Bounds::union)
+    }
+
+    pub fn union(self, other: Bounds) -> Bounds {
+        Bounds {
+            min: Point3::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y), self.min.z.min(other.min.z)),
+            max: Point3::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y), self.max.z.max(other.max.z)),
+        }
+    }
+
+    // The box around this one once moved by `matrix`, found from its
+    // eight corners.
+    pub fn transformed(&self, matrix: Matrix4<f32>) -> Bounds {
+        (0..8)
+            .map(|corner| {
+                let pick = |bit: usize, min: f32, max: f32| if corner & bit == 0 { min } else { max };
+                let point = Point3::new(
+                    pick(1, self.min.x, self.max.x),
+                    pick(2, self.min.y, self.max.y),
+                    pick(4, self.min.z, self.max.z),
+                );
+                let point = matrix.transform_point(point);
+                Bounds { min: point, max: point }
+            })
+            .reduce(Bounds::union)
+            .unwrap()
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        self.min + (self.max - self.min) / 2.0
+    }
+
+    // Radius of the sphere around the box.
+    pub fn radius(&self) -> f32 {
+        use cgmath::InnerSpace;
+        (self.max - self.min).magnitude() / 2.0
+    }
+}
@@ -0,0 +1,81 @@
+use crate::{
+    model::{PlainVertex, Polygon, Wireframe},
+    EdgeColor,
+};
+use std::mem;
+
+// An end of a wireframe edge, carrying a per-edge scalar in [0, 1] that
+// the shader maps through a colormap.  Edges cannot share vertices here,
+// because two edges meeting at a vertex may have different colors.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ScalarVertex {
+    pub position: [f32; 3],
+    pub scalar: f32,
+}
+
+// Teach wgpu how model a vertex.
+impl ScalarVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32];
+
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<ScalarVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+
+    // Two line vertices per edge.
+    pub fn buffer_size(edges: usize) -> usize {
+        2 * mem::size_of::<ScalarVertex>() * edges
+    }
+
+    // Expand indexed facet outlines into line vertices, colored by `by`.
+    // Lengths are normalized over the whole artifact, so the shortest edge
+    // is blue and the longest red; the range is returned for the legend.
+    // Facets get hues spread by the golden ratio, so neighbours rarely
+    // look alike.
+    pub fn edges(
+        vertices: &[PlainVertex],
+        facets: &[Polygon],
+        by: EdgeColor,
+    ) -> (Vec<ScalarVertex>, Option<(f32, f32)>) {
+        let position = |index: i32| match vertices.get(index as usize) {
+            Some(vertex) => vertex.position,
+            None => [0.0; 3],
+        };
+
+        let mut lines = Vec::with_capacity(2 * Wireframe::count(facets));
+        for (facet, polygon) in facets.iter().enumerate() {
+            for edge in polygon.edges() {
+                let (a, b) = (position(edge[0]), position(edge[1]));
+                let scalar = match by {
+                    EdgeColor::Length => {
+                        let d = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+                        (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt()
+                    }
+                    EdgeColor::Facet => (facet as f32 * 0.618_034).fract(),
+                    EdgeColor::Uniform => 0.0,
+                };
+                lines.push(ScalarVertex { position: a, scalar });
+                lines.push(ScalarVertex { position: b, scalar });
+            }
+        }
+
+        if by != EdgeColor::Length || lines.is_empty() {
+            return (lines, None);
+        }
+
+        let (min, max) = lines.iter().fold((f32::MAX, f32::MIN), |(min, max), v| {
+            (min.min(v.scalar), max.max(v.scalar))
+        });
+        let range = (max - min).max(f32::EPSILON);
+        for vertex in lines.iter_mut() {
+            vertex.scalar = (vertex.scalar - min) / range;
+        }
+
+        (lines, Some((min, max)))
+    }
+}
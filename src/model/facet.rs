@@ -7,27 +7,149 @@ pub struct TriFacet {
     pub vertex_indices: [i32; 3],
 }
 
-// Teach worldview how to find the facet in the PLY header
+// Teach worldview how to find the facet in the PLY header.
 impl IntoElement for TriFacet {
     fn element() -> Element { Element::Facet }
 }
 
+impl TriFacet {
+    // How many triangles `facets` split into.
+    pub fn count(facets: &[Polygon]) -> usize {
+        facets.iter().map(|facet| facet.corners().saturating_sub(2)).sum()
+    }
+}
+
+// A facet as read from the PLY file: a triangle, a quad or any larger
+// polygon.  Solid rendering splits polygons into triangles, but their
+// outline follows the sides, without the diagonals.  Facets with fewer
+// than three corners are skipped.
+#[derive(Clone, Debug)]
+pub struct Polygon {
+    pub vertex_indices: Vec<i32>,
+}
+
+impl Polygon {
+    pub fn corners(&self) -> usize {
+        self.vertex_indices.len()
+    }
+
+    // Split into triangles, fanning out from the first corner.  That is
+    // exact for convex polygons, which is what exporters write; concave
+    // ones may be drawn with some triangles outside them.
+    pub fn triangles(&self) -> impl Iterator<Item = TriFacet> + '_ {
+        let v = &self.vertex_indices;
+        (2..self.corners()).map(move |i| TriFacet {
+            vertex_indices: [v[0], v[i - 1], v[i]],
+        })
+    }
+
+    // The sides, each as a pair of vertex indices.
+    pub fn edges(&self) -> impl Iterator<Item = [i32; 2]> + '_ {
+        let v = &self.vertex_indices;
+        let n = self.corners();
+        (0..n).map(move |i| [v[i], v[(i + 1) % n]])
+    }
+}
+
+// Teach worldview how to find the facet in the PLY header
+impl IntoElement for Polygon {
+    fn element() -> Element { Element::Facet }
+}
+
 // Teach ply_rs how model a facet.
-impl ply::PropertyAccess for TriFacet {
+impl ply::PropertyAccess for Polygon {
     fn new() -> Self {
-        TriFacet {
-            vertex_indices: [0, 0, 0],
-        }
+        Polygon { vertex_indices: vec![] }
     }
 
     fn set_property(&mut self, key: String, property: ply::Property) {
-        match (key.as_ref(), property) {
-            ("vertex_indices", ply::Property::ListInt(vec)) => {
-                if vec.len() == 3 {
-                    self.vertex_indices = [vec[0], vec[1], vec[2]];
-                }
-            }
+        match (key.as_ref(), list_indices(property)) {
+            ("vertex_indices", Some(vec)) if vec.len() >= 3 => self.vertex_indices = vec,
             (_, _) => {}
         }
     }
 }
+
+// Exporters disagree on the index type; PCL and Open3D write
+// `uchar uint vertex_indices`, others int or even short.  Accept any
+// integer list and convert to i32, the GPU index type.
+pub fn list_indices(property: ply::Property) -> Option<Vec<i32>> {
+    match property {
+        ply::Property::ListInt(vec) => Some(vec),
+        ply::Property::ListUInt(vec) => Some(vec.into_iter().map(|i| i as i32).collect()),
+        ply::Property::ListShort(vec) => Some(vec.into_iter().map(i32::from).collect()),
+        ply::Property::ListUShort(vec) => Some(vec.into_iter().map(i32::from).collect()),
+        ply::Property::ListChar(vec) => Some(vec.into_iter().map(i32::from).collect()),
+        ply::Property::ListUChar(vec) => Some(vec.into_iter().map(i32::from).collect()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read_header;
+    use ply_rs::parser::Parser;
+    use std::io::{self, Cursor};
+
+    // Read the facets of a binary PLY with `uchar uint vertex_indices`,
+    // as PCL and Open3D write them.
+    fn read_facets(faces: &[&[u32]], truncate: usize) -> io::Result<Vec<Polygon>> {
+        let mut bytes = format!(
+            "ply\n\
+             format binary_little_endian 1.0\n\
+             element face {}\n\
+             property list uchar uint vertex_indices\n\
+             end_header\n",
+            faces.len()
+        )
+        .into_bytes();
+        for face in faces {
+            bytes.push(face.len() as u8);
+            bytes.extend(face.iter().flat_map(|i| i.to_le_bytes()));
+        }
+        bytes.truncate(bytes.len() - truncate);
+
+        let mut f = Cursor::new(bytes);
+        let header = read_header(&mut f)?;
+        let element = Element::Facet.require(&header)?;
+        Parser::<Polygon>::new().read_payload_for_element(&mut f, element, &header)
+    }
+
+    #[test]
+    fn every_integer_list() {
+        let expected = Some(vec![0, 1, 2]);
+        assert_eq!(list_indices(ply::Property::ListInt(vec![0, 1, 2])), expected);
+        assert_eq!(list_indices(ply::Property::ListUInt(vec![0, 1, 2])), expected);
+        assert_eq!(list_indices(ply::Property::ListShort(vec![0, 1, 2])), expected);
+        assert_eq!(list_indices(ply::Property::ListUShort(vec![0, 1, 2])), expected);
+        assert_eq!(list_indices(ply::Property::ListChar(vec![0, 1, 2])), expected);
+        assert_eq!(list_indices(ply::Property::ListUChar(vec![0, 1, 2])), expected);
+        assert_eq!(list_indices(ply::Property::ListFloat(vec![0.0, 1.0, 2.0])), None);
+        assert_eq!(list_indices(ply::Property::Int(0)), None);
+    }
+
+    #[test]
+    fn triangles_and_quads() {
+        let facets = read_facets(&[&[0, 1, 2], &[1, 3, 4, 2]], 0).unwrap();
+        let corners: Vec<usize> = facets.iter().map(Polygon::corners).collect();
+        assert_eq!(corners, [3, 4]);
+        assert_eq!(facets[1].vertex_indices, [1, 3, 4, 2]);
+        assert_eq!(TriFacet::count(&facets), 3);
+        let triangles: Vec<[i32; 3]> = facets.iter().flat_map(Polygon::triangles).map(|t| t.vertex_indices).collect();
+        assert_eq!(triangles, [[0, 1, 2], [1, 3, 4], [1, 4, 2]]);
+    }
+
+    // A facet of two corners is kept, but draws nothing.
+    #[test]
+    fn short_facet_is_skipped() {
+        let facets = read_facets(&[&[0, 1], &[0, 1, 2]], 0).unwrap();
+        assert_eq!(facets[0].corners(), 0);
+        assert_eq!(TriFacet::count(&facets), 1);
+    }
+
+    #[test]
+    fn short_face_list_is_an_error() {
+        assert!(read_facets(&[&[0, 1, 2], &[1, 3, 4, 2]], 4).is_err());
+    }
+}
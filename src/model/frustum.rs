@@ -0,0 +1,95 @@
+use crate::model::Bounds;
+use cgmath::{InnerSpace, Matrix, Matrix4, Vector4};
+
+// The six planes around what a camera sees, to skip drawing artifacts
+// wholly outside it.  Each plane is (a, b, c, d), with a x + b y + c z + d
+// at least 0 on the inside.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Frustum {
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    // The planes of a view-projection matrix, read off its rows (Gribb and
+    // Hartmann), for wgpu's clip space: x and y from -w to w, and z from 0
+    // to w.
+    pub fn of(view_proj: Matrix4<f32>) -> Frustum {
+        let rows = view_proj.transpose();
+        let (x, y, z, w) = (rows.x, rows.y, rows.z, rows.w);
+        Frustum {
+            planes: [w + x, w - x, w + y, w - y, z, w - z],
+        }
+    }
+
+    // Whether any of `bounds` may be in view: false only if the box is
+    // wholly outside one of the planes.  A box past a corner of the
+    // frustum, but not wholly outside any one plane, still counts; that
+    // costs no more than a draw call.
+    pub fn intersects(&self, bounds: &Bounds) -> bool {
+        self.planes.iter().all(|plane| {
+            // The corner furthest inside the plane.
+            let pick = |along: f32, min: f32, max: f32| if along >= 0.0 { max } else { min };
+            let corner = Vector4::new(
+                pick(plane.x, bounds.min.x, bounds.max.x),
+                pick(plane.y, bounds.min.y, bounds.max.y),
+                pick(plane.z, bounds.min.z, bounds.max.z),
+                1.0,
+            );
+            plane.dot(corner) >= 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Deg, Point3, Vector3};
+
+    // A camera at the origin looking down -z, seeing from 1 to 100 away,
+    // 90 degrees wide.  OpenGL's depth, -w to w, is moved to wgpu's, 0 to w.
+    fn frustum() -> Frustum {
+        #[rustfmt::skip]
+        let to_wgpu = Matrix4::new(
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 0.5, 0.0,
+            0.0, 0.0, 0.5, 1.0,
+        );
+        let projection = to_wgpu * cgmath::perspective(Deg(90.0), 1.0, 1.0, 100.0);
+        let view = Matrix4::look_at_rh(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0), Vector3::unit_y());
+        Frustum::of(projection * view)
+    }
+
+    fn cube(center: [f32; 3]) -> Bounds {
+        let [x, y, z] = center;
+        Bounds {
+            min: Point3::new(x - 0.5, y - 0.5, z - 0.5),
+            max: Point3::new(x + 0.5, y + 0.5, z + 0.5),
+        }
+    }
+
+    #[test]
+    fn box_inside() {
+        assert!(frustum().intersects(&cube([0.0, 0.0, -10.0])));
+    }
+
+    #[test]
+    fn box_behind_near_plane() {
+        assert!(!frustum().intersects(&cube([0.0, 0.0, 0.0])));
+        assert!(!frustum().intersects(&cube([0.0, 0.0, 10.0])));
+    }
+
+    #[test]
+    fn box_beyond_far_plane() {
+        assert!(!frustum().intersects(&cube([0.0, 0.0, -200.0])));
+    }
+
+    #[test]
+    fn box_outside_side_plane() {
+        // At 10 away the view is 20 wide; 15 to the side is out.
+        assert!(!frustum().intersects(&cube([15.0, 0.0, -10.0])));
+        assert!(!frustum().intersects(&cube([0.0, -15.0, -10.0])));
+        // Straddling the plane still counts.
+        assert!(frustum().intersects(&cube([10.0, 0.0, -10.0])));
+    }
+}
@@ -1,7 +1,18 @@
 mod vertex;
 mod wireframe;
 mod facet;
+mod edge;
+mod weld;
+mod bounds;
+mod frustum;
+mod normal;
+pub mod origin;
 
-pub use vertex::PlainVertex;
-pub use facet::TriFacet;
+pub use vertex::{ColoredVertex, MeshVertex, Opacity, PlainVertex, TexCoord, TranslucentVertex, VertexColor};
+pub use facet::{Polygon, TriFacet};
 pub use wireframe::Wireframe;
+pub use edge::ScalarVertex;
+pub use weld::weld;
+pub use bounds::Bounds;
+pub use frustum::Frustum;
+pub use normal::Normal;
@@ -0,0 +1,78 @@
+use crate::{
+    model::{PlainVertex, TriFacet},
+    Element, IntoElement,
+};
+use ply_rs::ply;
+use std::mem;
+
+// A vertex normal for shading meshes, in its own vertex buffer beside the
+// positions, like TexCoord.  Read from nx/ny/nz when the PLY file has
+// them; otherwise computed from the facets.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Normal {
+    pub normal: [f32; 3],
+}
+
+impl IntoElement for Normal {
+    fn element() -> Element { Element::Vertex }
+}
+
+const NORMAL_NAMES: [&str; 3] = ["nx", "ny", "nz"];
+
+impl Normal {
+    const ATTRIBS: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![1 => Float32x3];
+
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Normal>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+
+    pub fn in_header(header: &ply::Header) -> bool {
+        match header.elements.get(&Element::Vertex.to_string()) {
+            Some(element) => NORMAL_NAMES.iter().all(|name| element.properties.contains_key(*name)),
+            None => false,
+        }
+    }
+
+    // Set from a PLY property, if it is one of nx, ny or nz.  Returns
+    // false for any other property.
+    pub fn set(&mut self, key: &str, v: f32) -> bool {
+        match NORMAL_NAMES.iter().position(|name| *name == key) {
+            Some(axis) => {
+                self.normal[axis] = v;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Every vertex gets the sum of the normals of the triangles around
+    // it, weighted by their area, so a flat region shades flat however it
+    // is triangulated.  Vertices on no triangle, and triangles with
+    // indices out of range, are left out; their normals are zero, which
+    // the shader draws unlit.
+    pub fn of_triangles(vertices: &[PlainVertex], triangles: &[TriFacet]) -> Vec<Normal> {
+        let mut normals = vec![[0.0f32; 3]; vertices.len()];
+        for triangle in triangles {
+            let corners = triangle.vertex_indices.map(|index| usize::try_from(index).ok().filter(|&i| i < vertices.len()));
+            let [Some(a), Some(b), Some(c)] = corners else {
+                continue;
+            };
+            let [p, q, r] = [a, b, c].map(|index| vertices[index].position);
+            let u = [q[0] - p[0], q[1] - p[1], q[2] - p[2]];
+            let v = [r[0] - p[0], r[1] - p[1], r[2] - p[2]];
+            // Twice the area, along the facet normal.
+            let cross = [u[1] * v[2] - u[2] * v[1], u[2] * v[0] - u[0] * v[2], u[0] * v[1] - u[1] * v[0]];
+            for index in [a, b, c] {
+                for axis in 0..3 {
+                    normals[index][axis] += cross[axis];
+                }
+            }
+        }
+        normals.into_iter().map(|normal| Normal { normal }).collect()
+    }
+}
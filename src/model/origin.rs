@@ -0,0 +1,33 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    OnceLock,
+};
+
+// --recenter: positions are moved so that the first vertex loaded is at
+// the origin.  Survey and geospatial data sit hundreds of kilometers from
+// their origin, in doubles; an f32 there resolves only to centimeters or
+// worse, and the GPU works in f32.  Subtracting the origin while still in
+// f64 keeps the precision near the data, where it is needed.
+//
+// The origin is shared by every artifact, in every pane, so artifacts in
+// the same coordinates still line up.  Each axis is fixed by the first
+// value read for it, which for any format is the first vertex.
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static ORIGIN: [OnceLock<f64>; 3] = [OnceLock::new(), OnceLock::new(), OnceLock::new()];
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+// The coordinate along `axis` to draw, for one read from a file.
+pub fn recenter(axis: usize, v: f64) -> f32 {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return v as f32;
+    }
+    let origin = *ORIGIN[axis].get_or_init(|| {
+        log::info!("Recentered {} on {}", ["x", "y", "z"][axis], v);
+        v
+    });
+    (v - origin) as f32
+}
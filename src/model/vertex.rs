@@ -1,4 +1,4 @@
-use crate::{Element, IntoElement};
+use crate::{model::{origin, Normal}, Element, IntoElement};
 use std::mem;
 use ply_rs::ply;
 
@@ -24,6 +24,11 @@ impl PlainVertex {
             attributes: &Self::ATTRIBS,
         }
     }
+
+    // A position read from a file, moved for --recenter.
+    pub fn at(position: [f64; 3]) -> PlainVertex {
+        PlainVertex { position: [0, 1, 2].map(|axis| origin::recenter(axis, position[axis])) }
+    }
 }
 
 // Teach ply_rs how model a vertex.
@@ -32,12 +37,329 @@ impl ply::PropertyAccess for PlainVertex {
         PlainVertex { position: [0.0, 0.0, 0.0] }
     }
 
+    // Properties are matched by name, so their order in the file does not
+    // matter.
+    fn set_property(&mut self, key: String, property: ply::Property) {
+        let axis = match key.as_ref() {
+            "x" => 0,
+            "y" => 1,
+            "z" => 2,
+            _ => return,
+        };
+        if let Some(v) = wide_scalar(&property) {
+            self.position[axis] = origin::recenter(axis, v);
+        }
+    }
+}
+
+// Any numeric PLY scalar, as f32.
+fn scalar(property: &ply::Property) -> Option<f32> {
+    wide_scalar(property).map(|v| v as f32)
+}
+
+// Any numeric PLY scalar, as f64.  Instruments write positions as
+// anything from char to double, and doubles far from the origin must not
+// be narrowed before --recenter moves them.
+fn wide_scalar(property: &ply::Property) -> Option<f64> {
+    match *property {
+        ply::Property::Char(v) => Some(v as f64),
+        ply::Property::UChar(v) => Some(v as f64),
+        ply::Property::Short(v) => Some(v as f64),
+        ply::Property::UShort(v) => Some(v as f64),
+        ply::Property::Int(v) => Some(v as f64),
+        ply::Property::UInt(v) => Some(v as f64),
+        ply::Property::Float(v) => Some(v as f64),
+        ply::Property::Double(v) => Some(v),
+        _ => None,
+    }
+}
+
+// Texture coordinates, kept in their own vertex buffer beside the
+// positions, so the untextured render modes share the position buffer
+// unchanged.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TexCoord {
+    pub uv: [f32; 2],
+}
+
+impl IntoElement for TexCoord {
+    fn element() -> Element { Element::Vertex }
+}
+
+impl TexCoord {
+    const ATTRIBS: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![1 => Float32x2];
+
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<TexCoord>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+
+    // Whether the vertices in this header carry texture coordinates,
+    // under any of the names PLY writers use for them.
+    pub fn in_header(header: &ply::Header) -> bool {
+        let element = match header.elements.get(&Element::Vertex.to_string()) {
+            Some(element) => element,
+            None => return false,
+        };
+        let has = |names: &[&str]| names.iter().any(|name| element.properties.contains_key(*name));
+        has(&U_NAMES) && has(&V_NAMES)
+    }
+}
+
+const U_NAMES: [&str; 4] = ["u", "s", "texture_u", "texture_s"];
+const V_NAMES: [&str; 4] = ["v", "t", "texture_v", "texture_t"];
+
+// A mesh vertex as read from a PLY file with texture coordinates or
+// normals.  It is split into a PlainVertex, a TexCoord and a Normal for
+// the GPU.
+#[derive(Copy, Clone, Debug)]
+pub struct MeshVertex {
+    pub position: PlainVertex,
+    pub uv: TexCoord,
+    pub normal: Normal,
+}
+
+impl ply::PropertyAccess for MeshVertex {
+    fn new() -> Self {
+        MeshVertex {
+            position: PlainVertex { position: [0.0, 0.0, 0.0] },
+            uv: TexCoord { uv: [0.0, 0.0] },
+            normal: Normal { normal: [0.0, 0.0, 0.0] },
+        }
+    }
+
+    fn set_property(&mut self, key: String, property: ply::Property) {
+        match scalar(&property) {
+            Some(v) if U_NAMES.contains(&key.as_str()) => self.uv.uv[0] = v,
+            Some(v) if V_NAMES.contains(&key.as_str()) => self.uv.uv[1] = v,
+            Some(v) if self.normal.set(&key, v) => {}
+            _ => ply::PropertyAccess::set_property(&mut self.position, key, property),
+        }
+    }
+}
+
+// Per-point opacity, from 0 (invisible) to 1, in its own vertex buffer
+// like TexCoord.  Exporters store confidence under several names, as
+// floats from 0 to 1 or as integers scaled to their full range.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Opacity {
+    pub alpha: f32,
+}
+
+impl IntoElement for Opacity {
+    fn element() -> Element { Element::Vertex }
+}
+
+const OPACITY_NAMES: [&str; 4] = ["alpha", "opacity", "quality", "confidence"];
+
+impl Opacity {
+    const ATTRIBS: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![1 => Float32];
+
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Opacity>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+
+    pub fn in_header(header: &ply::Header) -> bool {
+        match header.elements.get(&Element::Vertex.to_string()) {
+            Some(element) => OPACITY_NAMES.iter().any(|name| element.properties.contains_key(*name)),
+            None => false,
+        }
+    }
+
+    fn from_property(property: &ply::Property) -> Option<f32> {
+        let alpha = match *property {
+            ply::Property::Float(v) => v,
+            ply::Property::Double(v) => v as f32,
+            ply::Property::UChar(v) => v as f32 / u8::MAX as f32,
+            ply::Property::UShort(v) => v as f32 / u16::MAX as f32,
+            _ => return None,
+        };
+        Some(alpha.clamp(0.0, 1.0))
+    }
+}
+
+// A vertex as read from a PLY file with an opacity.  It is split into a
+// PlainVertex and an Opacity for the GPU.
+#[derive(Copy, Clone, Debug)]
+pub struct TranslucentVertex {
+    pub position: PlainVertex,
+    pub opacity: Opacity,
+}
+
+impl ply::PropertyAccess for TranslucentVertex {
+    fn new() -> Self {
+        TranslucentVertex {
+            position: PlainVertex { position: [0.0, 0.0, 0.0] },
+            opacity: Opacity { alpha: 1.0 },
+        }
+    }
+
     fn set_property(&mut self, key: String, property: ply::Property) {
-        match (key.as_ref(), property) {
-            ("x", ply::Property::Float(v)) => self.position[0] = v,
-            ("y", ply::Property::Float(v)) => self.position[1] = v,
-            ("z", ply::Property::Float(v)) => self.position[2] = v,
-            (_, _) => {}
+        if OPACITY_NAMES.contains(&key.as_str()) {
+            if let Some(alpha) = Opacity::from_property(&property) {
+                self.opacity.alpha = alpha;
+            }
+            return;
         }
+        ply::PropertyAccess::set_property(&mut self.position, key, property);
+    }
+}
+
+// Per-point color, in its own vertex buffer like Opacity.  Photogrammetry
+// and scanner exports store it as red/green/blue, mostly as uchar, with
+// an optional alpha.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct VertexColor {
+    pub color: [f32; 4],
+}
+
+impl IntoElement for VertexColor {
+    fn element() -> Element { Element::Vertex }
+}
+
+const RED_NAMES: [&str; 2] = ["red", "diffuse_red"];
+const GREEN_NAMES: [&str; 2] = ["green", "diffuse_green"];
+const BLUE_NAMES: [&str; 2] = ["blue", "diffuse_blue"];
+
+impl VertexColor {
+    const ATTRIBS: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![1 => Float32x4];
+
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<VertexColor>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+
+    // All three channels must be there; a lone "red" is more likely some
+    // other measurement than a color.
+    pub fn in_header(header: &ply::Header) -> bool {
+        let element = match header.elements.get(&Element::Vertex.to_string()) {
+            Some(element) => element,
+            None => return false,
+        };
+        let has = |names: &[&str]| names.iter().any(|name| element.properties.contains_key(*name));
+        has(&RED_NAMES) && has(&GREEN_NAMES) && has(&BLUE_NAMES)
+    }
+}
+
+// A vertex as read from a PLY file with a color, and maybe an opacity.
+// It is split into a PlainVertex, a VertexColor and an Opacity for the
+// GPU.  The opacity is also the alpha of the color, so the colored render
+// mode fades points just as the translucent one does.
+#[derive(Copy, Clone, Debug)]
+pub struct ColoredVertex {
+    pub position: PlainVertex,
+    pub color: VertexColor,
+    pub opacity: Opacity,
+}
+
+impl ply::PropertyAccess for ColoredVertex {
+    fn new() -> Self {
+        ColoredVertex {
+            position: PlainVertex { position: [0.0, 0.0, 0.0] },
+            color: VertexColor { color: [0.0, 0.0, 0.0, 1.0] },
+            opacity: Opacity { alpha: 1.0 },
+        }
+    }
+
+    fn set_property(&mut self, key: String, property: ply::Property) {
+        let channel = match key.as_str() {
+            key if RED_NAMES.contains(&key) => 0,
+            key if GREEN_NAMES.contains(&key) => 1,
+            key if BLUE_NAMES.contains(&key) => 2,
+            key if OPACITY_NAMES.contains(&key) => 3,
+            _ => return ply::PropertyAccess::set_property(&mut self.position, key, property),
+        };
+        // Channels are scaled like opacity: integers over their full
+        // range, floats from 0 to 1.
+        if let Some(v) = Opacity::from_property(&property) {
+            self.color.color[channel] = v;
+            if channel == 3 {
+                self.opacity.alpha = v;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read_header;
+    use ply::PropertyAccess;
+    use ply_rs::parser::Parser;
+    use std::io::Cursor;
+
+    #[test]
+    fn every_scalar_type() {
+        let properties = [
+            ply::Property::Char(-7),
+            ply::Property::UChar(200),
+            ply::Property::Short(-300),
+            ply::Property::UShort(60000),
+            ply::Property::Int(-70000),
+            ply::Property::UInt(4_000_000),
+            ply::Property::Float(1.5),
+            ply::Property::Double(-2.25),
+        ];
+        let values: Vec<Option<f32>> = properties.iter().map(scalar).collect();
+        let expected = [-7.0, 200.0, -300.0, 60000.0, -70000.0, 4_000_000.0, 1.5, -2.25].map(Some);
+        assert_eq!(values, expected);
+        assert_eq!(scalar(&ply::Property::ListInt(vec![1])), None);
+    }
+
+    // Properties are set by name, in whatever order and type the file
+    // has them.
+    #[test]
+    fn position_in_any_order() {
+        let mut vertex = PlainVertex::new();
+        vertex.set_property("z".to_string(), ply::Property::Short(-3));
+        vertex.set_property("y".to_string(), ply::Property::UChar(2));
+        vertex.set_property("x".to_string(), ply::Property::Double(1.0));
+        vertex.set_property("intensity".to_string(), ply::Property::Float(9.0));
+        assert_eq!(vertex.position, [1.0, 2.0, -3.0]);
+    }
+
+    // Survey coordinates, far from the origin, are not narrowed to f32
+    // before --recenter can move them.
+    #[test]
+    fn doubles_keep_precision() {
+        let easting = 500_000.01;
+        assert_eq!(wide_scalar(&ply::Property::Double(easting)), Some(easting));
+        assert_ne!(easting as f32 as f64, easting);
+    }
+
+    #[test]
+    fn double_positions() {
+        let mut f = Cursor::new(
+            "ply\n\
+             format binary_big_endian 1.0\n\
+             element vertex 2\n\
+             property double x\n\
+             property double y\n\
+             property double z\n\
+             end_header\n"
+                .as_bytes()
+                .iter()
+                .copied()
+                .chain([1.5f64, -2.0, 3.25, 1e6, 0.5, -0.125].iter().flat_map(|v| v.to_be_bytes()))
+                .collect::<Vec<u8>>(),
+        );
+        let header = read_header(&mut f).unwrap();
+        let element = Element::Vertex.require(&header).unwrap();
+        let vertices = Parser::<PlainVertex>::new().read_payload_for_element(&mut f, element, &header).unwrap();
+        let positions: Vec<[f32; 3]> = vertices.iter().map(|v| v.position).collect();
+        assert_eq!(positions, [[1.5, -2.0, 3.25], [1e6, 0.5, -0.125]]);
     }
 }
@@ -0,0 +1,112 @@
+use crate::model::{PlainVertex, Polygon};
+use itertools::Itertools;
+use std::collections::HashMap;
+
+// Merge vertices that lie within `epsilon` of each other, and point the
+// facets at the survivors.  Triangle soup, where every facet carries its
+// own copy of each corner, shrinks to one vertex per distinct position.
+//
+// Positions are hashed into cells `epsilon` wide, so a neighbor within
+// `epsilon` is always in the same cell or one of the 26 around it.  An
+// epsilon of zero welds exact duplicates only.
+pub fn weld(vertices: &[PlainVertex], facets: &mut [Polygon], epsilon: f32) -> Vec<PlainVertex> {
+    let cell = |position: [f32; 3]| -> [i64; 3] {
+        match epsilon > 0.0 {
+            true => position.map(|v| (v / epsilon).floor() as i64),
+            // Adding zero turns -0.0 into 0.0, which must weld.
+            false => position.map(|v| (v + 0.0).to_bits() as i64),
+        }
+    };
+    let near = |a: [f32; 3], b: [f32; 3]| {
+        let d2: f32 = (0..3).map(|i| (a[i] - b[i]) * (a[i] - b[i])).sum();
+        d2 <= epsilon * epsilon
+    };
+
+    let mut welded: Vec<PlainVertex> = vec![];
+    let mut grid: HashMap<[i64; 3], Vec<u32>> = HashMap::new();
+    let mut remap: Vec<i32> = Vec::with_capacity(vertices.len());
+
+    for vertex in vertices {
+        let [x, y, z] = cell(vertex.position);
+        let reach = if epsilon > 0.0 { 1 } else { 0 };
+        let neighbors = (-reach..=reach)
+            .cartesian_product(-reach..=reach)
+            .cartesian_product(-reach..=reach)
+            .map(|((dx, dy), dz)| [x + dx, y + dy, z + dz]);
+        let found = neighbors
+            .filter_map(|neighbor| grid.get(&neighbor))
+            .flatten()
+            .find(|&&index| near(welded[index as usize].position, vertex.position));
+
+        let index = match found {
+            Some(&index) => index,
+            None => {
+                let index = welded.len() as u32;
+                welded.push(*vertex);
+                grid.entry([x, y, z]).or_default().push(index);
+                index
+            }
+        };
+        remap.push(index as i32);
+    }
+
+    // Indices out of range are left alone, for the GPU to ignore as before.
+    for facet in facets.iter_mut() {
+        for index in facet.vertex_indices.iter_mut() {
+            if let Some(&welded) = remap.get(*index as usize) {
+                *index = welded;
+            }
+        }
+    }
+
+    welded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A unit cube as triangle soup: 12 triangles, each with its own 3
+    // corners.
+    fn cube_soup() -> (Vec<PlainVertex>, Vec<Polygon>) {
+        let corner = |i: usize| PlainVertex {
+            position: [(i & 1) as f32, (i >> 1 & 1) as f32, (i >> 2 & 1) as f32],
+        };
+        let quads = [[0, 1, 3, 2], [4, 6, 7, 5], [0, 4, 5, 1], [2, 3, 7, 6], [0, 2, 6, 4], [1, 5, 7, 3]];
+        let triangles = quads.iter().flat_map(|[a, b, c, d]| [[*a, *b, *c], [*a, *c, *d]]);
+        let vertices: Vec<PlainVertex> = triangles.flatten().map(corner).collect();
+        let facets = (0..12)
+            .map(|t| Polygon {
+                vertex_indices: (3 * t..3 * t + 3).collect(),
+            })
+            .collect();
+        (vertices, facets)
+    }
+
+    #[test]
+    fn cube_welds_to_eight_vertices() {
+        let (vertices, mut facets) = cube_soup();
+        assert_eq!(vertices.len(), 36);
+        let welded = weld(&vertices, &mut facets, 1e-6);
+        assert_eq!(welded.len(), 8);
+        for (t, facet) in facets.iter().enumerate() {
+            for (c, &index) in facet.vertex_indices.iter().enumerate() {
+                assert_eq!(welded[index as usize].position, vertices[3 * t + c].position);
+            }
+        }
+    }
+
+    #[test]
+    fn only_near_vertices_weld() {
+        let (vertices, mut facets) = cube_soup();
+        assert_eq!(weld(&vertices, &mut facets.clone(), 0.0).len(), 8);
+        let shifted: Vec<PlainVertex> = vertices
+            .iter()
+            .enumerate()
+            .map(|(i, v)| PlainVertex {
+                position: v.position.map(|p| p + i as f32 * 1e-3),
+            })
+            .collect();
+        assert_eq!(weld(&shifted, &mut facets, 1e-6).len(), 36);
+    }
+}
@@ -1,35 +1,80 @@
-use crate::{Element, IntoElement};
-use ply_rs::ply;
+use crate::{model::Polygon, Element, IntoElement};
+use std::collections::HashSet;
 
+// One edge of a facet outline, as a pair of vertex indices for a line
+// list.
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Wireframe {
-    pub vertex_indices: [i32; 6],
+    pub vertex_indices: [i32; 2],
 }
 
-// Teach worldview how to find the vertex in the PLY header
+// Teach worldview how to find the vertex in the PLY header.
 impl IntoElement for Wireframe {
     fn element() -> Element { Element::Facet }
 }
 
-// Teach ply_rs how model a wireframe facet.
-impl ply::PropertyAccess for Wireframe {
-    fn new() -> Self {
-        Wireframe {
-            vertex_indices: [0, 0, 0, 0, 0, 0],
-        }
+impl Wireframe {
+    // How many edges outline `facets` at most: one per corner.  Buffers
+    // are sized for this, before the shared edges are known.
+    pub fn count(facets: &[Polygon]) -> usize {
+        facets.iter().map(Polygon::corners).sum()
+    }
+
+    // Outline every facet along its sides; quads get four edges, not the
+    // five a triangulated quad would show.  An edge shared by neighboring
+    // facets, in either direction, is drawn once.
+    pub fn outline(facets: &[Polygon]) -> Vec<Wireframe> {
+        let mut seen = HashSet::new();
+        facets
+            .iter()
+            .flat_map(|facet| facet.edges())
+            .filter(|&[a, b]| seen.insert([a.min(b), a.max(b)]))
+            .map(|vertex_indices| Wireframe { vertex_indices })
+            .collect()
     }
+}
 
-    fn set_property(&mut self, key: String, property: ply::Property) {
-        match (key.as_ref(), property) {
-            ("vertex_indices", ply::Property::ListInt(vec)) => {
-                if vec.len() == 3 {
-                    self.vertex_indices = [vec[0], vec[1], vec[1], vec[2], vec[2], vec[0]];
-                } else {
-                    panic!("Wrong number of indices");
-                }
-            }
-            (_, _) => {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn facet(vertex_indices: &[i32]) -> Polygon {
+        Polygon {
+            vertex_indices: vertex_indices.to_vec(),
         }
     }
+
+    fn indices(edges: &[Wireframe]) -> Vec<i32> {
+        edges.iter().flat_map(|edge| edge.vertex_indices).collect()
+    }
+
+    // The index buffer is sized by count(), and the draw covers two
+    // indices for each edge of outline(); a triangle draws 6, a quad 8.
+    #[test]
+    fn count_covers_outline() {
+        let facets = [facet(&[0, 1, 2]), facet(&[3, 4, 5, 6])];
+        assert_eq!(Wireframe::count(&facets), 7);
+        assert_eq!(indices(&Wireframe::outline(&facets[..1])).len(), 6);
+        assert_eq!(indices(&Wireframe::outline(&facets)).len(), 14);
+
+        // Shared edges make the outline shorter, never longer.
+        let facets = [facet(&[0, 1, 2]), facet(&[0, 2, 3])];
+        assert_eq!(Wireframe::count(&facets), 6);
+        assert_eq!(Wireframe::outline(&facets).len(), 5);
+    }
+
+    #[test]
+    fn quad_has_four_edges() {
+        let edges = Wireframe::outline(&[facet(&[0, 1, 2, 3])]);
+        assert_eq!(indices(&edges), [0, 1, 1, 2, 2, 3, 3, 0]);
+    }
+
+    #[test]
+    fn shared_edge_is_drawn_once() {
+        // Two triangles of a quad, sharing the diagonal 0-2 in opposite
+        // directions.
+        let edges = Wireframe::outline(&[facet(&[0, 1, 2]), facet(&[0, 2, 3])]);
+        assert_eq!(indices(&edges), [0, 1, 1, 2, 2, 0, 2, 3, 3, 0]);
+    }
 }
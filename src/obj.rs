@@ -0,0 +1,120 @@
+use crate::{
+    element::header_for,
+    model::{Normal, PlainVertex, Polygon, TexCoord},
+};
+use ply_rs::ply;
+use std::io::{self, BufRead};
+
+// Wavefront OBJ files, for meshing tools that do not write PLY.  Positions,
+// texture coordinates, normals and faces of any number of corners are
+// read; materials, lines and points are not.  Every object and group in
+// the file joins one artifact.  A file of vertices alone is a point cloud.
+//
+// The rest of worldview sizes its buffers from a PLY header, so the file
+// is described by the header a PLY file with the same elements would
+// have.
+pub struct Obj {
+    pub vertices: Vec<PlainVertex>,
+    pub facets: Vec<Polygon>,
+    pub uvs: Option<Vec<TexCoord>>,
+    pub normals: Option<Vec<Normal>>,
+}
+
+impl Obj {
+    pub fn read(f: &mut impl BufRead) -> io::Result<Obj> {
+        let mut text = String::new();
+        f.read_to_string(&mut text)?;
+
+        // One index per vertex, so the texture coordinates and normals
+        // line up with the positions, as in PLY.
+        let options = tobj::LoadOptions {
+            single_index: true,
+            ignore_points: true,
+            ignore_lines: true,
+            ..Default::default()
+        };
+        let (models, _) = tobj::load_obj_buf(&mut text.as_bytes(), &options, |_| Err(tobj::LoadError::OpenFileFailed))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        // tobj keeps only the vertices that faces use, so without faces
+        // there would be none at all.
+        if models.iter().all(|model| model.mesh.indices.is_empty()) {
+            return Ok(Obj {
+                vertices: read_positions(&text)?,
+                facets: vec![],
+                uvs: None,
+                normals: None,
+            });
+        }
+
+        let mut obj = Obj {
+            vertices: vec![],
+            facets: vec![],
+            uvs: Some(vec![]),
+            normals: Some(vec![]),
+        };
+        for model in models {
+            let mesh = model.mesh;
+            let offset = obj.vertices.len() as i32;
+            let count = mesh.positions.len() / 3;
+            obj.vertices.extend(mesh.positions.chunks_exact(3).map(|p| PlainVertex::at([p[0], p[1], p[2]].map(f64::from))));
+            // Either every vertex has them, or they are of no use.
+            match (&mut obj.uvs, mesh.texcoords.len() == 2 * count) {
+                (Some(uvs), true) => uvs.extend(mesh.texcoords.chunks_exact(2).map(|t| TexCoord { uv: [t[0], t[1]] })),
+                _ => obj.uvs = None,
+            }
+            match (&mut obj.normals, mesh.normals.len() == 3 * count) {
+                (Some(normals), true) => {
+                    normals.extend(mesh.normals.chunks_exact(3).map(|n| Normal { normal: [n[0], n[1], n[2]] }))
+                }
+                _ => obj.normals = None,
+            }
+
+            // Without arities, every face is a triangle.
+            let mut indices = mesh.indices.iter().map(|index| *index as i32 + offset);
+            let arities = match mesh.face_arities.is_empty() {
+                true => vec![3; mesh.indices.len() / 3],
+                false => mesh.face_arities,
+            };
+            for arity in arities {
+                obj.facets.push(Polygon {
+                    vertex_indices: indices.by_ref().take(arity as usize).collect(),
+                });
+            }
+        }
+        Ok(obj)
+    }
+
+    // The header of a PLY file holding the same elements.
+    pub fn header(&self) -> ply::Header {
+        let mut names = vec!["x", "y", "z"];
+        if self.uvs.is_some() {
+            names.extend(["u", "v"]);
+        }
+        if self.normals.is_some() {
+            names.extend(["nx", "ny", "nz"]);
+        }
+        header_for(self.vertices.len(), &names, self.facets.len())
+    }
+}
+
+// The positions of the "v x y z" lines, for files that tobj finds nothing
+// in.  A fourth number, the weight, or vertex colors after the position
+// are ignored.
+fn read_positions(text: &str) -> io::Result<Vec<PlainVertex>> {
+    let mut vertices = vec![];
+    for (number, line) in text.lines().enumerate() {
+        let mut words = line.split_whitespace();
+        if words.next() != Some("v") {
+            continue;
+        }
+        let position: Vec<f64> = words.take(3).map(|word| word.parse()).collect::<Result<_, _>>().map_err(|err| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", number + 1, err))
+        })?;
+        let position = position.try_into().map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("line {}: a vertex needs x, y and z", number + 1))
+        })?;
+        vertices.push(PlainVertex::at(position));
+    }
+    Ok(vertices)
+}
@@ -0,0 +1,391 @@
+use crate::{pipeline, Colormap};
+use std::mem;
+
+// Overlay draws flat, screen space decorations over the 3D scene: colored
+// rectangles and text in a small built-in bitmap font.  Everything is
+// rebuilt from scratch every frame; there is little enough of it that
+// this is cheaper than keeping track of what changed.
+//
+// Coordinates are in physical pixels, from the top left of the window.
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct OverlayVertex {
+    position: [f32; 2],
+    color: [f32; 4],
+}
+
+impl OverlayVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4];
+
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<OverlayVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+// Glyphs are 5x7 pixels, with one pixel of spacing, at scale 1.
+pub const GLYPH_WIDTH: f32 = 6.0;
+pub const GLYPH_HEIGHT: f32 = 8.0;
+
+// Which corner of the window a decoration is anchored to.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Corner {
+    TopLeft,
+    #[default]
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+pub struct Overlay {
+    pipeline: wgpu::RenderPipeline,
+    buffer: Option<wgpu::Buffer>,
+    vertices: Vec<OverlayVertex>,
+    num_vertices: u32,
+}
+
+impl Overlay {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Overlay {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("overlay::shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                (include_str!("pipeline/shader/overlay.wsgl").to_owned()).into(),
+            ),
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("overlay::pipeline_layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("overlay::render_pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                compilation_options: Default::default(),
+                entry_point: "vs_main",
+                buffers: &[OverlayVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                compilation_options: Default::default(),
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            depth_stencil: pipeline::ignore_depth(),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Overlay {
+            pipeline,
+            buffer: None,
+            vertices: vec![],
+            num_vertices: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    // A rectangle with a color per corner, clockwise from the top left.
+    pub fn gradient(&mut self, x: f32, y: f32, w: f32, h: f32, colors: [[f32; 4]; 4]) {
+        let corners = [[x, y], [x + w, y], [x + w, y + h], [x, y + h]];
+        for i in [0, 3, 1, 1, 3, 2] {
+            self.vertices.push(OverlayVertex {
+                position: corners[i],
+                color: colors[i],
+            });
+        }
+    }
+
+    pub fn rect(&mut self, x: f32, y: f32, w: f32, h: f32, color: [f32; 4]) {
+        self.gradient(x, y, w, h, [color; 4]);
+    }
+
+    // Draw a line of text with its top left corner at (x, y).  Letters
+    // are upper case only; characters without a glyph show as '?'.
+    pub fn text(&mut self, x: f32, y: f32, scale: f32, text: &str, color: [f32; 4]) {
+        for (i, c) in text.chars().enumerate() {
+            let left = x + i as f32 * GLYPH_WIDTH * scale;
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for col in 0..5 {
+                    if bits & (0x10 >> col) != 0 {
+                        self.rect(
+                            left + col as f32 * scale,
+                            y + row as f32 * scale,
+                            scale,
+                            scale,
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn text_width(text: &str, scale: f32) -> f32 {
+        text.chars().count() as f32 * GLYPH_WIDTH * scale
+    }
+
+    // A vertical ramp through the colormap from `min` (bottom) to `max`
+    // (top) with labeled ticks, anchored to a corner of a `width` x `height`
+    // window.
+    #[allow(clippy::too_many_arguments)]
+    pub fn legend(
+        &mut self,
+        corner: Corner,
+        title: &str,
+        min: f32,
+        max: f32,
+        colormap: Colormap,
+        width: f32,
+        height: f32,
+    ) {
+        const MARGIN: f32 = 16.0;
+        const STRIP_WIDTH: f32 = 16.0;
+        const STRIP_HEIGHT: f32 = 200.0;
+        const SEGMENTS: usize = 32;
+        const SCALE: f32 = 2.0;
+        const INK: [f32; 4] = [0.1, 0.1, 0.1, 1.0];
+
+        let text_height = GLYPH_HEIGHT * SCALE;
+        let right = matches!(corner, Corner::TopRight | Corner::BottomRight);
+        let top = match corner {
+            Corner::TopLeft | Corner::TopRight => MARGIN,
+            Corner::BottomLeft | Corner::BottomRight => {
+                height - MARGIN - STRIP_HEIGHT - text_height - 8.0
+            }
+        };
+        let strip_x = match right {
+            true => width - MARGIN - STRIP_WIDTH,
+            false => MARGIN,
+        };
+        let strip_y = top + text_height + 8.0;
+
+        // Text hugs the strip, on the side facing the middle of the window.
+        let align = |text: &str| match right {
+            true => strip_x + STRIP_WIDTH - Overlay::text_width(text, SCALE),
+            false => strip_x,
+        };
+        self.text(align(title), top, SCALE, title, INK);
+
+        let segment = STRIP_HEIGHT / SEGMENTS as f32;
+        for i in 0..SEGMENTS {
+            let upper = colormap.color(1.0 - i as f32 / SEGMENTS as f32);
+            let lower = colormap.color(1.0 - (i + 1) as f32 / SEGMENTS as f32);
+            let y = strip_y + i as f32 * segment;
+            self.gradient(strip_x, y, STRIP_WIDTH, segment, [upper, upper, lower, lower]);
+        }
+
+        for t in [0.0, 0.5, 1.0] {
+            let label = format_value(min + t * (max - min));
+            let y = strip_y + (1.0 - t) * STRIP_HEIGHT;
+            let (tick_x, label_x) = match right {
+                true => (strip_x - 6.0, strip_x - 10.0 - Overlay::text_width(&label, SCALE)),
+                false => (strip_x + STRIP_WIDTH + 2.0, strip_x + STRIP_WIDTH + 10.0),
+            };
+            self.rect(tick_x, y - 1.0, 4.0, 2.0, INK);
+            self.text(label_x, y - text_height / 2.0, SCALE, &label, INK);
+        }
+    }
+
+    // A panel of (key, description) rows, centered in a `width` x `height`
+    // window, in as many columns as it takes to fit its height.
+    pub fn help(&mut self, rows: &[(&str, &str)], width: f32, height: f32) {
+        const PADDING: f32 = 16.0;
+        const GUTTER: f32 = 24.0;
+        const SCALE: f32 = 2.0;
+        const INK: [f32; 4] = [0.1, 0.1, 0.1, 1.0];
+        const PAPER: [f32; 4] = [1.0, 1.0, 1.0, 0.85];
+
+        let line_height = (GLYPH_HEIGHT + 2.0) * SCALE;
+        let widest = |column: fn(&(&str, &str)) -> f32| {
+            rows.iter().map(column).fold(0.0, f32::max)
+        };
+        let key_width = widest(|row| Overlay::text_width(row.0, SCALE));
+        let description_width = widest(|row| Overlay::text_width(row.1, SCALE));
+
+        let fits = (((height - 2.0 * PADDING) / line_height).floor() as usize).max(1);
+        let columns = rows.len().div_ceil(fits).max(1);
+        let per_column = rows.len().div_ceil(columns).max(1);
+        let column_width = key_width + GUTTER + description_width;
+
+        let panel_width = columns as f32 * (column_width + GUTTER) - GUTTER + 2.0 * PADDING;
+        let panel_height = per_column as f32 * line_height + 2.0 * PADDING;
+        let left = ((width - panel_width) / 2.0).max(0.0);
+        let top = ((height - panel_height) / 2.0).max(0.0);
+        self.rect(left, top, panel_width, panel_height, PAPER);
+
+        for (i, (key, description)) in rows.iter().enumerate() {
+            let x = left + PADDING + (i / per_column) as f32 * (column_width + GUTTER);
+            let y = top + PADDING + (i % per_column) as f32 * line_height;
+            self.text(x, y, SCALE, key, INK);
+            self.text(x + key_width + GUTTER, y, SCALE, description, INK);
+        }
+    }
+
+    // A thin bar along the bottom of a `width` x `height` window, filled
+    // to `fraction`.
+    pub fn progress(&mut self, fraction: f32, width: f32, height: f32) {
+        const HEIGHT: f32 = 6.0;
+        const TRACK: [f32; 4] = [0.1, 0.1, 0.1, 0.25];
+        const FILL: [f32; 4] = [0.2, 0.4, 0.9, 1.0];
+
+        let top = height - HEIGHT;
+        self.rect(0.0, top, width, HEIGHT, TRACK);
+        self.rect(0.0, top, width * fraction.clamp(0.0, 1.0), HEIGHT, FILL);
+    }
+
+    // Lines of text stacked up from the bottom left corner of a window
+    // `height` pixels tall.
+    pub fn readout(&mut self, lines: &[String], height: f32) {
+        const MARGIN: f32 = 16.0;
+        const SCALE: f32 = 2.0;
+        const INK: [f32; 4] = [0.1, 0.1, 0.1, 1.0];
+
+        let line_height = (GLYPH_HEIGHT + 4.0) * SCALE;
+        let top = height - MARGIN - lines.len() as f32 * line_height;
+        for (i, line) in lines.iter().enumerate() {
+            self.text(MARGIN, top + i as f32 * line_height, SCALE, line, INK);
+        }
+    }
+
+    // Convert to clip space and upload.  Must be called before the render
+    // pass that draws the overlay begins.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        width: f32,
+        height: f32,
+    ) {
+        let (w, h) = (width.max(1.0), height.max(1.0));
+        let vertices: Vec<OverlayVertex> = self
+            .vertices
+            .iter()
+            .map(|v| OverlayVertex {
+                position: [2.0 * v.position[0] / w - 1.0, 1.0 - 2.0 * v.position[1] / h],
+                color: v.color,
+            })
+            .collect();
+
+        let size = mem::size_of_val(vertices.as_slice()) as u64;
+        let too_small = match &self.buffer {
+            Some(buffer) => buffer.size() < size,
+            None => true,
+        };
+        if too_small {
+            self.buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                mapped_at_creation: false,
+                size: (2 * size).max(1024),
+                label: Some("overlay::vertices"),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            }));
+        }
+
+        let buffer = self.buffer.as_ref().unwrap();
+        queue.write_buffer(buffer, 0, bytemuck::cast_slice(&vertices));
+        self.num_vertices = vertices.len() as u32;
+    }
+
+    pub fn render<'rpass>(&'rpass self, render_pass: &mut wgpu::RenderPass<'rpass>) {
+        let buffer = match &self.buffer {
+            Some(buffer) if self.num_vertices > 0 => buffer,
+            _ => return,
+        };
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, buffer.slice(..));
+        render_pass.draw(0..self.num_vertices, 0..1);
+    }
+}
+
+// Short enough for a legend, whatever the magnitude.
+fn format_value(value: f32) -> String {
+    let magnitude = value.abs();
+    if magnitude != 0.0 && !(0.01..10_000.0).contains(&magnitude) {
+        format!("{:.2e}", value)
+    } else {
+        format!("{:.3}", value)
+    }
+}
+
+// A classic 5x7 character ROM.  Each row is five bits, most significant
+// bit on the left.
+fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        ' ' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+        '=' => [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '[' => [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E],
+        ']' => [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E],
+        '<' => [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02],
+        '>' => [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08],
+        '%' => [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+        '#' => [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A],
+        '*' => [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00],
+        '!' => [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04],
+        '\'' => [0x0C, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00],
+        _ => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    }
+}
@@ -0,0 +1,81 @@
+// Default artifact colors, so several clouds in one scene can be told
+// apart.  Each artifact name gets a hue from a hash of the name: stable
+// from run to run and machine to machine, unlike std's hasher, which
+// makes no such promise.  --no-palette goes back to one color per
+// artifact type.
+
+const SATURATION: f32 = 0.7;
+const VALUE: f32 = 0.85;
+
+pub fn color(name: &str) -> [f32; 4] {
+    // 32 bit FNV-1a.
+    let hash = name
+        .bytes()
+        .fold(0x811c9dc5u32, |hash, byte| (hash ^ byte as u32).wrapping_mul(0x01000193));
+    // FNV-1a leaves the last byte in the low bits, so "scan1" and "scan2"
+    // would get almost the same hue.  MurmurHash3's finalizer spreads
+    // every bit over the whole hash.
+    let hash = (hash ^ (hash >> 16)).wrapping_mul(0x85ebca6b);
+    let hash = (hash ^ (hash >> 13)).wrapping_mul(0xc2b2ae35);
+    let hash = hash ^ (hash >> 16);
+    let hue = hash as f32 / u32::MAX as f32;
+    let [r, g, b] = hsv(hue, SATURATION, VALUE);
+    [r, g, b, 1.0]
+}
+
+// Hue in [0, 1].
+fn hsv(hue: f32, saturation: f32, value: f32) -> [f32; 3] {
+    let sector = (hue * 6.0).rem_euclid(6.0);
+    let f = sector.fract();
+    let (p, q, t) = (
+        value * (1.0 - saturation),
+        value * (1.0 - saturation * f),
+        value * (1.0 - saturation * (1.0 - f)),
+    );
+    match sector as u32 {
+        0 => [value, t, p],
+        1 => [q, value, p],
+        2 => [p, value, t],
+        3 => [p, q, value],
+        4 => [t, p, value],
+        _ => [value, p, q],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hue(name: &str) -> f32 {
+        let [r, g, b, _] = color(name);
+        // Back from the color, for the sectors hsv() writes.
+        let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+        let sector = match max {
+            _ if max == r => (g - b) / (max - min),
+            _ if max == g => 2.0 + (b - r) / (max - min),
+            _ => 4.0 + (r - g) / (max - min),
+        };
+        (sector / 6.0).rem_euclid(1.0)
+    }
+
+    // The same name has the same color from run to run, and on every
+    // machine, so screenshots and muscle memory stay valid.
+    #[test]
+    fn color_is_stable() {
+        assert_eq!(color("cloud"), color("cloud"));
+        let expected = [0.44093, 0.85, 0.255, 1.0];
+        for (channel, expected) in color("cloud").into_iter().zip(expected) {
+            assert!((channel - expected).abs() < 1e-4, "{:?}", color("cloud"));
+        }
+    }
+
+    // Producers number their artifacts; names one digit apart must not
+    // look alike.
+    #[test]
+    fn similar_names_spread_apart() {
+        let hues: Vec<f32> = (0..10).map(|i| hue(&format!("scan{}", i))).collect();
+        let sectors: std::collections::HashSet<u32> = hues.iter().map(|hue| (hue * 6.0) as u32).collect();
+        assert!(sectors.len() >= 4, "{:?}", hues);
+        assert!((hue("scan1") - hue("scan2")).abs() > 0.1);
+    }
+}
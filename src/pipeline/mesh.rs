@@ -1,59 +1,109 @@
-use crate::{model, ArtifactUniform, Element, RenderArtifact, WindowState, IntoElement};
+use crate::{
+    model,
+    pipeline::{self, point_cloud, wireframe},
+    pool::{self, PooledBuffer},
+    upload::Upload,
+    ArtifactUniform, EdgeColor, Element, IntoElement, RenderArtifact, WindowState,
+};
 use wgpu::util::DeviceExt;
-use std::io::BufRead;
+use std::io::{self, BufRead};
 use ply_rs::{parser::Parser, ply};
 
 pub struct Mesh {
-    pub vertices: wgpu::Buffer,
-    pub indices: wgpu::Buffer,
+    pub vertices: PooledBuffer,
+    pub indices: PooledBuffer,
+    pub edges: PooledBuffer,
     stage_vertices: Vec<model::PlainVertex>,
+    bounds: Option<model::Bounds>,
     stage_indices: Vec<model::TriFacet>,
-    num_facets: u32,
+    stage_edges: Vec<model::Wireframe>,
+    num_vertices: u32,
+    num_triangles: u32,
+    num_edges: u32,
+    edge_color: EdgeColor,
+    scalar_edges: Option<PooledBuffer>,
+    stage_scalar_edges: Vec<model::ScalarVertex>,
+    pub scalar_range: Option<(f32, f32)>,
+    // Texture coordinates, if the mesh has them and a texture was given.
+    textured: bool,
+    uvs: Option<PooledBuffer>,
+    stage_uvs: Vec<model::TexCoord>,
+    // Vertex normals for shading, read or computed from the facets.
+    normals: PooledBuffer,
+    stage_normals: Vec<model::Normal>,
 }
 
 impl Mesh {
-    pub fn new(device: &wgpu::Device, header: &ply::Header) -> Option<Mesh> {
-        if !header.elements.contains_key(&Element::Vertex.to_string())
-            || !header.elements.contains_key(&Element::Facet.to_string())
-        {
-            return None;
-        }
+    // As for the wireframe, the facets read say how many triangles and
+    // edges there are.
+    pub fn new(
+        device: &wgpu::Device,
+        header: &ply::Header,
+        facets: &[model::Polygon],
+        edge_color: EdgeColor,
+        textured: bool,
+    ) -> Option<Mesh> {
+        let vertex = Element::Vertex.find(header)?;
+        Element::Facet.find(header)?;
+        let (triangles, edge_count) = (model::TriFacet::count(facets), model::Wireframe::count(facets));
 
         let element_size = std::mem::size_of::<model::PlainVertex>();
-        let count = header.elements.get(&Element::Vertex.to_string()).unwrap().count;
-        let vertices = device.create_buffer(&wgpu::BufferDescriptor {
-            mapped_at_creation: false,
-            size: (2 * element_size * count) as u64,
-            label: Some("wireframe::vertices"),
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-        });
+        let count = vertex.count;
+        let num_vertices = count as u32;
+        let vertices = pool::buffer(device, "wireframe::vertices", pool::VERTEX, element_size * count);
 
         let element_size = std::mem::size_of::<model::TriFacet>();
-        let count = header.elements.get(&Element::Facet.to_string()).unwrap().count;
-        let indices = device.create_buffer(&wgpu::BufferDescriptor {
-            mapped_at_creation: false,
-            size: (4 * element_size * count) as u64,
-            label: Some("wireframe::indices"),
-            usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+        let indices = pool::buffer(device, "wireframe::indices", pool::INDEX, element_size * triangles);
+
+        // Edges are only drawn in wireframe mode, but are cheap enough to
+        // keep around so switching modes does not require a reload.
+        let element_size = std::mem::size_of::<model::Wireframe>();
+        let edges = pool::buffer(device, "mesh::edges", pool::INDEX, element_size * edge_count);
+
+        let uvs = (textured && model::TexCoord::in_header(header)).then(|| {
+            let element_size = std::mem::size_of::<model::TexCoord>();
+            pool::buffer(device, "mesh::uvs", pool::VERTEX, element_size * num_vertices as usize)
         });
 
+        let element_size = std::mem::size_of::<model::Normal>();
+        let normals = pool::buffer(device, "mesh::normals", pool::VERTEX, element_size * num_vertices as usize);
+
         Some(Mesh {
             vertices,
             indices,
+            edges,
             stage_vertices: vec![],
+            bounds: None,
             stage_indices: vec![],
-            num_facets: count as u32,
+            stage_edges: vec![],
+            num_vertices,
+            num_triangles: 0,
+            num_edges: 0,
+            edge_color,
+            scalar_edges: wireframe::create_scalar_edges(device, edge_count, edge_color),
+            stage_scalar_edges: vec![],
+            scalar_range: None,
+            textured,
+            uvs,
+            stage_uvs: vec![],
+            normals,
+            stage_normals: vec![],
         })
     }
 }
 
 impl RenderArtifact for Mesh {
+    type Stage = (
+        Vec<model::PlainVertex>,
+        Vec<model::Polygon>,
+        Option<Vec<model::TexCoord>>,
+        Option<Vec<model::Normal>>,
+    );
+
     fn update_count(&mut self, header: &ply::Header) {
-        self.num_facets = header
-            .elements
-            .get(&Element::Facet.to_string())
-            .unwrap()
-            .count as u32;
+        if let Some(element) = Element::Vertex.find(header) {
+            self.num_vertices = element.count as u32;
+        }
     }
 
     fn create_pipeline_layout(
@@ -63,35 +113,155 @@ impl RenderArtifact for Mesh {
     ) -> wgpu::PipelineLayout {
         device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("mesh::pipeline_layout"),
-            bind_group_layouts: &[&world_bind_group_layout, &artifact_bind_group_layout],
+            bind_group_layouts: &[world_bind_group_layout, artifact_bind_group_layout],
             push_constant_ranges: &[],
         })
     }
 
+    // Facets are shaded by a headlight, a light at the camera.
     fn create_pipeline(device: &wgpu::Device, state: &WindowState) -> wgpu::RenderPipeline {
+        Mesh::create_shaded_pipeline(device, state, "mesh::render_pipeline", wgpu::BlendState::REPLACE, pipeline::fill_depth(state.depth_bias))
+    }
+
+    const COLOR: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+
+    fn create_uniform_buffer(
+        device: &wgpu::Device,
+        color: Option<[f32; 4]>,
+        transform: [[f32; 4]; 4],
+    ) -> wgpu::Buffer {
+        let uniform = ArtifactUniform::new(color.unwrap_or(Self::COLOR), transform);
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("mesh::uniform_buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        })
+    }
+
+    fn needs_resize(&self, header: &ply::Header, (_, facets, _, _): &Self::Stage) -> bool {
+        let edges = model::Wireframe::count(facets);
+        model::PlainVertex::buffer_too_small(header, &self.vertices)
+            || model::TriFacet::too_small(model::TriFacet::count(facets), &self.indices)
+            || model::Wireframe::too_small(edges, &self.edges)
+            || model::Normal::buffer_too_small(header, &self.normals)
+            || wireframe::scalar_edges_too_small(edges, &self.scalar_edges)
+            || match &self.uvs {
+                Some(uvs) => model::TexCoord::buffer_too_small(header, uvs),
+                None => self.textured && model::TexCoord::in_header(header),
+            }
+    }
+    
+    fn read_ply(f: &mut impl BufRead, header: &ply::Header) -> io::Result<Self::Stage> {
+        let element = Element::Vertex.require(header)?;
+        let (has_uvs, has_normals) = (model::TexCoord::in_header(header), model::Normal::in_header(header));
+        let (vertices, uvs, normals) = if has_uvs || has_normals {
+            let parse = Parser::<model::MeshVertex>::new();
+            let vertices = parse.read_payload_for_element(f, element, header)?;
+            let uvs = has_uvs.then(|| vertices.iter().map(|v| v.uv).collect());
+            let normals = has_normals.then(|| vertices.iter().map(|v| v.normal).collect());
+            (vertices.into_iter().map(|v| v.position).collect(), uvs, normals)
+        } else {
+            let parse = Parser::<model::PlainVertex>::new();
+            (parse.read_payload_for_element(f, element, header)?, None, None)
+        };
+
+        let parse = Parser::<model::Polygon>::new();
+        let element = Element::Facet.require(header)?;
+        let indices = parse.read_payload_for_element(f, element, header)?;
+        Ok((vertices, indices, uvs, normals))
+    }
+
+    // Polygons are split into triangles for solid rendering, but keep
+    // their outline for the wireframe.  Meshes without normals get
+    // them from their triangles.
+    fn stage(&mut self, (vertices, facets, uvs, normals): Self::Stage) {
+        self.stage_edges = model::Wireframe::outline(&facets);
+        if self.scalar_edges.is_some() {
+            (self.stage_scalar_edges, self.scalar_range) =
+                model::ScalarVertex::edges(&vertices, &facets, self.edge_color);
+        }
+        self.bounds = model::Bounds::of(&vertices);
+        self.stage_vertices = vertices;
+        if self.uvs.is_some() {
+            self.stage_uvs = uvs.unwrap_or_default();
+        }
+        self.stage_indices = facets.iter().flat_map(|facet| facet.triangles()).collect();
+        self.stage_normals = normals.unwrap_or_else(|| model::Normal::of_triangles(&self.stage_vertices, &self.stage_indices));
+        self.num_triangles = self.stage_indices.len() as u32;
+        self.num_edges = self.stage_edges.len() as u32;
+    }
+
+    fn allocated(&self) -> u64 {
+        self.vertices.size()
+            + self.indices.size()
+            + self.edges.size()
+            + self.scalar_edges.as_ref().map_or(0, |buffer| buffer.size())
+            + self.uvs.as_ref().map_or(0, |buffer| buffer.size())
+            + self.normals.size()
+    }
+
+    fn bounds(&self) -> Option<model::Bounds> {
+        self.bounds
+    }
+
+    fn write_buffer(&self, upload: &mut Upload) {
+        upload.write(&self.vertices, bytemuck::cast_slice(&self.stage_vertices));
+        upload.write(&self.indices, bytemuck::cast_slice(&self.stage_indices));
+        upload.write(&self.edges, bytemuck::cast_slice(&self.stage_edges));
+        upload.write(&self.normals, bytemuck::cast_slice(&self.stage_normals));
+        if let Some(scalar_edges) = &self.scalar_edges {
+            upload.write(scalar_edges, bytemuck::cast_slice(&self.stage_scalar_edges));
+        }
+        if let Some(uvs) = &self.uvs {
+            upload.write(uvs, bytemuck::cast_slice(&self.stage_uvs));
+        }
+    }
+
+    fn render<'rpass>(&'rpass self, render_pass: &mut wgpu::RenderPass<'rpass>) {
+        render_pass.set_vertex_buffer(0, self.vertices.slice(..));
+        render_pass.set_vertex_buffer(1, self.normals.slice(..));
+        render_pass.set_index_buffer(self.indices.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.num_triangles * 3, 0, 0..1);
+    }
+}
+
+impl Mesh {
+    // Whether the mesh can be drawn with the texture.
+    pub fn is_textured(&self) -> bool {
+        self.uvs.is_some()
+    }
+
+    // The shaded fill, as opaque or see-through.
+    fn create_shaded_pipeline(
+        device: &wgpu::Device,
+        state: &WindowState,
+        label: &str,
+        blend: wgpu::BlendState,
+        depth_stencil: Option<wgpu::DepthStencilState>,
+    ) -> wgpu::RenderPipeline {
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("mesh::shader"),
             source: wgpu::ShaderSource::Wgsl(
-                (include_str!("shader/plain_geometry.wsgl").to_owned()).into(),
+                (include_str!("shader/shaded_geometry.wsgl").to_owned()).into(),
             ),
         });
 
         device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("mesh::render_pipeline"),
+            label: Some(label),
             layout: Some(&state.mesh_pipeline_layout),
             vertex: wgpu::VertexState {
                 module: &shader,
                 compilation_options: Default::default(),
                 entry_point: "vs_main",
-                buffers: &[model::PlainVertex::desc()],
+                buffers: &[model::PlainVertex::desc(), model::Normal::desc()],
             },
             fragment: Some(wgpu::FragmentState {
                 module: &shader,
                 compilation_options: Default::default(),
                 entry_point: "fs_main",
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: state.surface_capabilities.formats[0],
-                    blend: Some(wgpu::BlendState::REPLACE),
+                    format: state.surface_format,
+                    blend: Some(blend),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
             }),
@@ -99,48 +269,146 @@ impl RenderArtifact for Mesh {
                 topology: wgpu::PrimitiveTopology::TriangleList,
                 ..Default::default()
             },
-            depth_stencil: None,
+            depth_stencil,
             multisample: wgpu::MultisampleState::default(),
             multiview: None,
         })
     }
 
-    fn create_uniform_buffer(device: &wgpu::Device) -> wgpu::Buffer {
-        let uniform = ArtifactUniform::new([0.0, 0.0, 1.0, 1.0]);
-        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("mesh::uniform_buffer"),
-            contents: bytemuck::cast_slice(&[uniform]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    // Like the plain fill, blended by the color's alpha (--alpha).
+    pub fn create_see_through_pipeline(device: &wgpu::Device, state: &WindowState) -> wgpu::RenderPipeline {
+        Mesh::create_shaded_pipeline(device, state, "mesh::see_through_render_pipeline", wgpu::BlendState::ALPHA_BLENDING, pipeline::see_through_depth(state.depth_bias))
+    }
+
+    // Like the plain mesh layout, plus the texture and its sampler.
+    pub fn create_textured_pipeline_layout(
+        device: &wgpu::Device,
+        world_bind_group_layout: &wgpu::BindGroupLayout,
+        artifact_bind_group_layout: &wgpu::BindGroupLayout,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::PipelineLayout {
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("mesh::textured_pipeline_layout"),
+            bind_group_layouts: &[
+                world_bind_group_layout,
+                artifact_bind_group_layout,
+                texture_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        })
+    }
+
+    pub fn create_textured_pipeline(device: &wgpu::Device, state: &WindowState) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("mesh::textured_shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                (include_str!("shader/textured_geometry.wsgl").to_owned()).into(),
+            ),
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("mesh::textured_render_pipeline"),
+            layout: Some(&state.textured_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                compilation_options: Default::default(),
+                entry_point: "vs_main",
+                buffers: &[model::PlainVertex::desc(), model::TexCoord::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                compilation_options: Default::default(),
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: state.surface_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            depth_stencil: pipeline::fill_depth(state.depth_bias),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
         })
     }
 
-    fn needs_resize(&self, header: &ply::Header) -> bool {
-        model::PlainVertex::buffer_too_small(&header, &self.vertices)
-            || model::TriFacet::buffer_too_small(&header, &self.indices)
+    // Draw the triangles with the texture, which the caller binds.  Only
+    // textured meshes offer this render mode.
+    pub fn render_textured<'rpass>(&'rpass self, render_pass: &mut wgpu::RenderPass<'rpass>) {
+        let uvs = match &self.uvs {
+            Some(uvs) => uvs,
+            None => return,
+        };
+        render_pass.set_vertex_buffer(0, self.vertices.slice(..));
+        render_pass.set_vertex_buffer(1, uvs.slice(..));
+        render_pass.set_index_buffer(self.indices.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.num_triangles * 3, 0, 0..1);
+    }
+
+    // The facet outlines over the fill (--edges), in black over light
+    // colors and white over dark, whatever the --edge-color-by.
+    pub fn create_edges_pipeline(device: &wgpu::Device, state: &WindowState) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("mesh::edges_shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                (include_str!("shader/plain_geometry.wsgl").to_owned()).into(),
+            ),
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("mesh::edges_render_pipeline"),
+            layout: Some(&state.mesh_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                compilation_options: Default::default(),
+                entry_point: "vs_main",
+                buffers: &[model::PlainVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                compilation_options: Default::default(),
+                entry_point: "fs_contrast",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: state.surface_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: pipeline::edges_depth(),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
     }
-    
-    fn read_ply(&mut self, f: &mut impl BufRead, header: &ply::Header) {
-        let parse = Parser::<model::PlainVertex>::new();
-        let element = header.elements.get(&Element::Vertex.to_string()).unwrap();
-        self.stage_vertices = parse
-            .read_payload_for_element(f, &element, &header)
-            .unwrap();
 
-        let parse = Parser::<model::TriFacet>::new();
-        let element = header.elements.get(&Element::Facet.to_string()).unwrap();
-        self.stage_indices = parse
-            .read_payload_for_element(f, &element, &header)
-            .unwrap();
+    // Draw the facet outlines over the fill, from the same vertices.
+    pub fn render_outline<'rpass>(&'rpass self, render_pass: &mut wgpu::RenderPass<'rpass>) {
+        render_pass.set_vertex_buffer(0, self.vertices.slice(..));
+        render_pass.set_index_buffer(self.edges.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.num_edges * 2, 0, 0..1);
     }
 
-    fn write_buffer(&self, queue: &wgpu::Queue) {
-        queue.write_buffer(&self.vertices, 0, bytemuck::cast_slice(&self.stage_vertices));
-        queue.write_buffer(&self.indices, 0, bytemuck::cast_slice(&self.stage_indices));
+    // Draw only the vertices, ignoring the facets, as a point cloud
+    // would: a square instance per vertex.
+    pub fn render_points<'rpass>(&'rpass self, render_pass: &mut wgpu::RenderPass<'rpass>) {
+        render_pass.set_vertex_buffer(0, self.vertices.slice(..));
+        render_pass.draw(point_cloud::SQUARE, 0..self.num_vertices);
     }
 
-    fn render<'rpass>(&'rpass self, render_pass: &mut wgpu::RenderPass<'rpass>) {
+    // Draw the facet outlines, two indices per edge.
+    pub fn render_edges<'rpass>(&'rpass self, render_pass: &mut wgpu::RenderPass<'rpass>) {
+        let count = self.stage_scalar_edges.len();
+        if wireframe::render_scalar_edges(&self.scalar_edges, count, render_pass) {
+            return;
+        }
         render_pass.set_vertex_buffer(0, self.vertices.slice(..));
-        render_pass.set_index_buffer(self.indices.slice(..), wgpu::IndexFormat::Uint32);
-        render_pass.draw_indexed(0..self.num_facets as u32, 0, 0..1);
+        render_pass.set_index_buffer(self.edges.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.num_edges * 2, 0, 0..1);
     }
 }
@@ -5,3 +5,74 @@ pub mod mesh;
 pub use point_cloud::PointCloud;
 pub use mesh::Mesh;
 pub use wireframe::Wireframe;
+
+// Frames have a depth buffer, but only filled meshes and the grid write
+// it, and only they and the edges drawn over them (--edges) test against
+// it.  Everything else is drawn in order (--layer-order), over whatever
+// came before, as if there were no depth buffer.
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+// How far filled triangles are pushed back (--depth-bias), both as a
+// constant and scaled by their slope.
+pub const DEFAULT_DEPTH_BIAS: i32 = 2;
+
+pub fn ignore_depth() -> Option<wgpu::DepthStencilState> {
+    Some(wgpu::DepthStencilState {
+        format: DEPTH_FORMAT,
+        depth_write_enabled: false,
+        depth_compare: wgpu::CompareFunction::Always,
+        stencil: wgpu::StencilState::default(),
+        bias: wgpu::DepthBiasState::default(),
+    })
+}
+
+// Filled triangles hide what is behind them.  They are pushed back by
+// `bias`, so that lines at the same depth win: their own edges, drawn
+// over them, and the grid, drawn before them.  Lines cannot have a depth
+// bias of their own; backends apply it to triangles only.
+pub fn fill_depth(bias: i32) -> Option<wgpu::DepthStencilState> {
+    Some(wgpu::DepthStencilState {
+        format: DEPTH_FORMAT,
+        depth_write_enabled: true,
+        depth_compare: wgpu::CompareFunction::Less,
+        stencil: wgpu::StencilState::default(),
+        bias: wgpu::DepthBiasState {
+            constant: bias,
+            slope_scale: bias as f32,
+            clamp: 0.0,
+        },
+    })
+}
+
+// A see-through fill (--alpha) is hidden behind opaque triangles, but
+// does not hide what is drawn after it, so that blends over it.
+pub fn see_through_depth(bias: i32) -> Option<wgpu::DepthStencilState> {
+    Some(wgpu::DepthStencilState {
+        depth_write_enabled: false,
+        ..fill_depth(bias).unwrap()
+    })
+}
+
+// The grid and axes are drawn first, and hide the fills behind them.  A
+// fill lying on the ground plane is pushed back behind the grid, so the
+// grid shows on it instead of z-fighting with it.
+pub fn grid_depth() -> Option<wgpu::DepthStencilState> {
+    Some(wgpu::DepthStencilState {
+        format: DEPTH_FORMAT,
+        depth_write_enabled: true,
+        depth_compare: wgpu::CompareFunction::LessEqual,
+        stencil: wgpu::StencilState::default(),
+        bias: wgpu::DepthBiasState::default(),
+    })
+}
+
+// Edges over a fill are hidden by the triangles in front of them.
+pub fn edges_depth() -> Option<wgpu::DepthStencilState> {
+    Some(wgpu::DepthStencilState {
+        format: DEPTH_FORMAT,
+        depth_write_enabled: false,
+        depth_compare: wgpu::CompareFunction::LessEqual,
+        stencil: wgpu::StencilState::default(),
+        bias: wgpu::DepthBiasState::default(),
+    })
+}
@@ -1,44 +1,98 @@
-use crate::{model, ArtifactUniform, Element, RenderArtifact, WindowState, IntoElement};
+use crate::{model, pipeline, pool::{self, PooledBuffer}, upload::Upload, ArtifactUniform, Element, RenderArtifact, WindowState, IntoElement};
 use wgpu::util::DeviceExt;
-use std::io::BufRead;
+use std::io::{self, BufRead};
 use ply_rs::{parser::Parser, ply};
 
+// Points are squares this many pixels across (--point-size), resized
+// with the + and - keys.
+pub const DEFAULT_POINT_SIZE: f32 = 2.0;
+pub const MIN_POINT_SIZE: f32 = 1.0;
+pub const MAX_POINT_SIZE: f32 = 32.0;
+
+// PointUniform is the GPU side of the point size, shared by every pane.
+// The shaders need the pane size too, to turn pixels into clip space.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PointUniform {
+    // The point size, then the pane width and height, in pixels.
+    size: [f32; 4],
+}
+
+impl PointUniform {
+    pub fn new(size: f32, width: u32, height: u32) -> PointUniform {
+        PointUniform {
+            size: [size, width as f32, height as f32, 0.0],
+        }
+    }
+}
+
+// WGSL cannot size points, so each point is a square of two triangles,
+// expanded by the vertex shader from the six vertices of an instance.
+// There is one instance per point, so the point buffers step per
+// instance.
+pub const SQUARE: std::ops::Range<u32> = 0..6;
+
+fn per_point(layout: wgpu::VertexBufferLayout) -> wgpu::VertexBufferLayout {
+    wgpu::VertexBufferLayout {
+        step_mode: wgpu::VertexStepMode::Instance,
+        ..layout
+    }
+}
+
 pub struct PointCloud {
-    pub vertices: wgpu::Buffer,
+    pub vertices: PooledBuffer,
     stage_vertices: Vec<model::PlainVertex>,
+    bounds: Option<model::Bounds>,
     pub num_vertices: u32,
+    // Per-point opacity, if the PLY has it.
+    opacity: Option<PooledBuffer>,
+    stage_opacity: Vec<model::Opacity>,
+    // Per-point color, if the PLY has it.
+    colors: Option<PooledBuffer>,
+    stage_colors: Vec<model::VertexColor>,
 }
 
 impl PointCloud {
     pub fn new(device: &wgpu::Device, header: &ply::Header) -> Option<PointCloud> {
-        if !header.elements.contains_key(&Element::Vertex.to_string()) {
-            return None;
-        }
-        
+        let count = Element::Vertex.find(header)?.count;
+
         let element_size = std::mem::size_of::<model::PlainVertex>();
-        let count = header.elements.get(&Element::Vertex.to_string()).unwrap().count;
-        let vertices = device.create_buffer(&wgpu::BufferDescriptor {
-            mapped_at_creation: false,
-            size: (2 * element_size * count) as u64,
-            label: Some("point_cloud::vertices"),
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        let vertices = pool::buffer(device, "point_cloud::vertices", pool::VERTEX, element_size * count);
+
+        let opacity = model::Opacity::in_header(header).then(|| {
+            let element_size = std::mem::size_of::<model::Opacity>();
+            pool::buffer(device, "point_cloud::opacity", pool::VERTEX, element_size * count)
+        });
+
+        let colors = model::VertexColor::in_header(header).then(|| {
+            let element_size = std::mem::size_of::<model::VertexColor>();
+            pool::buffer(device, "point_cloud::colors", pool::VERTEX, element_size * count)
         });
 
         Some(PointCloud {
             vertices,
             stage_vertices: vec![],
+            bounds: None,
             num_vertices: count as u32,
+            opacity,
+            stage_opacity: vec![],
+            colors,
+            stage_colors: vec![],
         })
     }
 }
 
 impl RenderArtifact for PointCloud {
+    type Stage = (
+        Vec<model::PlainVertex>,
+        Option<Vec<model::Opacity>>,
+        Option<Vec<model::VertexColor>>,
+    );
+
     fn update_count(&mut self, header: &ply::Header) {
-        self.num_vertices = header
-            .elements
-            .get(&Element::Vertex.to_string())
-            .unwrap()
-            .count as u32;
+        if let Some(element) = Element::Vertex.find(header) {
+            self.num_vertices = element.count as u32;
+        }
     }
 
     fn create_pipeline_layout(
@@ -48,7 +102,7 @@ impl RenderArtifact for PointCloud {
     ) -> wgpu::PipelineLayout {
         device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("point_cloud::pipeline_layout"),
-            bind_group_layouts: &[&world_bind_group_layout, &artifact_bind_group_layout],
+            bind_group_layouts: &[world_bind_group_layout, artifact_bind_group_layout],
             push_constant_ranges: &[],
         })
     }
@@ -57,7 +111,7 @@ impl RenderArtifact for PointCloud {
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("point_cloud::shader"),
             source: wgpu::ShaderSource::Wgsl(
-                (include_str!("shader/plain_geometry.wsgl").to_owned()).into(),
+                (include_str!("shader/point_geometry.wsgl").to_owned()).into(),
             ),
         });
 
@@ -68,30 +122,36 @@ impl RenderArtifact for PointCloud {
                 module: &shader,
                 compilation_options: Default::default(),
                 entry_point: "vs_main",
-                buffers: &[model::PlainVertex::desc()],
+                buffers: &[per_point(model::PlainVertex::desc())],
             },
             fragment: Some(wgpu::FragmentState {
                 module: &shader,
                 compilation_options: Default::default(),
                 entry_point: "fs_main",
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: state.surface_capabilities.formats[0],
-                    blend: Some(wgpu::BlendState::REPLACE),
+                    format: state.surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
             }),
             primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::PointList,
+                topology: wgpu::PrimitiveTopology::TriangleList,
                 ..Default::default()
             },
-            depth_stencil: None,
+            depth_stencil: pipeline::ignore_depth(),
             multisample: wgpu::MultisampleState::default(),
             multiview: None,
         })
     }
 
-    fn create_uniform_buffer(device: &wgpu::Device) -> wgpu::Buffer {
-        let uniform = ArtifactUniform::new([0.0, 1.0, 0.0, 1.0]);
+    const COLOR: [f32; 4] = [0.0, 1.0, 0.0, 1.0];
+
+    fn create_uniform_buffer(
+        device: &wgpu::Device,
+        color: Option<[f32; 4]>,
+        transform: [[f32; 4]; 4],
+    ) -> wgpu::Buffer {
+        let uniform = ArtifactUniform::new(color.unwrap_or(Self::COLOR), transform);
         device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("point_cloud::uniform_buffer"),
             contents: bytemuck::cast_slice(&[uniform]),
@@ -99,24 +159,261 @@ impl RenderArtifact for PointCloud {
         })
     }
 
-    fn needs_resize(&self, header: &ply::Header) -> bool {
-        model::PlainVertex::buffer_too_small(&header, &self.vertices)
+    fn needs_resize(&self, header: &ply::Header, _: &Self::Stage) -> bool {
+        model::PlainVertex::buffer_too_small(header, &self.vertices)
+            || match &self.opacity {
+                Some(opacity) => model::Opacity::buffer_too_small(header, opacity),
+                None => model::Opacity::in_header(header),
+            }
+            || match &self.colors {
+                Some(colors) => model::VertexColor::buffer_too_small(header, colors),
+                None => model::VertexColor::in_header(header),
+            }
     }
 
-    fn read_ply(&mut self, f: &mut impl BufRead, header: &ply::Header) {
-        let parse = Parser::<model::PlainVertex>::new();
-        let element = header.elements.get(&Element::Vertex.to_string()).unwrap();
-        self.stage_vertices = parse
-            .read_payload_for_element(f, &element, &header)
-            .unwrap();
+    fn read_ply(f: &mut impl BufRead, header: &ply::Header) -> io::Result<Self::Stage> {
+        let element = Element::Vertex.require(header)?;
+        if model::VertexColor::in_header(header) {
+            let parse = Parser::<model::ColoredVertex>::new();
+            let vertices = parse.read_payload_for_element(f, element, header)?;
+            let mut positions = Vec::with_capacity(vertices.len());
+            let mut opacity = Vec::with_capacity(vertices.len());
+            let mut colors = Vec::with_capacity(vertices.len());
+            for vertex in vertices {
+                positions.push(vertex.position);
+                opacity.push(vertex.opacity);
+                colors.push(vertex.color);
+            }
+            let opacity = model::Opacity::in_header(header).then_some(opacity);
+            Ok((positions, opacity, Some(colors)))
+        } else if model::Opacity::in_header(header) {
+            let parse = Parser::<model::TranslucentVertex>::new();
+            let vertices = parse.read_payload_for_element(f, element, header)?;
+            let (vertices, opacity) = vertices.into_iter().map(|v| (v.position, v.opacity)).unzip();
+            Ok((vertices, Some(opacity), None))
+        } else {
+            let parse = Parser::<model::PlainVertex>::new();
+            Ok((parse.read_payload_for_element(f, element, header)?, None, None))
+        }
+    }
+
+    fn stage(&mut self, (vertices, opacity, colors): Self::Stage) {
+        self.bounds = model::Bounds::of(&vertices);
+        self.stage_vertices = vertices;
+        if self.opacity.is_some() {
+            self.stage_opacity = opacity.unwrap_or_default();
+        }
+        if self.colors.is_some() {
+            self.stage_colors = colors.unwrap_or_default();
+        }
     }
 
-    fn write_buffer(&self, queue: &wgpu::Queue) {
-        queue.write_buffer(&self.vertices, 0, bytemuck::cast_slice(&self.stage_vertices));
+    fn allocated(&self) -> u64 {
+        self.vertices.size()
+            + self.opacity.as_ref().map_or(0, |buffer| buffer.size())
+            + self.colors.as_ref().map_or(0, |buffer| buffer.size())
+    }
+
+    fn bounds(&self) -> Option<model::Bounds> {
+        self.bounds
+    }
+
+    fn write_buffer(&self, upload: &mut Upload) {
+        upload.write(&self.vertices, bytemuck::cast_slice(&self.stage_vertices));
+        if let Some(opacity) = &self.opacity {
+            upload.write(opacity, bytemuck::cast_slice(&self.stage_opacity));
+        }
+        if let Some(colors) = &self.colors {
+            upload.write(colors, bytemuck::cast_slice(&self.stage_colors));
+        }
     }
 
     fn render<'rpass>(&'rpass self, render_pass: &mut wgpu::RenderPass<'rpass>) {
         render_pass.set_vertex_buffer(0, self.vertices.slice(..));
-        render_pass.draw(0..self.num_vertices, 0..1);
+        render_pass.draw(SQUARE, 0..self.num_vertices);
+    }
+}
+
+impl PointCloud {
+    // Whether the points can be faded by their own opacity.
+    pub fn is_translucent(&self) -> bool {
+        self.opacity.is_some()
+    }
+
+    // Whether the points carry their own colors.
+    pub fn is_colored(&self) -> bool {
+        self.colors.is_some()
+    }
+
+    // Like the plain pipeline, but blending each point by its opacity.
+    pub fn create_translucent_pipeline(device: &wgpu::Device, state: &WindowState) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("point_cloud::translucent_shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                (include_str!("shader/translucent_geometry.wsgl").to_owned()).into(),
+            ),
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("point_cloud::translucent_render_pipeline"),
+            layout: Some(&state.point_cloud_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                compilation_options: Default::default(),
+                entry_point: "vs_main",
+                buffers: &[per_point(model::PlainVertex::desc()), per_point(model::Opacity::desc())],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                compilation_options: Default::default(),
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: state.surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            depth_stencil: pipeline::ignore_depth(),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    // Like the plain pipeline, but each point in its own color, blended by
+    // the color's alpha.
+    pub fn create_colored_pipeline(device: &wgpu::Device, state: &WindowState) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("point_cloud::colored_shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                (include_str!("shader/colored_geometry.wsgl").to_owned()).into(),
+            ),
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("point_cloud::colored_render_pipeline"),
+            layout: Some(&state.point_cloud_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                compilation_options: Default::default(),
+                entry_point: "vs_main",
+                buffers: &[per_point(model::PlainVertex::desc()), per_point(model::VertexColor::desc())],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                compilation_options: Default::default(),
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: state.surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            depth_stencil: pipeline::ignore_depth(),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    // Like the plain pipeline, but each point colored by its height, as
+    // given in the artifact uniform.
+    pub fn create_height_pipeline(device: &wgpu::Device, state: &WindowState) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("point_cloud::height_shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                (include_str!("shader/height_geometry.wsgl").to_owned()).into(),
+            ),
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("point_cloud::height_render_pipeline"),
+            layout: Some(&state.point_cloud_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                compilation_options: Default::default(),
+                entry_point: "vs_main",
+                buffers: &[per_point(model::PlainVertex::desc())],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                compilation_options: Default::default(),
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: state.surface_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            depth_stencil: pipeline::ignore_depth(),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    // Only translucent point clouds offer this render mode.
+    pub fn render_translucent<'rpass>(&'rpass self, render_pass: &mut wgpu::RenderPass<'rpass>) {
+        let opacity = match &self.opacity {
+            Some(opacity) => opacity,
+            None => return,
+        };
+        render_pass.set_vertex_buffer(0, self.vertices.slice(..));
+        render_pass.set_vertex_buffer(1, opacity.slice(..));
+        render_pass.draw(SQUARE, 0..self.num_vertices);
+    }
+
+    // Only colored point clouds offer this render mode.
+    pub fn render_colored<'rpass>(&'rpass self, render_pass: &mut wgpu::RenderPass<'rpass>) {
+        let colors = match &self.colors {
+            Some(colors) => colors,
+            None => return,
+        };
+        render_pass.set_vertex_buffer(0, self.vertices.slice(..));
+        render_pass.set_vertex_buffer(1, colors.slice(..));
+        render_pass.draw(SQUARE, 0..self.num_vertices);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wgpu::naga;
+
+    // The shaders that draw points as squares, as meshes and wireframes
+    // do in RenderMode::Points too.
+    const SHADERS: [(&str, &str); 4] = [
+        ("point_geometry", include_str!("shader/point_geometry.wsgl")),
+        ("translucent_geometry", include_str!("shader/translucent_geometry.wsgl")),
+        ("colored_geometry", include_str!("shader/colored_geometry.wsgl")),
+        ("height_geometry", include_str!("shader/height_geometry.wsgl")),
+    ];
+
+    // Each shader is valid, and has a corner for every vertex of SQUARE.
+    #[test]
+    fn square_shaders() {
+        for (name, source) in SHADERS {
+            let module = naga::front::wgsl::parse_str(source).unwrap_or_else(|err| panic!("{}: {}", name, err));
+            naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::empty())
+                .validate(&module)
+                .unwrap_or_else(|err| panic!("{}: {:?}", name, err));
+
+            let corner = module.functions.iter().find(|(_, f)| f.name.as_deref() == Some("corner"));
+            let (_, corner) = corner.unwrap_or_else(|| panic!("{}: no corner()", name));
+            let corners = corner.local_variables.iter().find_map(|(_, local)| match module.types[local.ty].inner {
+                naga::TypeInner::Array { size: naga::ArraySize::Constant(size), .. } => Some(size.get()),
+                _ => None,
+            });
+            assert_eq!(corners, Some(SQUARE.len() as u32), "{}", name);
+        }
     }
 }
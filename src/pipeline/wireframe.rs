@@ -1,53 +1,147 @@
-use crate::{model, ArtifactUniform, Element, IntoElement, RenderArtifact, WindowState};
+use crate::{
+    model, pipeline,
+    pool::{self, PooledBuffer},
+    upload::Upload,
+    ArtifactUniform, EdgeColor, Element, IntoElement, RenderArtifact, WindowState,
+};
 use ply_rs::{parser::Parser, ply};
-use std::io::BufRead;
+use std::io::{self, BufRead};
 use wgpu::util::DeviceExt;
 
 pub struct Wireframe {
-    pub vertices: wgpu::Buffer,
-    pub indices: wgpu::Buffer,
+    pub vertices: PooledBuffer,
+    pub indices: PooledBuffer,
     stage_vertices: Vec<model::PlainVertex>,
+    bounds: Option<model::Bounds>,
     stage_indices: Vec<model::Wireframe>,
-    pub num_lines: u32,
+    pub num_vertices: u32,
+    pub num_edges: u32,
+    // Per-edge colored lines, only when edges are not a uniform color.
+    edge_color: EdgeColor,
+    scalar_edges: Option<PooledBuffer>,
+    stage_scalar_edges: Vec<model::ScalarVertex>,
+    pub scalar_range: Option<(f32, f32)>,
 }
 
 impl Wireframe {
-    pub fn new(device: &wgpu::Device, header: &ply::Header) -> Option<Wireframe> {
-        if !header.elements.contains_key(&Element::Vertex.to_string())
-            || !header.elements.contains_key(&Element::Facet.to_string())
-        {
-            return None;
-        }
+    // Facets may have any number of sides, so there are only as many
+    // edges as the facets read say.
+    pub fn new(
+        device: &wgpu::Device,
+        header: &ply::Header,
+        facets: &[model::Polygon],
+        edge_color: EdgeColor,
+    ) -> Option<Wireframe> {
+        let vertex = Element::Vertex.find(header)?;
+        Element::Facet.find(header)?;
+        let edges = model::Wireframe::count(facets);
 
         let element_size = std::mem::size_of::<model::PlainVertex>();
-        let count = header.elements.get(&Element::Vertex.to_string()).unwrap().count;
-        let vertices = device.create_buffer(&wgpu::BufferDescriptor {
-            mapped_at_creation: false,
-            size: (2 * element_size * count) as u64,
-            label: Some("wireframe::vertices"),
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-        });
+        let count = vertex.count;
+        let num_vertices = count as u32;
+        let vertices = pool::buffer(device, "wireframe::vertices", pool::VERTEX, element_size * count);
 
-        let element_size = std::mem::size_of::<model::TriFacet>();
-        let count = header.elements.get(&Element::Facet.to_string()).unwrap().count;
-        let indices = device.create_buffer(&wgpu::BufferDescriptor {
-            mapped_at_creation: false,
-            size: (4 * element_size * count) as u64,
-            label: Some("wireframe::indices"),
-            usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
-        });
+        let element_size = std::mem::size_of::<model::Wireframe>();
+        let indices = pool::buffer(device, "wireframe::indices", pool::INDEX, element_size * edges);
 
         Some(Wireframe {
             vertices,
             indices,
             stage_vertices: vec![],
+            bounds: None,
             stage_indices: vec![],
-            num_lines: count as u32 / 2,
+            num_vertices,
+            num_edges: 0,
+            edge_color,
+            scalar_edges: create_scalar_edges(device, edges, edge_color),
+            stage_scalar_edges: vec![],
+            scalar_range: None,
+        })
+    }
+
+    // The same as create_pipeline(), but edges are colored by their
+    // per-edge scalar instead of the artifact color.
+    pub fn create_scalar_pipeline(
+        device: &wgpu::Device,
+        state: &WindowState,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("wireframe::scalar_shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                (include_str!("shader/scalar_geometry.wsgl").to_owned()).into(),
+            ),
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("wireframe::scalar_render_pipeline"),
+            layout: Some(&state.mesh_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                compilation_options: Default::default(),
+                entry_point: "vs_main",
+                buffers: &[model::ScalarVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                compilation_options: Default::default(),
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: state.surface_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: pipeline::ignore_depth(),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
         })
     }
 }
 
+// Draw the colored lines built at staging.  Returns false if edges are a
+// uniform color, and the caller should draw its indexed lines instead.
+pub fn render_scalar_edges<'rpass>(
+    buffer: &'rpass Option<PooledBuffer>,
+    count: usize,
+    render_pass: &mut wgpu::RenderPass<'rpass>,
+) -> bool {
+    match buffer {
+        Some(buffer) => {
+            render_pass.set_vertex_buffer(0, buffer.slice(..));
+            render_pass.draw(0..count as u32, 0..1);
+            true
+        }
+        None => false,
+    }
+}
+
+// Lines with per-edge scalars are only needed when edges are colored by
+// something other than the artifact color.
+pub fn create_scalar_edges(
+    device: &wgpu::Device,
+    edges: usize,
+    edge_color: EdgeColor,
+) -> Option<PooledBuffer> {
+    if edge_color == EdgeColor::Uniform {
+        return None;
+    }
+    Some(pool::buffer(device, "wireframe::scalar_edges", pool::VERTEX, model::ScalarVertex::buffer_size(edges)))
+}
+
+pub fn scalar_edges_too_small(edges: usize, buffer: &Option<PooledBuffer>) -> bool {
+    match buffer {
+        Some(buffer) => (buffer.size() as usize) < model::ScalarVertex::buffer_size(edges),
+        None => false,
+    }
+}
+
 impl RenderArtifact for Wireframe {
+    type Stage = (Vec<model::PlainVertex>, Vec<model::Polygon>);
+
     fn create_pipeline_layout(
         device: &wgpu::Device,
         world_bind_group_layout: &wgpu::BindGroupLayout,
@@ -55,7 +149,7 @@ impl RenderArtifact for Wireframe {
     ) -> wgpu::PipelineLayout {
         device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("wireframe::pipeline_layout"),
-            bind_group_layouts: &[&world_bind_group_layout, &artifact_bind_group_layout],
+            bind_group_layouts: &[world_bind_group_layout, artifact_bind_group_layout],
             push_constant_ranges: &[],
         })
     }
@@ -82,8 +176,8 @@ impl RenderArtifact for Wireframe {
                 compilation_options: Default::default(),
                 entry_point: "fs_main",
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: state.surface_capabilities.formats[0],
-                    blend: Some(wgpu::BlendState::REPLACE),
+                    format: state.surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
             }),
@@ -91,14 +185,20 @@ impl RenderArtifact for Wireframe {
                 topology: wgpu::PrimitiveTopology::LineList,
                 ..Default::default()
             },
-            depth_stencil: None,
+            depth_stencil: pipeline::ignore_depth(),
             multisample: wgpu::MultisampleState::default(),
             multiview: None,
         })
     }
 
-    fn create_uniform_buffer(device: &wgpu::Device) -> wgpu::Buffer {
-        let uniform = ArtifactUniform::new([0.1, 0.1, 0.1, 1.0]);
+    const COLOR: [f32; 4] = [0.1, 0.1, 0.1, 1.0];
+
+    fn create_uniform_buffer(
+        device: &wgpu::Device,
+        color: Option<[f32; 4]>,
+        transform: [[f32; 4]; 4],
+    ) -> wgpu::Buffer {
+        let uniform = ArtifactUniform::new(color.unwrap_or(Self::COLOR), transform);
         device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("wireframe::uniform_buffer"),
             contents: bytemuck::cast_slice(&[uniform]),
@@ -107,41 +207,74 @@ impl RenderArtifact for Wireframe {
     }
 
     fn update_count(&mut self, header: &ply::Header) {
-        self.num_lines = header
-            .elements
-            .get(&Element::Facet.to_string())
-            .unwrap()
-            .count as u32
-            * 3; // three lines per facet
+        if let Some(element) = Element::Vertex.find(header) {
+            self.num_vertices = element.count as u32;
+        }
     }
 
-    fn needs_resize(&self, header: &ply::Header) -> bool {
-        model::PlainVertex::buffer_too_small(&header, &self.vertices)
-            || model::Wireframe::buffer_too_small(&header, &self.indices)
+    fn needs_resize(&self, header: &ply::Header, (_, facets): &Self::Stage) -> bool {
+        let edges = model::Wireframe::count(facets);
+        model::PlainVertex::buffer_too_small(header, &self.vertices)
+            || model::Wireframe::too_small(edges, &self.indices)
+            || scalar_edges_too_small(edges, &self.scalar_edges)
     }
 
-    fn read_ply(&mut self, f: &mut impl BufRead, header: &ply::Header) {
+    fn read_ply(f: &mut impl BufRead, header: &ply::Header) -> io::Result<Self::Stage> {
         let parse = Parser::<model::PlainVertex>::new();
-        let element = header.elements.get(&Element::Vertex.to_string()).unwrap();
-        self.stage_vertices = parse
-            .read_payload_for_element(f, &element, &header)
-            .unwrap();
+        let element = Element::Vertex.require(header)?;
+        let vertices = parse.read_payload_for_element(f, element, header)?;
+
+        let parse = Parser::<model::Polygon>::new();
+        let element = Element::Facet.require(header)?;
+        let indices = parse.read_payload_for_element(f, element, header)?;
+        Ok((vertices, indices))
+    }
+
+    fn stage(&mut self, (vertices, facets): Self::Stage) {
+        if self.scalar_edges.is_some() {
+            (self.stage_scalar_edges, self.scalar_range) =
+                model::ScalarVertex::edges(&vertices, &facets, self.edge_color);
+        }
+        self.bounds = model::Bounds::of(&vertices);
+        self.stage_vertices = vertices;
+        self.stage_indices = model::Wireframe::outline(&facets);
+        self.num_edges = self.stage_indices.len() as u32;
+    }
+
+    fn allocated(&self) -> u64 {
+        self.vertices.size()
+            + self.indices.size()
+            + self.scalar_edges.as_ref().map_or(0, |buffer| buffer.size())
+    }
 
-        let parse = Parser::<model::Wireframe>::new();
-        let element = header.elements.get(&Element::Facet.to_string()).unwrap();
-        self.stage_indices = parse
-            .read_payload_for_element(f, &element, &header)
-            .unwrap();
+    fn bounds(&self) -> Option<model::Bounds> {
+        self.bounds
     }
 
-    fn write_buffer(&self, queue: &wgpu::Queue) {
-        queue.write_buffer(&self.vertices, 0, bytemuck::cast_slice(&self.stage_vertices));
-        queue.write_buffer(&self.indices, 0, bytemuck::cast_slice(&self.stage_indices));
+    fn write_buffer(&self, upload: &mut Upload) {
+        upload.write(&self.vertices, bytemuck::cast_slice(&self.stage_vertices));
+        upload.write(&self.indices, bytemuck::cast_slice(&self.stage_indices));
+        if let Some(scalar_edges) = &self.scalar_edges {
+            upload.write(scalar_edges, bytemuck::cast_slice(&self.stage_scalar_edges));
+        }
     }
 
     fn render<'rpass>(&'rpass self, render_pass: &mut wgpu::RenderPass<'rpass>) {
+        let count = self.stage_scalar_edges.len();
+        if render_scalar_edges(&self.scalar_edges, count, render_pass) {
+            return;
+        }
         render_pass.set_vertex_buffer(0, self.vertices.slice(..));
         render_pass.set_index_buffer(self.indices.slice(..), wgpu::IndexFormat::Uint32);
-        render_pass.draw_indexed(0..self.num_lines, 0, 0..1);
+        render_pass.draw_indexed(0..self.num_edges * 2, 0, 0..1);
+    }
+}
+
+impl Wireframe {
+    // Draw only the vertices, ignoring the line indices, as a point
+    // cloud would: a square instance per vertex.
+    pub fn render_points<'rpass>(&'rpass self, render_pass: &mut wgpu::RenderPass<'rpass>) {
+        render_pass.set_vertex_buffer(0, self.vertices.slice(..));
+        render_pass.draw(pipeline::point_cloud::SQUARE, 0..self.num_vertices);
     }
 }
@@ -0,0 +1,82 @@
+use std::{ops::Deref, sync::Mutex};
+
+// Recycling of artifact GPU buffers.  Artifacts take their buffers from
+// here, and give them back when they are dropped, replaced or evicted, for
+// the next artifact needing one of the same usage and size.  Accumulating
+// or evicting many frames of about the same size then reuses a few
+// buffers, instead of freeing and allocating on every frame.
+//
+// Sizes are rounded up to classes 1.5 times apart, so that a freed buffer
+// fits any artifact of about the same size, and every buffer has room for
+// its contents to grow by half before it must be replaced.  A stream of
+// frames that keeps growing reallocates every so often, not every frame.
+const SMALLEST: u64 = 1024;
+
+// Freed buffers past this many bytes in total are dropped, oldest first.
+// They are not counted against --vram-limit.
+const LIMIT: u64 = 64 * 1024 * 1024;
+
+// The usages of vertex and index buffers, written from the CPU.
+pub const VERTEX: wgpu::BufferUsages = wgpu::BufferUsages::VERTEX.union(wgpu::BufferUsages::COPY_DST);
+pub const INDEX: wgpu::BufferUsages = wgpu::BufferUsages::INDEX.union(wgpu::BufferUsages::COPY_DST);
+
+// Freed buffers, oldest first.
+static FREE: Mutex<Vec<wgpu::Buffer>> = Mutex::new(Vec::new());
+
+// A buffer that goes back to the pool when dropped.
+#[derive(Debug)]
+pub struct PooledBuffer(Option<wgpu::Buffer>);
+
+impl Deref for PooledBuffer {
+    type Target = wgpu::Buffer;
+
+    fn deref(&self) -> &wgpu::Buffer {
+        self.0.as_ref().unwrap()
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        let buffer = self.0.take().unwrap();
+        let mut free = FREE.lock().unwrap();
+        free.push(buffer);
+        let mut bytes: u64 = free.iter().map(|buffer| buffer.size()).sum();
+        while bytes > LIMIT {
+            bytes -= free.remove(0).size();
+        }
+    }
+}
+
+// The size class for `bytes` and half as much again.
+fn size_class(bytes: u64) -> u64 {
+    let wanted = bytes + bytes / 2;
+    let mut size = SMALLEST;
+    while size < wanted {
+        // Whole multiples of 256 bytes, which any copy is aligned to.
+        size = (size * 3 / 2).next_multiple_of(256);
+    }
+    size
+}
+
+// A buffer to hold `bytes` and grow by half, freed by an earlier artifact
+// if one fits, or else new.  What it held before is left in it.
+pub fn buffer(device: &wgpu::Device, label: &str, usage: wgpu::BufferUsages, bytes: usize) -> PooledBuffer {
+    let size = size_class(bytes as u64);
+    let mut free = FREE.lock().unwrap();
+    if let Some(index) = free.iter().position(|buffer| buffer.usage() == usage && buffer.size() == size) {
+        log::trace!("Recycled a {} byte buffer", size);
+        return PooledBuffer(Some(free.remove(index)));
+    }
+    drop(free);
+    PooledBuffer(Some(device.create_buffer(&wgpu::BufferDescriptor {
+        mapped_at_creation: false,
+        size,
+        label: Some(label),
+        usage,
+    })))
+}
+
+// Drop every freed buffer, before the GPU goes away.
+pub fn clear() {
+    FREE.lock().unwrap().clear();
+}
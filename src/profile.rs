@@ -0,0 +1,159 @@
+use std::{
+    collections::BTreeMap,
+    mem,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+// GPU time spent drawing each artifact, measured with timestamp queries
+// written around every draw call.  Results come back a frame or two
+// late, through a mapped readback buffer, so the GPU is never stalled
+// waiting for them.
+//
+// Timestamps inside a render pass need two optional adapter features;
+// without them there is simply no profiler.
+
+// Draws timed per frame; any beyond this go untimed.
+const MAX_SPANS: u32 = 128;
+const BUFFER_SIZE: u64 = 2 * MAX_SPANS as u64 * mem::size_of::<u64>() as u64;
+
+pub struct Profiler {
+    query_set: wgpu::QuerySet,
+    resolve: wgpu::Buffer,
+    readback: wgpu::Buffer,
+    // Nanoseconds per timestamp tick.
+    period: f32,
+    // What each pair of queries in the current frame measures.
+    spans: Vec<String>,
+    // Spans copied to the readback buffer this frame, to be mapped once
+    // the frame is submitted.
+    copied: Option<Vec<String>>,
+    // Spans being mapped, and whether the mapping has completed.
+    mapping: Option<(Vec<String>, Arc<AtomicBool>)>,
+    // Milliseconds per artifact name, from the latest complete frame.
+    pub readout: Vec<(String, f32)>,
+    logged: Instant,
+}
+
+impl Profiler {
+    pub const FEATURES: wgpu::Features = wgpu::Features::TIMESTAMP_QUERY
+        .union(wgpu::Features::TIMESTAMP_QUERY_INSIDE_PASSES);
+
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Profiler {
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("profile::query_set"),
+            ty: wgpu::QueryType::Timestamp,
+            count: 2 * MAX_SPANS,
+        });
+        let resolve = device.create_buffer(&wgpu::BufferDescriptor {
+            mapped_at_creation: false,
+            size: BUFFER_SIZE,
+            label: Some("profile::resolve"),
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            mapped_at_creation: false,
+            size: BUFFER_SIZE,
+            label: Some("profile::readback"),
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Profiler {
+            query_set,
+            resolve,
+            readback,
+            period: queue.get_timestamp_period(),
+            spans: vec![],
+            copied: None,
+            mapping: None,
+            readout: vec![],
+            logged: Instant::now(),
+        }
+    }
+
+    // Start a frame, first collecting the results of an earlier one if
+    // they have arrived.
+    pub fn begin(&mut self, device: &wgpu::Device) {
+        self.spans.clear();
+        device.poll(wgpu::Maintain::Poll);
+
+        let done = match &self.mapping {
+            Some((_, done)) => done.load(Ordering::Acquire),
+            None => false,
+        };
+        if !done {
+            return;
+        }
+        let (spans, _) = self.mapping.take().unwrap();
+
+        let mut totals = BTreeMap::<String, f32>::new();
+        {
+            let data = self.readback.slice(..).get_mapped_range();
+            let ticks: &[u64] = bytemuck::cast_slice(&data);
+            for (label, pair) in spans.into_iter().zip(ticks.chunks_exact(2)) {
+                let nanoseconds = pair[1].wrapping_sub(pair[0]) as f32 * self.period;
+                *totals.entry(label).or_default() += nanoseconds / 1_000_000.0;
+            }
+        }
+        self.readback.unmap();
+        self.readout = totals.into_iter().collect();
+
+        if self.logged.elapsed() >= Duration::from_secs(1) {
+            self.logged = Instant::now();
+            for (label, milliseconds) in &self.readout {
+                log::info!("GPU {}: {:.3}ms", label, milliseconds);
+            }
+        }
+    }
+
+    // Time one draw under `label`.  Draws with the same label in a frame
+    // add up.
+    pub fn time<'rpass>(
+        &mut self,
+        render_pass: &mut wgpu::RenderPass<'rpass>,
+        label: &str,
+        draw: impl FnOnce(&mut wgpu::RenderPass<'rpass>),
+    ) {
+        let index = self.spans.len() as u32;
+        if index >= MAX_SPANS {
+            return draw(render_pass);
+        }
+        render_pass.write_timestamp(&self.query_set, 2 * index);
+        draw(render_pass);
+        render_pass.write_timestamp(&self.query_set, 2 * index + 1);
+        self.spans.push(label.to_string());
+    }
+
+    // Copy this frame's timestamps out, unless the previous copy is still
+    // being read.  Call after the render pass ends.
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if self.spans.is_empty() || self.mapping.is_some() {
+            return;
+        }
+        let count = 2 * self.spans.len() as u32;
+        encoder.resolve_query_set(&self.query_set, 0..count, &self.resolve, 0);
+        encoder.copy_buffer_to_buffer(&self.resolve, 0, &self.readback, 0, BUFFER_SIZE);
+        self.copied = Some(mem::take(&mut self.spans));
+    }
+
+    // Start reading back the copied timestamps.  Call after submitting
+    // the frame.
+    pub fn submitted(&mut self) {
+        let spans = match self.copied.take() {
+            Some(spans) => spans,
+            None => return,
+        };
+        let done = Arc::new(AtomicBool::new(false));
+        self.readback.slice(..).map_async(wgpu::MapMode::Read, {
+            let done = done.clone();
+            move |result| match result {
+                Ok(()) => done.store(true, Ordering::Release),
+                Err(err) => log::warn!("Cannot read GPU timestamps: {}", err),
+            }
+        });
+        self.mapping = Some((spans, done));
+    }
+}
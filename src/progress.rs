@@ -0,0 +1,106 @@
+use std::{
+    io::{self, BufRead, Read},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+// Feedback while large files are parsed, so a long load does not look
+// like a hang.  Readers wrapped in Progress count the bytes consumed;
+// the window polls fraction() to draw a progress bar, and every tenth of
+// the way is logged.
+//
+// Small files parse faster than anyone could read a progress bar, so
+// they are not tracked at all.
+const MIN_TRACKED: u64 = 16 * 1024 * 1024;
+
+struct Counter {
+    read: AtomicU64,
+    total: u64,
+}
+
+static LOADING: Mutex<Vec<Arc<Counter>>> = Mutex::new(Vec::new());
+
+// How far along the files being parsed are, together, from 0 to 1.  None
+// when nothing large is loading.
+pub fn fraction() -> Option<f32> {
+    let loading = LOADING.lock().unwrap();
+    if loading.is_empty() {
+        return None;
+    }
+    let read: u64 = loading.iter().map(|c| c.read.load(Ordering::Relaxed)).sum();
+    let total: u64 = loading.iter().map(|c| c.total).sum();
+    Some((read as f64 / total as f64).min(1.0) as f32)
+}
+
+pub struct Progress<R> {
+    inner: R,
+    path: PathBuf,
+    counter: Option<Arc<Counter>>,
+    // The last tenth that was logged.
+    logged: u64,
+}
+
+impl<R: BufRead> Progress<R> {
+    pub fn new(inner: R, path: &Path, total: u64) -> Progress<R> {
+        let counter = (total >= MIN_TRACKED).then(|| {
+            let counter = Arc::new(Counter {
+                read: AtomicU64::new(0),
+                total,
+            });
+            LOADING.lock().unwrap().push(counter.clone());
+            counter
+        });
+        Progress {
+            inner,
+            path: path.to_owned(),
+            counter,
+            logged: 0,
+        }
+    }
+
+    fn advance(&mut self, amount: usize) {
+        let counter = match &self.counter {
+            Some(counter) => counter,
+            None => return,
+        };
+        let read = counter.read.fetch_add(amount as u64, Ordering::Relaxed) + amount as u64;
+        let tenth = 10 * read / counter.total;
+        if tenth > self.logged && tenth < 10 {
+            self.logged = tenth;
+            log::info!("Loading {}: {}%", self.path.display(), 10 * tenth);
+        }
+    }
+}
+
+impl<R> Drop for Progress<R> {
+    fn drop(&mut self) {
+        if let Some(counter) = &self.counter {
+            LOADING
+                .lock()
+                .unwrap()
+                .retain(|loading| !Arc::ptr_eq(loading, counter));
+        }
+    }
+}
+
+impl<R: BufRead> Read for Progress<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let amount = self.inner.read(buf)?;
+        self.advance(amount);
+        Ok(amount)
+    }
+}
+
+impl<R: BufRead> BufRead for Progress<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.inner.consume(amount);
+        self.advance(amount);
+    }
+}
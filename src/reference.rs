@@ -0,0 +1,162 @@
+use crate::{model::PlainVertex, pipeline, ArtifactUniform, UpAxis};
+use cgmath::SquareMatrix;
+use std::ops::Range;
+use wgpu::util::DeviceExt;
+
+// Reference geometry to judge scale and orientation by: a grid on the
+// ground plane (--grid) and the coordinate axes at the origin (--axes),
+// x red, y green and z blue, each one grid spacing long.  They are scene
+// decoration, not artifacts, so no filter, sequencer or history touches
+// them.  Every pane draws them first, so artifacts are drawn over them,
+// except filled meshes behind them or on the ground plane (--depth-bias).
+//
+// Positions are in world coordinates, like a PLY file's, so the ground is
+// the XZ plane, or with --up z, the XY plane.
+
+pub const DEFAULT_GRID_SPACING: f32 = 1.0;
+pub const DEFAULT_GRID_EXTENT: f32 = 10.0;
+const GRID_COLOR: [f32; 4] = [0.35, 0.35, 0.35, 1.0];
+const AXIS_COLORS: [[f32; 4]; 3] = [[1.0, 0.0, 0.0, 1.0], [0.0, 1.0, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0]];
+
+pub struct Reference {
+    pipeline: wgpu::RenderPipeline,
+    vertices: wgpu::Buffer,
+    // The grid lines come first in the buffer, then one line per axis.
+    grid: Range<u32>,
+    axes: [Range<u32>; 3],
+    // The colors, bound in place of an artifact's uniform.
+    grid_bind_group: wgpu::BindGroup,
+    axis_bind_groups: Vec<wgpu::BindGroup>,
+}
+
+impl Reference {
+    pub fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        artifact_bind_group_layout: &wgpu::BindGroupLayout,
+        format: wgpu::TextureFormat,
+        up: UpAxis,
+        spacing: f32,
+        extent: f32,
+    ) -> Reference {
+        // The grid lines run from -extent to extent, across the two ground
+        // axes, at whole multiples of the spacing.
+        let ground = match up {
+            UpAxis::Y => [0, 2],
+            UpAxis::Z => [0, 1],
+        };
+        let point = |u: f32, v: f32| {
+            let mut position = [0.0; 3];
+            position[ground[0]] = u;
+            position[ground[1]] = v;
+            PlainVertex { position }
+        };
+        let steps = (extent / spacing).floor() as i32;
+        let mut vertices = vec![];
+        for step in -steps..=steps {
+            let offset = step as f32 * spacing;
+            vertices.extend([point(offset, -extent), point(offset, extent)]);
+            vertices.extend([point(-extent, offset), point(extent, offset)]);
+        }
+        let grid = 0..vertices.len() as u32;
+        let axes = [0, 1, 2].map(|axis| {
+            let mut end = [0.0; 3];
+            end[axis] = spacing;
+            let start = vertices.len() as u32;
+            vertices.extend([PlainVertex { position: [0.0; 3] }, PlainVertex { position: end }]);
+            start..start + 2
+        });
+        let vertices = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("reference::vertices"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let bind_group = |color: [f32; 4]| {
+            let uniform = ArtifactUniform::new(color, cgmath::Matrix4::identity().into());
+            let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("reference::uniform_buffer"),
+                contents: bytemuck::cast_slice(&[uniform]),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: artifact_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }],
+                label: Some("reference::bind_group"),
+            })
+        };
+
+        Reference {
+            pipeline: create_pipeline(device, layout, format),
+            vertices,
+            grid,
+            axes,
+            grid_bind_group: bind_group(GRID_COLOR),
+            axis_bind_groups: AXIS_COLORS.into_iter().map(bind_group).collect(),
+        }
+    }
+
+    // Draw with the pane's world bind group already set.
+    pub fn render<'rpass>(&'rpass self, grid: bool, axes: bool, render_pass: &mut wgpu::RenderPass<'rpass>) {
+        if !grid && !axes {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, self.vertices.slice(..));
+        if grid {
+            render_pass.set_bind_group(1, &self.grid_bind_group, &[]);
+            render_pass.draw(self.grid.clone(), 0..1);
+        }
+        if axes {
+            for (range, bind_group) in self.axes.iter().zip(&self.axis_bind_groups) {
+                render_pass.set_bind_group(1, bind_group, &[]);
+                render_pass.draw(range.clone(), 0..1);
+            }
+        }
+    }
+}
+
+// Uniformly colored lines, as for wireframes.
+fn create_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("reference::shader"),
+        source: wgpu::ShaderSource::Wgsl(
+            (include_str!("pipeline/shader/plain_geometry.wsgl").to_owned()).into(),
+        ),
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("reference::render_pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            compilation_options: Default::default(),
+            entry_point: "vs_main",
+            buffers: &[PlainVertex::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            compilation_options: Default::default(),
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::LineList,
+            ..Default::default()
+        },
+        depth_stencil: pipeline::grid_depth(),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
@@ -0,0 +1,68 @@
+use crate::{history::History, sequence::Replace, Artifact, ArtifactKind, EventProxy, Key, Sequencer, Staged, WorldviewConfig, WorldviewError};
+use std::{
+    collections::HashMap,
+    io::BufRead,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+// Accumulate is a sequencer that keeps every instance of every artifact,
+// so numbered frames pile up on screen, as for a trajectory or a scan
+// building up.  A frame goes only when its file is removed, or when
+// --vram-limit evicts it to make room.  Otherwise it loads artifacts
+// just as Replace does.
+
+#[derive(Clone)]
+pub struct Accumulate {
+    replace: Replace,
+}
+
+impl Accumulate {
+    pub fn new(
+        artifacts: Arc<Mutex<HashMap<Key, Artifact>>>,
+        event_loop_proxy: EventProxy,
+        config: &WorldviewConfig,
+    ) -> Self {
+        Self {
+            replace: Replace::new(artifacts, event_loop_proxy, config).keeping_instances(None),
+        }
+    }
+}
+
+impl Sequencer for Accumulate {
+    fn get_artifacts(&self) -> Arc<Mutex<HashMap<Key, Artifact>>> {
+        self.replace.get_artifacts()
+    }
+
+    fn forced_kind(&self, key: &Key) -> Option<ArtifactKind> {
+        self.replace.forced_kind(key)
+    }
+
+    fn history(&self) -> Option<History> {
+        self.replace.history()
+    }
+
+    fn add(&self, path: &Path) -> Result<Key, WorldviewError> {
+        self.replace.add(path)
+    }
+
+    fn add_as(&self, key: Key, path: &Path) -> Result<Key, WorldviewError> {
+        self.replace.add_as(key, path)
+    }
+
+    fn add_reader(&self, key: Key, f: &mut dyn BufRead) -> Result<Key, WorldviewError> {
+        self.replace.add_reader(key, f)
+    }
+
+    fn add_staged(&self, key: Key, staged: Staged) -> Result<Key, WorldviewError> {
+        self.replace.add_staged(key, staged)
+    }
+
+    fn remove(&self, path: &Path) -> Result<Key, WorldviewError> {
+        self.replace.remove(path)
+    }
+
+    fn remove_as(&self, key: Key) -> Result<Key, WorldviewError> {
+        self.replace.remove_as(key)
+    }
+}
@@ -1,15 +1,51 @@
-use crate::{Artifact, Key};
+use crate::{history::History, Artifact, ArtifactKind, Key, Staged, WorldviewError};
 use std::{
     collections::HashMap,
-    path::PathBuf,
+    io::BufRead,
+    path::Path,
     sync::{Arc, Mutex},
 };
 
 pub trait Sequencer {
-    fn add(&self, path: &PathBuf) -> Option<Key>;
-    fn remove(&self, path: &PathBuf) -> Option<Key>;
+    // Each returns the key the artifact is shown under, or why it is not.
+    fn add(&self, path: &Path) -> Result<Key, WorldviewError>;
+    fn add_as(&self, key: Key, path: &Path) -> Result<Key, WorldviewError>;
+    fn add_reader(&self, key: Key, f: &mut dyn BufRead) -> Result<Key, WorldviewError>;
+    fn add_staged(&self, key: Key, staged: Staged) -> Result<Key, WorldviewError>;
+    fn remove(&self, path: &Path) -> Result<Key, WorldviewError>;
+    fn remove_as(&self, key: Key) -> Result<Key, WorldviewError>;
     fn get_artifacts(&self) -> Arc<Mutex<HashMap<Key, Artifact>>>;
+
+    // The artifact type the user forced for this key, overriding
+    // detection from the PLY header; see ArtifactKind::resolve().
+    fn forced_kind(&self, key: &Key) -> Option<ArtifactKind> {
+        ArtifactKind::of_name(&key.artifact)
+    }
+
+    // Where recently injected files are kept (--history), if they are.
+    // Injectors that parse files themselves keep their bytes in the
+    // Staged they add.
+    fn history(&self) -> Option<History> {
+        None
+    }
+}
+
+// How long artifacts stay on screen (--sequencer).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SequencerKind {
+    // Show only the newest instance of each artifact.
+    #[default]
+    Replace,
+    // Show every instance ever injected.
+    Accumulate,
+    // Show the newest --ring-size instances of each artifact.
+    #[value(alias = "ring")]
+    RingBuffer,
 }
 
+pub mod accumulate;
 pub mod replace;
+pub mod ring_buffer;
+pub use accumulate::Accumulate;
 pub use replace::Replace;
+pub use ring_buffer::RingBuffer;
@@ -1,110 +1,307 @@
 use crate::{
-    window::{DEVICE, QUEUE},
-    Artifact, Element, InjectionEvent, Key, Sequencer, PLY_RE,
+    events::{self, Event},
+    history::History,
+    upload::Upload,
+    vram::{self, Budget},
+    window::gpu,
+    Artifact, ArtifactKind, EdgeColor, Element, EventProxy, Format, InjectionEvent, Key, KindOverride, Sequencer, Staged,
+    WorldviewConfig, WorldviewError,
 };
-use ply_rs::{parser::Parser, ply};
-use regex::Regex;
 use std::{
-    collections::HashMap,
-    fs::File,
-    io::BufReader,
-    path::PathBuf,
+    collections::{HashMap, HashSet, VecDeque},
+    io::{self, BufRead},
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
 };
-use winit::event_loop::EventLoopProxy;
 
-// Replace is a sequencer that only keeps the newest artifact, and
-// ejects all others.  Consequently, the display will show at most
-// one artifact type at a time.
+// Replace is a sequencer that only keeps the newest instance of each
+// artifact, and ejects the others.  Consequently, a producer writing
+// numbered frames is shown as an animation, one frame at a time.  The
+// new frame takes over the buffers of the one it replaces.
 
 #[derive(Clone)]
 pub struct Replace {
     pub artifacts: Arc<Mutex<HashMap<Key, Artifact>>>,
-    pub ply_re: Regex,
-    event_loop_proxy: EventLoopProxy<InjectionEvent>,
+    event_loop_proxy: EventProxy,
+    // Files that failed to parse, most likely because the producer was
+    // still writing them.  These are retried on the next event.
+    pending: Arc<Mutex<HashMap<PathBuf, Key>>>,
+    // Artifact types forced by the user, by artifact name.
+    overrides: Arc<[KindOverride]>,
+    edge_color: EdgeColor,
+    // Point clouds larger than this are decimated before upload.
+    max_points: Option<usize>,
+    // Mesh vertices closer than this are merged before upload.
+    weld: Option<f32>,
+    // Whether meshes with texture coordinates have a texture to show.
+    textured: bool,
+    // Artifact names already reported as shared by different types.
+    collisions: Arc<Mutex<HashSet<(String, ArtifactKind)>>>,
+    // GPU memory held by the artifacts, oldest evicted first when over
+    // the limit.
+    budget: Arc<Mutex<Budget>>,
+    // Recently injected files, to show again.
+    history: Option<History>,
+    // Whether to print injections and removals to stdout.
+    events_out: bool,
+    // How many instances of each artifact stay on screen: one, unless
+    // this is an Accumulate (all) or a RingBuffer (the newest N).
+    instances: Option<usize>,
+    // Keys in the order they were last shown, newest last, to eject the
+    // oldest instances first.
+    shown: Arc<Mutex<VecDeque<Key>>>,
 }
 
 impl Replace {
     pub fn new(
         artifacts: Arc<Mutex<HashMap<Key, Artifact>>>,
-        event_loop_proxy: EventLoopProxy<InjectionEvent>,
+        event_loop_proxy: EventProxy,
+        config: &WorldviewConfig,
     ) -> Self {
         Self {
             artifacts,
-            ply_re: Regex::new(PLY_RE).expect("invalid regex"),
             event_loop_proxy,
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            overrides: config.kind_overrides.clone().into(),
+            edge_color: config.edge_color,
+            max_points: config.max_points,
+            weld: config.weld,
+            textured: config.texture.is_some(),
+            collisions: Arc::new(Mutex::new(HashSet::new())),
+            budget: Arc::new(Mutex::new(Budget::new(config.vram_limit))),
+            history: (config.history > 0).then(|| History::new(config.history)),
+            events_out: config.events_out,
+            instances: Some(1),
+            shown: Arc::new(Mutex::new(VecDeque::new())),
         }
     }
 
-    fn inject(&self, key: Key, path: &PathBuf) {
-        let parse_header = Parser::<ply::DefaultElement>::new();
+    // A Replace that keeps this many instances of each artifact, or all
+    // of them; see Accumulate and RingBuffer.
+    pub(super) fn keeping_instances(mut self, instances: Option<usize>) -> Self {
+        self.instances = instances;
+        self
+    }
+
+    fn key(&self, path: &Path) -> Result<Key, WorldviewError> {
+        Key::from_path(path).ok_or_else(|| WorldviewError::NoKey(path.to_path_buf()))
+    }
 
-        let f = File::open(path).unwrap();
-        let mut f = BufReader::new(f);
-        let header = match parse_header.read_header(&mut f) {
-            Ok(h) => h,
-            Err(err) => {
-                log::error!("Failed to parse PLY header {}: {:?}", path.display(), err);
-                return;
+    // Retry files that were rejected earlier as partially written.  Files
+    // that have since been deleted are forgotten.
+    fn retry_pending(&self, current: &Path) {
+        let pending: Vec<(PathBuf, Key)> = {
+            let mut pending = self.pending.lock().unwrap();
+            pending.retain(|path, _| path.exists());
+            pending
+                .iter()
+                .filter(|(path, _)| *path != current)
+                .map(|(path, key)| (path.clone(), key.clone()))
+                .collect()
+        };
+
+        for (path, key) in pending {
+            self.pending.lock().unwrap().remove(&path);
+            log::debug!("Retry {}", key);
+            if let Err(err) = self.inject(key.clone(), &path) {
+                log::warn!("{}", err);
+                if let WorldviewError::Unreadable { .. } = err {
+                    self.pending.lock().unwrap().insert(path, key);
+                }
             }
+        }
+    }
+
+    // Fails with WorldviewError::Unreadable when the file could not be
+    // parsed, which usually means it was caught mid-write; the caller may
+    // try again later.
+    fn inject(&self, key: Key, path: &Path) -> Result<Key, WorldviewError> {
+        let staged = match self.history {
+            Some(_) => Staged::from_path_keeping(path, self.forced_kind(&key)),
+            None => Staged::from_path(path, self.forced_kind(&key)),
         };
+        self.inject_staged(key, staged, &path.display().to_string())
+    }
 
-        if header
-            .elements
-            .get(&Element::Vertex.to_string())
-            .unwrap()
-            .count
-            == 0
-        {
-            log::warn!("{} is empty; rejecting it", key);
-            return;
+    fn inject_staged(&self, key: Key, staged: io::Result<Option<Staged>>, source: &str) -> Result<Key, WorldviewError> {
+        match staged {
+            Ok(Some(staged)) => self.upload(key, staged),
+            Ok(None) => Err(WorldviewError::Unknown(key)),
+            // The header may be complete while the body is still being
+            // written.  Leave the GPU buffers untouched.
+            Err(error) => Err(WorldviewError::Unreadable {
+                source_name: source.to_string(),
+                error,
+            }),
         }
+    }
 
-        // Remove buffers that are smaller than the new artifact.  This
-        // will cause reallocation of larger buffers, immediately below.
-        let mut artifacts = self.artifacts.lock().unwrap();
-        let needs_resize = match artifacts.get(&key) {
-            Some(artifact) => artifact.needs_resize(&header),
-            None => false,
+    // Load a parsed artifact into GPU buffers under `key`.  The artifacts
+    // lock serializes uploads from concurrent injectors.  Returns the key
+    // it is shown under, renamed if another type has its name.
+    fn upload(&self, key: Key, mut staged: Staged) -> Result<Key, WorldviewError> {
+        if let (Some(history), Some(bytes)) = (&self.history, staged.bytes.take()) {
+            history.keep(&key, bytes);
+        }
+        let vertices = match Element::Vertex.find(&staged.header) {
+            Some(element) => element.count,
+            None => return Err(WorldviewError::Missing(key, Element::Vertex)),
         };
+        if vertices == 0 {
+            return Err(WorldviewError::Empty(key));
+        }
 
-        if needs_resize {
-            artifacts.remove(&key);
+        if let Some(max_points) = self.max_points {
+            staged.decimate(max_points);
+        }
+        if let Some(epsilon) = self.weld {
+            staged.weld(epsilon);
         }
+        // What will be shown, for --events-out.
+        let kind = staged.kind();
+        let verts = Element::Vertex.find(&staged.header).map_or(0, |element| element.count);
+        let faces = Element::Facet.find(&staged.header).map(|element| element.count);
 
-        if !artifacts.contains_key(&key) {
-            // Allocate new wgpu::Buffers
-            let device = match DEVICE.get() {
-                Some(device) => device,
-                None => {
-                    log::debug!("Wait for WGPU initialization");
-                    return;
-                }
-            };
+        // Buffers that are too small for the new artifact are replaced by
+        // larger ones.  The replacement is fully loaded before it is
+        // swapped in, so the last good artifact stays on screen if
+        // anything goes wrong along the way.
+        let mut artifacts = self.artifacts.lock().unwrap();
+        let key = self.disambiguate(&artifacts, key, kind);
+        let gpu = match gpu() {
+            Some(gpu) => gpu,
+            None => {
+                log::debug!("Wait for WGPU initialization");
+                return Ok(key);
+            }
+        };
+
+        // Eject the oldest other instances of this artifact, to make room
+        // for the new one.  The first ejected hands its buffers on to the
+        // new instance, so a stream of frames reuses the same buffers, as
+        // a file overwritten in place does.  This happens under the same
+        // lock as the upload, so a redraw never sees one without the
+        // other.
+        if let Some(instances) = self.instances {
+            let mut shown = self.shown.lock().unwrap();
+            // Keys evicted or removed since are forgotten here.
+            shown.retain(|old| artifacts.contains_key(old) && *old != key);
+            let others: Vec<Key> = shown.iter().filter(|old| old.artifact == key.artifact).cloned().collect();
+            let excess = (others.len() + 1).saturating_sub(instances);
+            shown.push_back(key.clone());
+            drop(shown);
 
-            match Artifact::new(&device, &header) {
-                Some(artifact) => {
+            for old in others.into_iter().take(excess) {
+                let artifact = artifacts.remove(&old).unwrap();
+                let mut budget = self.budget.lock().unwrap();
+                if artifacts.contains_key(&key) {
+                    budget.free(&old);
+                } else {
+                    budget.rename(&old, &key);
                     artifacts.insert(key.clone(), artifact);
-                    log::debug!("Allocated artifact {}", key)
                 }
-                None => {
-                    log::debug!("Unknown artifact {}", key);
-                    return;
-                }
-            };
+                drop(budget);
+                log::debug!("Replaced {} with {}", old, key);
+                self.send(InjectionEvent::Remove(old));
+            }
         }
 
-        let queue = QUEUE.get().unwrap();
-        let artifact = artifacts.get_mut(&key).unwrap();
-        artifact.update_count(&header);
-        artifact.read_ply(&mut f, &header);
-        artifact.write_buffer(queue);
-        queue.submit([]);
+        let mut upload = Upload::begin(&gpu);
+        match artifacts.get_mut(&key) {
+            Some(artifact) if !artifact.needs_resize(&staged) => {
+                artifact.stage(staged);
+                artifact.write_buffer(&mut upload);
+            }
+            _ => {
+                // Allocate new wgpu::Buffers, or take freed ones from
+                // the pool.
+                let mut artifact = match Artifact::new(&gpu.device, &staged, self.edge_color, self.textured) {
+                    Some(artifact) => artifact,
+                    None => return Err(WorldviewError::Unknown(key)),
+                };
+
+                // Make room under the GPU memory limit, evicting the
+                // oldest artifacts, before uploading anything.
+                let bytes = artifact.allocated();
+                let evicted = match self.budget.lock().unwrap().allocate(&key, bytes) {
+                    Some(evicted) => evicted,
+                    None => return Err(WorldviewError::OverBudget { key, bytes }),
+                };
+                for old in evicted {
+                    artifacts.remove(&old);
+                    log::debug!("Evicted {} to stay under --vram-limit", old);
+                    self.send(InjectionEvent::Remove(old));
+                }
+
+                artifact.stage(staged);
+                artifact.write_buffer(&mut upload);
+                artifacts.insert(key.clone(), artifact);
+                log::debug!(
+                    "Allocated artifact {} ({:.1} MiB, {:.1} MiB in total)",
+                    key,
+                    vram::mebibytes(bytes),
+                    vram::mebibytes(vram::total())
+                )
+            }
+        }
+        upload.submit();
 
         // New buffers are loaded.  Fire the graphics refresh!
+        if self.events_out {
+            events::emit(Event::Add {
+                key: &key,
+                kind,
+                verts,
+                faces,
+            });
+        }
         self.event_loop_proxy
-            .send_event(InjectionEvent::Add(key))
+            .send_event(InjectionEvent::Add(key.clone()))
             .ok();
+        Ok(key)
+    }
+
+    // Tell the window an artifact is gone, and --events-out too.
+    fn send(&self, event: InjectionEvent) {
+        if let (true, InjectionEvent::Remove(key)) = (self.events_out, &event) {
+            events::emit(Event::Remove { key });
+        }
+        self.event_loop_proxy.send_event(event).ok();
+    }
+
+    // Two sources that use one artifact name for different artifact
+    // types would keep replacing each other's buffers.  Whichever type is
+    // loaded first keeps the name; the others are renamed to
+    // "<name>:<type>", and reported once.
+    fn disambiguate(&self, artifacts: &HashMap<Key, Artifact>, key: Key, kind: ArtifactKind) -> Key {
+        let existing = artifacts
+            .iter()
+            .find(|(existing, _)| existing.artifact == key.artifact)
+            .map(|(_, artifact)| artifact.kind());
+        match existing {
+            Some(existing) if existing != kind => {
+                let first = self
+                    .collisions
+                    .lock()
+                    .unwrap()
+                    .insert((key.artifact.clone(), kind));
+                if first {
+                    log::warn!(
+                        "Artifact name '{}' is already a {}; showing the {} as '{}:{}'",
+                        key.artifact,
+                        existing,
+                        kind,
+                        key.artifact,
+                        kind
+                    );
+                }
+                Key {
+                    artifact: format!("{}:{}", key.artifact, kind),
+                    ..key
+                }
+            }
+            _ => key,
+        }
     }
 }
 
@@ -113,47 +310,152 @@ impl Sequencer for Replace {
         self.artifacts.clone()
     }
 
-    fn add(&self, path: &PathBuf) -> Option<Key> {
-        let filename = path.file_name().unwrap().to_str().unwrap();
-        let capture = match self.ply_re.captures(filename) {
-            Some(capture) => capture,
-            None => {
-                log::warn!("cannot match {}", filename);
-                return None;
+    fn forced_kind(&self, key: &Key) -> Option<ArtifactKind> {
+        KindOverride::forced(&self.overrides, &key.artifact)
+    }
+
+    fn history(&self) -> Option<History> {
+        self.history.clone()
+    }
+
+    fn add(&self, path: &Path) -> Result<Key, WorldviewError> {
+        let key = self.key(path)?;
+        self.add_as(key, path)
+    }
+
+    fn add_as(&self, key: Key, path: &Path) -> Result<Key, WorldviewError> {
+        log::debug!("Add {}", key);
+
+        let injected = self.inject(key.clone(), path);
+        if let Err(WorldviewError::Unreadable { .. }) = injected {
+            self.pending
+                .lock()
+                .unwrap()
+                .insert(path.to_path_buf(), key);
+        } else {
+            self.pending.lock().unwrap().remove(path);
+        }
+        self.retry_pending(path);
+        injected
+    }
+
+    fn add_reader(&self, key: Key, mut f: &mut dyn BufRead) -> Result<Key, WorldviewError> {
+        log::debug!("Add {}", key);
+        let source = key.to_string();
+        let staged = match self.history {
+            Some(_) => {
+                let mut bytes = vec![];
+                f.read_to_end(&mut bytes)
+                    .and_then(|_| Staged::from_bytes(bytes.into(), Format::Ply, self.forced_kind(&key)))
             }
+            None => Staged::read_ply(&mut f, self.forced_kind(&key)),
         };
+        self.inject_staged(key, staged, &source)
+    }
 
-        let key = Key {
-            instance: capture["instance"].parse::<u32>().ok(),
-            artifact: capture["artifact"].to_string(),
-        };
+    fn add_staged(&self, key: Key, staged: Staged) -> Result<Key, WorldviewError> {
         log::debug!("Add {}", key);
+        self.upload(key, staged)
+    }
 
-        self.inject(key.clone(), path);
-        Some(key)
+    // Only the instance named by the file is removed.  Once a newer one
+    // has replaced it, removing its file changes nothing on screen.
+    fn remove(&self, path: &Path) -> Result<Key, WorldviewError> {
+        let key = self.key(path)?;
+        self.remove_as(key)
     }
 
-    fn remove(&self, path: &PathBuf) -> Option<Key> {
-        let filename = path.file_name().unwrap().to_str().unwrap();
-        let capture = match self.ply_re.captures(filename) {
-            Some(capture) => capture,
-            None => {
-                log::warn!("cannot match {}", filename);
-                return None;
-            }
+    fn remove_as(&self, key: Key) -> Result<Key, WorldviewError> {
+        log::debug!("Remove {}", key);
+
+        self.artifacts.lock().unwrap().remove(&key);
+        self.budget.lock().unwrap().free(&key);
+
+        self.send(InjectionEvent::Remove(key.clone()));
+        Ok(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{window::start_test_gpu, Instance};
+    use std::{fs, io::Cursor};
+    use tokio::sync::mpsc;
+
+    const CLOUD: &str = "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\nend_header\n0 0 0\n1 0 0\n0 1 0\n";
+    const MESH: &str = "ply\nformat ascii 1.0\ncomment kind mesh\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\nelement face 1\nproperty list uchar int vertex_indices\nend_header\n0 0 0\n1 0 0\n0 1 0\n3 0 1 2\n";
+
+    // A Replace with artifacts of its own, or None without a GPU.
+    async fn replace() -> Option<Replace> {
+        if !start_test_gpu().await {
+            return None;
+        }
+        let (sender, _) = mpsc::unbounded_channel();
+        let artifacts = Arc::new(Mutex::new(HashMap::new()));
+        Some(Replace::new(artifacts, EventProxy::Headless(sender), &WorldviewConfig::default()))
+    }
+
+    // A file cut short is skipped, and the next good one still loads.
+    #[tokio::test]
+    async fn truncated_file_is_skipped() {
+        let Some(replace) = replace().await else {
+            return;
         };
+        let dir = tempfile::tempdir().unwrap();
+        let truncated = dir.path().join("0.cloud.ply");
+        fs::write(&truncated, &CLOUD[..CLOUD.len() - 6]).unwrap();
+        assert!(matches!(replace.add(&truncated), Err(WorldviewError::Unreadable { .. })));
+
+        let good = dir.path().join("1.cloud.ply");
+        fs::write(&good, CLOUD).unwrap();
+        let key = replace.add(&good).unwrap();
+        assert!(replace.artifacts.lock().unwrap().contains_key(&key));
+    }
 
+    // A good file overwritten by a broken one, cut short or empty, stays
+    // on screen as it was.
+    #[tokio::test]
+    async fn broken_file_keeps_the_good_one() {
+        let Some(replace) = replace().await else {
+            return;
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cloud.ply");
         let key = Key {
             instance: None,
-            artifact: capture["artifact"].to_string(),
+            artifact: "cloud".to_string(),
         };
-        log::debug!("Remove {}", key);
+        fs::write(&path, CLOUD).unwrap();
+        replace.add_as(key.clone(), &path).unwrap();
+        let bounds = replace.artifacts.lock().unwrap()[&key].bounds().unwrap();
 
-        self.artifacts.lock().unwrap().remove(&key);
+        let empty = CLOUD.replace("vertex 3", "vertex 0");
+        for broken in [&CLOUD[..CLOUD.len() - 6], &empty[..empty.find("end_header\n").unwrap() + 11]] {
+            fs::write(&path, broken).unwrap();
+            assert!(replace.add_as(key.clone(), &path).is_err());
+            assert_eq!(replace.artifacts.lock().unwrap()[&key].bounds(), Some(bounds));
+        }
+    }
 
-        self.event_loop_proxy
-            .send_event(InjectionEvent::Remove(key.clone()))
-            .ok();
-        Some(key)
+    // A mesh reusing the name of a point cloud is shown as "name:mesh",
+    // every time, and reported only the first time.
+    #[tokio::test]
+    async fn other_type_is_renamed() {
+        let Some(replace) = replace().await else {
+            return;
+        };
+        let key = |instance| Key {
+            instance: Some(Instance::Number(instance)),
+            artifact: "thing".to_string(),
+        };
+        let shown = replace.add_reader(key(0), &mut Cursor::new(CLOUD)).unwrap();
+        assert_eq!(shown.artifact, "thing");
+        for instance in 1..3 {
+            let shown = replace.add_reader(key(instance), &mut Cursor::new(MESH)).unwrap();
+            assert_eq!(shown.artifact, "thing:mesh");
+        }
+        let collisions = replace.collisions.lock().unwrap();
+        assert_eq!(*collisions, HashSet::from([("thing".to_string(), ArtifactKind::Mesh)]));
     }
 }
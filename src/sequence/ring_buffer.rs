@@ -0,0 +1,68 @@
+use crate::{history::History, sequence::Replace, Artifact, ArtifactKind, EventProxy, Key, Sequencer, Staged, WorldviewConfig, WorldviewError};
+use std::{
+    collections::HashMap,
+    io::BufRead,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+// RingBuffer is a sequencer that keeps the newest N instances of each
+// artifact (--ring-size), like Accumulate, but bounded, so a long
+// session does not grow without limit.  The oldest instance is ejected
+// for each new one, and hands its buffers on to it.  Otherwise it loads
+// artifacts just as Replace does.
+
+#[derive(Clone)]
+pub struct RingBuffer {
+    replace: Replace,
+}
+
+impl RingBuffer {
+    pub fn new(
+        artifacts: Arc<Mutex<HashMap<Key, Artifact>>>,
+        event_loop_proxy: EventProxy,
+        config: &WorldviewConfig,
+    ) -> Self {
+        Self {
+            replace: Replace::new(artifacts, event_loop_proxy, config).keeping_instances(Some(config.ring_size)),
+        }
+    }
+}
+
+impl Sequencer for RingBuffer {
+    fn get_artifacts(&self) -> Arc<Mutex<HashMap<Key, Artifact>>> {
+        self.replace.get_artifacts()
+    }
+
+    fn forced_kind(&self, key: &Key) -> Option<ArtifactKind> {
+        self.replace.forced_kind(key)
+    }
+
+    fn history(&self) -> Option<History> {
+        self.replace.history()
+    }
+
+    fn add(&self, path: &Path) -> Result<Key, WorldviewError> {
+        self.replace.add(path)
+    }
+
+    fn add_as(&self, key: Key, path: &Path) -> Result<Key, WorldviewError> {
+        self.replace.add_as(key, path)
+    }
+
+    fn add_reader(&self, key: Key, f: &mut dyn BufRead) -> Result<Key, WorldviewError> {
+        self.replace.add_reader(key, f)
+    }
+
+    fn add_staged(&self, key: Key, staged: Staged) -> Result<Key, WorldviewError> {
+        self.replace.add_staged(key, staged)
+    }
+
+    fn remove(&self, path: &Path) -> Result<Key, WorldviewError> {
+        self.replace.remove(path)
+    }
+
+    fn remove_as(&self, key: Key) -> Result<Key, WorldviewError> {
+        self.replace.remove_as(key)
+    }
+}
@@ -0,0 +1,252 @@
+use crate::{transform::Pose, EventProxy, InjectionEvent, Transforms};
+#[cfg(not(feature = "notify"))]
+use inotify::{EventMask, Inotify, WatchMask};
+use regex::Regex;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use tokio::sync::watch;
+
+// Display settings that can be tuned while worldview runs (--config).  The
+// TOML file is watched, and every saved edit is applied live:
+//
+//     filter = ["lidar", "mesh"]          # artifact names shown
+//     background = [0.1, 0.1, 0.1]
+//
+//     [colors]
+//     lidar = [1.0, 0.5, 0.0]             # RGB, or RGBA
+//
+//     [transforms.lidar]                  # as for --transforms
+//     translation = [0.0, 0.0, 1.2]
+//
+//     [flags]                             # command line flags, by name
+//     sequencer = "accumulate"
+//     width = 1280
+//     edges = true
+//     color = ["lidar=1,0.5,0"]           # repeated flags as lists
+//
+// Everything is optional.  An edit that does not parse is reported, and
+// the previous settings stay in effect.  Flags are the exception: they
+// are read once, at start, as defaults for those not given on the
+// command line (see main.rs).
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    filter: Option<Vec<String>>,
+    background: Option<[f32; 3]>,
+    #[serde(default)]
+    colors: HashMap<String, Vec<f32>>,
+    #[serde(default)]
+    transforms: HashMap<String, Pose>,
+    #[serde(default)]
+    flags: toml::Table,
+}
+
+impl File {
+    fn from_path(path: &Path) -> Result<File, String> {
+        let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        toml::from_str(&text).map_err(|err| err.to_string())
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Settings {
+    // Artifact names shown; the rest are loaded, but hidden.
+    filter: Option<Regex>,
+    pub background: Option<[f32; 3]>,
+    // Colors overriding the artifact type's own, by artifact name.
+    pub colors: HashMap<String, [f32; 4]>,
+    pub transforms: Transforms,
+}
+
+impl Settings {
+    pub fn from_path(path: &std::path::Path) -> Result<Settings, String> {
+        let file = File::from_path(path)?;
+
+        let filter = match file.filter {
+            Some(names) => Some(Regex::new(&format!("^({})$", names.join("|"))).map_err(|err| err.to_string())?),
+            None => None,
+        };
+        let mut colors = HashMap::new();
+        for (artifact, color) in file.colors {
+            let color = match color[..] {
+                [r, g, b] => [r, g, b, 1.0],
+                [r, g, b, a] => [r, g, b, a],
+                _ => return Err(format!("{}: a color is [r, g, b] or [r, g, b, a]", artifact)),
+            };
+            colors.insert(artifact, color);
+        }
+
+        Ok(Settings {
+            filter,
+            background: file.background,
+            colors,
+            transforms: Transforms::from_poses(file.transforms)?,
+        })
+    }
+
+    // The [flags] table, left for the command line parser to make sense
+    // of.
+    pub fn flags_from_path(path: &std::path::Path) -> Result<toml::Table, String> {
+        Ok(File::from_path(path)?.flags)
+    }
+
+    // Settings with only the --transforms sidecar, when there is no
+    // --config.
+    pub fn from_transforms(transforms: Transforms) -> Settings {
+        Settings {
+            transforms,
+            ..Default::default()
+        }
+    }
+
+    pub fn background(&self) -> [f32; 3] {
+        self.background.unwrap_or([0.9, 0.9, 0.9])
+    }
+
+    pub fn shows(&self, artifact: &str) -> bool {
+        match &self.filter {
+            Some(filter) => filter.is_match(artifact),
+            None => true,
+        }
+    }
+}
+
+// Reload the settings whenever the file is saved, and hand them to the
+// window.  Like the live injectors' run_file, this watches the parent
+// directory so editors that save by renaming a new file into place are
+// seen too.
+#[cfg(feature = "notify")]
+pub async fn watch(path: PathBuf, proxy: EventProxy, exit: watch::Sender<bool>) {
+    use crate::inject::watcher::{watch_dir, Change};
+
+    let (dir, file_name) = parent(&path);
+    let (_watcher, mut events) = match watch_dir(&dir, false) {
+        Ok(watching) => watching,
+        Err(err) => {
+            log::warn!("Cannot watch {}: {}", path.display(), err);
+            return;
+        }
+    };
+
+    let mut exit = exit.subscribe();
+    loop {
+        let event = tokio::select! {
+            _ = exit.changed() => return,
+            event = events.recv() => match event {
+                Some(event) => event,
+                None => return,
+            },
+        };
+        let saved = event.paths.iter().any(|changed| {
+            changed.file_name() == Some(file_name.as_os_str())
+                && matches!(Change::of(&event.kind, changed), Some(Change::Written))
+        });
+        if saved {
+            reload(&path, &proxy);
+        }
+    }
+}
+
+// Without the notify feature, inotify is polled, which is plenty for
+// hand edits.
+#[cfg(not(feature = "notify"))]
+pub async fn watch(path: PathBuf, proxy: EventProxy, exit: watch::Sender<bool>) {
+    let (dir, file_name) = parent(&path);
+
+    let mut inotify = Inotify::init().unwrap();
+    inotify
+        .watches()
+        .add(dir, WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO)
+        .unwrap();
+
+    let mut exit = exit.subscribe();
+    let mut interval = tokio::time::interval(std::time::Duration::from_millis(250));
+    let mut buffer = [0; 1024];
+    loop {
+        tokio::select! {
+            _ = exit.changed() => return,
+            _ = interval.tick() => {}
+        }
+
+        let changed = match inotify.read_events(&mut buffer) {
+            Ok(mut events) => events.any(|event| {
+                event.name == Some(file_name.as_os_str())
+                    && (event.mask == EventMask::CLOSE_WRITE || event.mask == EventMask::MOVED_TO)
+            }),
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => false,
+            Err(err) => {
+                log::warn!("Stopped watching {}: {}", path.display(), err);
+                return;
+            }
+        };
+        if changed {
+            reload(&path, &proxy);
+        }
+    }
+}
+
+// The directory to watch for the file, and the file's name in it.
+fn parent(path: &Path) -> (PathBuf, std::ffi::OsString) {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => std::env::current_dir().unwrap(),
+    };
+    (dir, path.file_name().unwrap().to_owned())
+}
+
+fn reload(path: &Path, proxy: &EventProxy) {
+    match Settings::from_path(path) {
+        Ok(settings) => {
+            log::info!("Reloaded {}", path.display());
+            proxy.send_event(InjectionEvent::Settings(Box::new(settings))).ok();
+        }
+        Err(err) => log::warn!("{}: {}; keeping the previous settings", path.display(), err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    // Save `text` to the watched file until the window is sent settings
+    // that pass `check`; writes before the watch is in place are missed.
+    async fn save(path: &Path, text: &str, events: &mut mpsc::UnboundedReceiver<InjectionEvent>, check: impl Fn(&Settings) -> bool) {
+        for _ in 0..100 {
+            fs::write(path, text).unwrap();
+            if let Ok(Some(InjectionEvent::Settings(settings))) = tokio::time::timeout(Duration::from_millis(50), events.recv()).await {
+                if check(&settings) {
+                    return;
+                }
+            }
+        }
+        panic!("{:?} was not applied", text);
+    }
+
+    // Editing the filter in the config file shows and hides artifacts
+    // while running.
+    #[tokio::test]
+    async fn rewriting_toggles_the_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("worldview.toml");
+        fs::write(&path, "").unwrap();
+        let (sender, mut events) = mpsc::unbounded_channel();
+        let (exit, _) = watch::channel(false);
+        let watching = tokio::spawn(watch(path.clone(), EventProxy::Headless(sender), exit.clone()));
+
+        let only_lidar = |settings: &Settings| settings.shows("lidar") && !settings.shows("mesh");
+        save(&path, "filter = [\"lidar\"]\n", &mut events, only_lidar).await;
+        let both = |settings: &Settings| settings.shows("lidar") && settings.shows("mesh");
+        save(&path, "background = [0.0, 0.0, 0.0]\n", &mut events, both).await;
+        save(&path, "filter = [\"lidar\"]\n", &mut events, only_lidar).await;
+
+        exit.send_replace(true);
+        watching.await.unwrap();
+    }
+}
@@ -0,0 +1,269 @@
+use crate::View;
+use winit::keyboard::{Key, NamedKey};
+
+// Every keyboard shortcut, in one table.  The window looks keys up here
+// and dispatches on the Action, and the help overlay lists this same
+// table, so the two cannot drift apart.
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    ResetView,
+    FrameAll,
+    CycleFocus,
+    CycleRenderMode,
+    ToggleEdges,
+    ToggleGrid,
+    ToggleAxes,
+    ToggleContinuous,
+    ToggleLinked,
+    NarrowFov,
+    WidenFov,
+    ToggleProjection,
+    CycleSensitivity,
+    GrowPoints,
+    ShrinkPoints,
+    ToggleLegend,
+    ToggleHelp,
+    SnapView(View),
+    HistoryBack,
+    HistoryForward,
+    HistoryLive,
+    Rescan,
+    PlaybackPause,
+    PlaybackForward,
+    PlaybackBack,
+    PlaybackRestart,
+    Screenshot,
+    SaveCamera,
+    LoadCamera,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Trigger {
+    Named(NamedKey),
+    Character(&'static str),
+}
+
+pub struct Shortcut {
+    trigger: Trigger,
+    // How the key is shown in the help overlay.
+    pub label: &'static str,
+    pub action: Action,
+    pub description: &'static str,
+}
+
+pub const SHORTCUTS: &[Shortcut] = &[
+    Shortcut {
+        trigger: Trigger::Named(NamedKey::Escape),
+        label: "Esc",
+        action: Action::Quit,
+        description: "Quit",
+    },
+    Shortcut {
+        trigger: Trigger::Character("0"),
+        label: "0",
+        action: Action::ResetView,
+        description: "Reset the view",
+    },
+    Shortcut {
+        trigger: Trigger::Character("f"),
+        label: "F",
+        action: Action::FrameAll,
+        description: "Fit everything shown into view",
+    },
+    Shortcut {
+        trigger: Trigger::Character("1"),
+        label: "1",
+        action: Action::SnapView(View::Front),
+        description: "Front view",
+    },
+    Shortcut {
+        trigger: Trigger::Character("3"),
+        label: "3",
+        action: Action::SnapView(View::Side),
+        description: "Side view",
+    },
+    Shortcut {
+        trigger: Trigger::Character("7"),
+        label: "7",
+        action: Action::SnapView(View::Top),
+        description: "Top view",
+    },
+    Shortcut {
+        trigger: Trigger::Character("5"),
+        label: "5",
+        action: Action::SnapView(View::Isometric),
+        description: "Isometric view",
+    },
+    Shortcut {
+        trigger: Trigger::Named(NamedKey::Tab),
+        label: "Tab",
+        action: Action::CycleFocus,
+        description: "Focus the next artifact",
+    },
+    Shortcut {
+        trigger: Trigger::Character("m"),
+        label: "M",
+        action: Action::CycleRenderMode,
+        description: "Next render mode for the focus",
+    },
+    Shortcut {
+        trigger: Trigger::Character("w"),
+        label: "W",
+        action: Action::ToggleEdges,
+        description: "Edges over filled meshes on/off",
+    },
+    Shortcut {
+        trigger: Trigger::Character("#"),
+        label: "#",
+        action: Action::ToggleGrid,
+        description: "Ground grid on/off",
+    },
+    Shortcut {
+        trigger: Trigger::Character("a"),
+        label: "A",
+        action: Action::ToggleAxes,
+        description: "Coordinate axes on/off",
+    },
+    Shortcut {
+        trigger: Trigger::Character("c"),
+        label: "C",
+        action: Action::ToggleContinuous,
+        description: "Continuous rendering on/off",
+    },
+    Shortcut {
+        trigger: Trigger::Character("l"),
+        label: "L",
+        action: Action::ToggleLinked,
+        description: "Link/unlink split pane cameras",
+    },
+    Shortcut {
+        trigger: Trigger::Character("["),
+        label: "[",
+        action: Action::NarrowFov,
+        description: "Narrow the field of view",
+    },
+    Shortcut {
+        trigger: Trigger::Character("]"),
+        label: "]",
+        action: Action::WidenFov,
+        description: "Widen the field of view",
+    },
+    Shortcut {
+        trigger: Trigger::Character("o"),
+        label: "O",
+        action: Action::ToggleProjection,
+        description: "Perspective/orthographic projection",
+    },
+    Shortcut {
+        trigger: Trigger::Character("p"),
+        label: "P",
+        action: Action::CycleSensitivity,
+        description: "Next mouse sensitivity preset",
+    },
+    Shortcut {
+        trigger: Trigger::Character("+"),
+        label: "+",
+        action: Action::GrowPoints,
+        description: "Larger points",
+    },
+    Shortcut {
+        trigger: Trigger::Character("-"),
+        label: "-",
+        action: Action::ShrinkPoints,
+        description: "Smaller points",
+    },
+    Shortcut {
+        trigger: Trigger::Named(NamedKey::ArrowLeft),
+        label: "Left",
+        action: Action::HistoryBack,
+        description: "Step back through the history",
+    },
+    Shortcut {
+        trigger: Trigger::Named(NamedKey::ArrowRight),
+        label: "Right",
+        action: Action::HistoryForward,
+        description: "Step forward through the history",
+    },
+    Shortcut {
+        trigger: Trigger::Named(NamedKey::End),
+        label: "End",
+        action: Action::HistoryLive,
+        description: "Back to live",
+    },
+    Shortcut {
+        trigger: Trigger::Character("r"),
+        label: "R",
+        action: Action::Rescan,
+        description: "Reload every file in the watched directory",
+    },
+    Shortcut {
+        trigger: Trigger::Named(NamedKey::Space),
+        label: "Space",
+        action: Action::PlaybackPause,
+        description: "Pause/resume playback",
+    },
+    Shortcut {
+        trigger: Trigger::Character("."),
+        label: ".",
+        action: Action::PlaybackForward,
+        description: "Next playback frame, while paused",
+    },
+    Shortcut {
+        trigger: Trigger::Character(","),
+        label: ",",
+        action: Action::PlaybackBack,
+        description: "Previous playback frame, while paused",
+    },
+    Shortcut {
+        trigger: Trigger::Named(NamedKey::Home),
+        label: "Home",
+        action: Action::PlaybackRestart,
+        description: "Restart playback",
+    },
+    Shortcut {
+        trigger: Trigger::Character("s"),
+        label: "S",
+        action: Action::Screenshot,
+        description: "Save the view as a PNG",
+    },
+    Shortcut {
+        trigger: Trigger::Character("v"),
+        label: "V",
+        action: Action::SaveCamera,
+        description: "Save the camera to a file",
+    },
+    Shortcut {
+        trigger: Trigger::Character("V"),
+        label: "Shift+V",
+        action: Action::LoadCamera,
+        description: "Restore the camera from the file",
+    },
+    Shortcut {
+        trigger: Trigger::Character("g"),
+        label: "G",
+        action: Action::ToggleLegend,
+        description: "Show/hide the colormap legend",
+    },
+    Shortcut {
+        trigger: Trigger::Character("?"),
+        label: "?",
+        action: Action::ToggleHelp,
+        description: "Show/hide this help",
+    },
+];
+
+impl Action {
+    // The action bound to a key, if any.
+    pub fn from_key(key: &Key) -> Option<Action> {
+        SHORTCUTS
+            .iter()
+            .find(|shortcut| match (shortcut.trigger, key) {
+                (Trigger::Named(named), Key::Named(pressed)) => named == *pressed,
+                (Trigger::Character(c), Key::Character(pressed)) => c == pressed.as_str(),
+                _ => false,
+            })
+            .map(|shortcut| shortcut.action)
+    }
+}
@@ -0,0 +1,183 @@
+use crate::model::{PlainVertex, Polygon};
+use std::{
+    collections::HashMap,
+    io::{self, BufRead},
+};
+
+// STL files, as CAD tools and slicers export them, binary or ASCII.  STL
+// is triangle soup: every triangle lists its own three corners.  Corners
+// at exactly the same position are merged into one vertex, so triangles
+// share their vertices as in PLY, and the mesh shades smoothly across
+// them.  The facet normals STL stores are ignored; normals are computed
+// from the triangles, as for PLY meshes without them.
+//
+// Binary STL is an 80 byte header, a 32-bit little-endian triangle count,
+// and 50 bytes per triangle: the normal, three corners, each as three
+// 32-bit floats, and 2 bytes of attributes.
+const HEADER: usize = 80;
+const TRIANGLE: usize = 50;
+
+pub struct Stl {
+    pub vertices: Vec<PlainVertex>,
+    pub facets: Vec<Polygon>,
+}
+
+// Whether `bytes` have the length a binary STL would, for the triangle
+// count in them.  ASCII STL starts with "solid", but so do the headers of
+// some binary files, so the length tells them apart.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    match bytes.get(HEADER..HEADER + 4) {
+        Some(count) => bytes.len() == HEADER + 4 + TRIANGLE * u32::from_le_bytes(count.try_into().unwrap()) as usize,
+        None => false,
+    }
+}
+
+impl Stl {
+    pub fn read(f: &mut impl BufRead) -> io::Result<Stl> {
+        let mut bytes = vec![];
+        f.read_to_end(&mut bytes)?;
+        let corners = if is_binary(&bytes) {
+            read_binary(&bytes)
+        } else if bytes.starts_with(b"solid") {
+            read_ascii(&String::from_utf8_lossy(&bytes))?
+        } else {
+            return Err(invalid("neither binary STL of the right length, nor ASCII STL"));
+        };
+
+        let mut stl = Stl {
+            vertices: vec![],
+            facets: Vec::with_capacity(corners.len() / 3),
+        };
+        let mut indices = HashMap::new();
+        for triangle in corners.chunks_exact(3) {
+            let vertex_indices = triangle
+                .iter()
+                .map(|position| {
+                    // -0.0 and 0.0 are the same place.
+                    let key = position.map(|axis| (axis + 0.0).to_bits());
+                    *indices.entry(key).or_insert_with(|| {
+                        stl.vertices.push(PlainVertex::at(position.map(f64::from)));
+                        stl.vertices.len() as i32 - 1
+                    })
+                })
+                .collect();
+            stl.facets.push(Polygon { vertex_indices });
+        }
+        log::trace!("Merged {} STL corners into {} vertices", corners.len(), stl.vertices.len());
+        Ok(stl)
+    }
+}
+
+fn read_binary(bytes: &[u8]) -> Vec<[f32; 3]> {
+    let float = |bytes: &[u8]| f32::from_le_bytes(bytes.try_into().unwrap());
+    bytes[HEADER + 4..]
+        .chunks_exact(TRIANGLE)
+        // Past the normal, the three corners; then the attributes.
+        .flat_map(|triangle| triangle[12..48].chunks_exact(12))
+        .map(|corner| [float(&corner[0..4]), float(&corner[4..8]), float(&corner[8..12])])
+        .collect()
+}
+
+// Only the "vertex x y z" lines matter; the facet, outer loop and normal
+// lines around them are skipped.
+fn read_ascii(text: &str) -> io::Result<Vec<[f32; 3]>> {
+    let mut corners = vec![];
+    for (number, line) in text.lines().enumerate() {
+        let mut words = line.split_whitespace();
+        if words.next() != Some("vertex") {
+            continue;
+        }
+        let position: Vec<f32> = words
+            .take(3)
+            .map(|word| word.parse())
+            .collect::<Result<_, _>>()
+            .map_err(|err| invalid(&format!("line {}: {}", number + 1, err)))?;
+        let position = position
+            .try_into()
+            .map_err(|_| invalid(&format!("line {}: a vertex needs x, y and z", number + 1)))?;
+        corners.push(position);
+    }
+    if corners.len() % 3 != 0 {
+        return Err(invalid("a facet does not have three vertices"));
+    }
+    Ok(corners)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // Two triangles of a unit square, sharing the diagonal.
+    const SQUARE: [[[f32; 3]; 3]; 2] = [
+        [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]],
+        [[0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]],
+    ];
+
+    fn binary(header: &[u8]) -> Vec<u8> {
+        let mut bytes = header.to_vec();
+        bytes.resize(HEADER, 0);
+        bytes.extend((SQUARE.len() as u32).to_le_bytes());
+        for triangle in SQUARE {
+            bytes.extend([0.0f32, 0.0, 1.0].iter().flat_map(|v| v.to_le_bytes()));
+            bytes.extend(triangle.iter().flatten().flat_map(|v| v.to_le_bytes()));
+            bytes.extend([0, 0]);
+        }
+        bytes
+    }
+
+    const ASCII: &str = "solid square\r
+  facet normal 0 0 1\r
+    outer loop\r
+      vertex 0 0 0\r
+      vertex 1 0 0\r
+      vertex 1 1 0\r
+    endloop\r
+  endfacet\r
+  facet normal 0 0 1\r
+    outer loop\r
+      vertex 0 0 0\r
+      vertex 1 1 0\r
+      vertex 0 1 0\r
+    endloop\r
+  endfacet\r
+endsolid square\r
+";
+
+    fn read(bytes: &[u8]) -> io::Result<Stl> {
+        Stl::read(&mut Cursor::new(bytes))
+    }
+
+    #[test]
+    fn binary_square() {
+        let stl = read(&binary(b"exported by a CAD tool")).unwrap();
+        assert_eq!(stl.facets.len(), 2);
+        assert_eq!(stl.vertices.len(), 4);
+    }
+
+    // Some exporters start binary headers with "solid" too.
+    #[test]
+    fn binary_square_named_solid() {
+        let stl = read(&binary(b"solid square")).unwrap();
+        assert_eq!(stl.facets.len(), 2);
+        assert_eq!(stl.vertices.len(), 4);
+    }
+
+    #[test]
+    fn ascii_square() {
+        let stl = read(ASCII.as_bytes()).unwrap();
+        assert_eq!(stl.facets.len(), 2);
+        assert_eq!(stl.vertices.len(), 4);
+        assert_eq!(stl.facets[1].vertex_indices, [0, 2, 3]);
+    }
+
+    #[test]
+    fn ascii_short_facet() {
+        let text = ASCII.replace("      vertex 0 1 0\r\n", "");
+        assert!(read(text.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,110 @@
+use cgmath::{InnerSpace, Matrix4, Quaternion, SquareMatrix, Vector3};
+use serde::Deserialize;
+use std::{collections::HashMap, fs, path::Path};
+
+// Per-artifact placement in the world, for multi-sensor setups where
+// every artifact is recorded in its own frame.  The sidecar file is TOML,
+// with a table per artifact name:
+//
+//     [lidar]
+//     translation = [0.0, 0.0, 1.2]
+//     rotation = [0.0, 0.0, 0.7071, 0.7071]  # quaternion x, y, z, w
+//
+// Both keys are optional.  Artifacts that are not listed stay where they
+// are.
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pose {
+    #[serde(default)]
+    translation: [f32; 3],
+    #[serde(default = "identity_rotation")]
+    rotation: [f32; 4],
+}
+
+fn identity_rotation() -> [f32; 4] {
+    [0.0, 0.0, 0.0, 1.0]
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Transforms(HashMap<String, Matrix4<f32>>);
+
+impl Transforms {
+    pub fn from_path(path: &Path) -> Result<Transforms, String> {
+        let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let poses: HashMap<String, Pose> = toml::from_str(&text).map_err(|err| err.to_string())?;
+        Transforms::from_poses(poses)
+    }
+
+    // The same tables, already parsed, e.g. from a --config file.
+    pub fn from_poses(poses: HashMap<String, Pose>) -> Result<Transforms, String> {
+        let mut transforms = HashMap::new();
+        for (artifact, pose) in poses {
+            let [x, y, z, w] = pose.rotation;
+            let rotation = Quaternion::new(w, x, y, z);
+            if rotation.magnitude2() == 0.0 {
+                return Err(format!("{}: rotation is not a unit quaternion", artifact));
+            }
+            let matrix = Matrix4::from_translation(Vector3::from(pose.translation))
+                * Matrix4::from(rotation.normalize());
+            transforms.insert(artifact, matrix);
+        }
+        Ok(Transforms(transforms))
+    }
+
+    // The model matrix for an artifact name; identity if it is unlisted.
+    pub fn get(&self, artifact: &str) -> Matrix4<f32> {
+        match self.0.get(artifact) {
+            Some(matrix) => *matrix,
+            None => Matrix4::identity(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Bounds;
+    use cgmath::Point3;
+
+    fn transforms(toml: &str) -> Transforms {
+        Transforms::from_poses(toml::from_str(toml).unwrap()).unwrap()
+    }
+
+    // A translated artifact's bounds move with it, and an unlisted one's
+    // stay put.
+    #[test]
+    fn translation_shifts_bounds() {
+        let transforms = transforms("[lidar]\ntranslation = [1.0, -2.0, 0.5]\n");
+        let bounds = Bounds {
+            min: Point3::new(0.0, 0.0, 0.0),
+            max: Point3::new(1.0, 2.0, 3.0),
+        };
+        let moved = Bounds {
+            min: Point3::new(1.0, -2.0, 0.5),
+            max: Point3::new(2.0, 0.0, 3.5),
+        };
+        assert_eq!(bounds.transformed(transforms.get("lidar")), moved);
+        assert_eq!(bounds.transformed(transforms.get("camera")), bounds);
+    }
+
+    // Rotated a quarter turn about z, then translated, the box turns with
+    // the artifact: x spans what y did.
+    #[test]
+    fn rotation_turns_bounds() {
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        let transforms = transforms(&format!("[lidar]\ntranslation = [10.0, 0.0, 0.0]\nrotation = [0.0, 0.0, {half}, {half}]\n"));
+        let bounds = Bounds {
+            min: Point3::new(0.0, 0.0, 0.0),
+            max: Point3::new(1.0, 2.0, 3.0),
+        };
+        let turned = bounds.transformed(transforms.get("lidar"));
+        let expected = [[8.0, 0.0, 0.0], [10.0, 1.0, 3.0]];
+        for (point, expected) in [turned.min, turned.max].into_iter().zip(expected) {
+            let point: [f32; 3] = point.into();
+            for (got, want) in point.iter().zip(expected) {
+                assert!((got - want).abs() < 1e-5, "{:?}", turned);
+            }
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use crate::window::Gpu;
+use std::sync::MutexGuard;
+use wgpu::util::StagingBelt;
+
+// Artifact uploads go through a staging belt: the data is copied into
+// mapped staging chunks, and one command buffer per artifact copies them
+// into its buffers on the GPU, in order with the frames being drawn.
+// Unlike queue.write_buffer(), this does not stage a fresh copy of every
+// upload inside wgpu, and the chunks are reused once the GPU is done.
+//
+// The belt lives with the device and queue, behind its own lock.  Only
+// the sequencers upload, each holding the lock from the first write to
+// the submission, so uploads from different injectors do not interleave.
+// The window never touches the belt; it only submits its own frames to
+// the same queue, which may be shared between threads.
+pub const CHUNK_SIZE: wgpu::BufferAddress = 4 * 1024 * 1024;
+
+pub struct Upload<'gpu> {
+    gpu: &'gpu Gpu,
+    belt: MutexGuard<'gpu, StagingBelt>,
+    encoder: wgpu::CommandEncoder,
+}
+
+impl<'gpu> Upload<'gpu> {
+    pub fn begin(gpu: &'gpu Gpu) -> Upload<'gpu> {
+        let belt = gpu.belt.lock().unwrap();
+        let encoder = gpu.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("upload::encoder"),
+        });
+        Upload { gpu, belt, encoder }
+    }
+
+    // Fill `buffer` from the start with `data`.
+    pub fn write(&mut self, buffer: &wgpu::Buffer, data: &[u8]) {
+        if let Some(size) = wgpu::BufferSize::new(data.len() as u64) {
+            self.belt
+                .write_buffer(&mut self.encoder, buffer, 0, size, &self.gpu.device)
+                .copy_from_slice(data);
+        }
+    }
+
+    // Copy everything written on the GPU, then take back the chunks that
+    // are done, to write the next artifact from.
+    pub fn submit(mut self) {
+        self.belt.finish();
+        self.gpu.queue.submit([self.encoder.finish()]);
+        self.belt.recall();
+        self.gpu.device.poll(wgpu::Maintain::Poll);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // Any adapter will do, llvmpipe included.  Without one, as on some CI
+    // runners, there is nothing to test.
+    async fn gpu() -> Option<Gpu> {
+        let instance = wgpu::Instance::default();
+        for force_fallback_adapter in [false, true] {
+            let options = wgpu::RequestAdapterOptions {
+                force_fallback_adapter,
+                ..Default::default()
+            };
+            let Some(adapter) = instance.request_adapter(&options).await else {
+                continue;
+            };
+            if let Ok((device, queue)) = adapter.request_device(&Default::default(), None).await {
+                return Some(Gpu {
+                    device,
+                    queue,
+                    belt: Mutex::new(StagingBelt::new(CHUNK_SIZE)),
+                });
+            }
+        }
+        None
+    }
+
+    fn create_buffer(gpu: &Gpu, size: usize, usage: wgpu::BufferUsages) -> wgpu::Buffer {
+        gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: size as u64,
+            usage,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn read_back(gpu: &Gpu, buffer: &wgpu::Buffer) -> Vec<u8> {
+        let size = buffer.size() as usize;
+        let readback = create_buffer(gpu, size, wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ);
+        let mut encoder = gpu.device.create_command_encoder(&Default::default());
+        encoder.copy_buffer_to_buffer(buffer, 0, &readback, 0, size as u64);
+        gpu.queue.submit([encoder.finish()]);
+        readback.slice(..).map_async(wgpu::MapMode::Read, |mapped| mapped.unwrap());
+        gpu.device.poll(wgpu::Maintain::Wait);
+        let bytes = readback.slice(..).get_mapped_range().to_vec();
+        bytes
+    }
+
+    // Several uploads in a row, each reusing the chunks recalled from
+    // the last, one of them larger than a chunk.
+    #[tokio::test]
+    async fn uploads_arrive_in_order() {
+        let Some(gpu) = gpu().await else {
+            eprintln!("No GPU adapter, not even a fallback; skipped");
+            return;
+        };
+        let usage = wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC;
+        for (round, size) in [1024, CHUNK_SIZE as usize + 4096, 1024].into_iter().enumerate() {
+            let data: Vec<Vec<u8>> = (0..3)
+                .map(|i| (0..size).map(|byte| (byte * 7 + i * 13 + round) as u8).collect())
+                .collect();
+            let buffers: Vec<wgpu::Buffer> = data.iter().map(|_| create_buffer(&gpu, size, usage)).collect();
+
+            let mut upload = Upload::begin(&gpu);
+            for (buffer, data) in buffers.iter().zip(&data) {
+                upload.write(buffer, data);
+            }
+            upload.write(&buffers[0], &[]);
+            upload.submit();
+
+            for (buffer, data) in buffers.iter().zip(&data) {
+                assert!(read_back(&gpu, buffer) == *data, "round {}", round);
+            }
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use crate::{error::skip_failed, Key, Sequencer};
+use std::{io::Cursor, sync::Arc};
+use tokio::sync::watch;
+
+// Viewer is the handle given to in-process producers.  It feeds artifacts
+// through the same sequencer as the file injectors, so allocation, upload
+// and the window refresh all follow the same path; only the source of the
+// bytes differs.  It is cheap to clone and safe to share across threads.
+#[derive(Clone)]
+pub struct Viewer {
+    sequencer: Arc<dyn Sequencer + Send + Sync>,
+    exit: watch::Receiver<bool>,
+}
+
+impl Viewer {
+    pub fn new(sequencer: impl Sequencer + Send + Sync + 'static, exit: watch::Receiver<bool>) -> Self {
+        Self {
+            sequencer: Arc::new(sequencer),
+            exit,
+        }
+    }
+
+    // Inject a complete PLY file held in memory.  Returns false, and logs
+    // why, if it is not shown.
+    pub fn push_ply(&self, key: Key, bytes: &[u8]) -> bool {
+        match self.sequencer.add_reader(key, &mut Cursor::new(bytes)) {
+            Ok(_) => true,
+            Err(err) => {
+                log::warn!("{}", err);
+                false
+            }
+        }
+    }
+
+    // Inject a bare point cloud.
+    pub fn push_points(&self, key: Key, points: &[[f32; 3]]) -> bool {
+        let mut bytes = format!(
+            "ply\n\
+             format binary_little_endian 1.0\n\
+             element vertex {}\n\
+             property float x\n\
+             property float y\n\
+             property float z\n\
+             end_header\n",
+            points.len()
+        )
+        .into_bytes();
+        // Little endian as declared, whatever this machine is.
+        bytes.extend(points.iter().flatten().flat_map(|v| v.to_le_bytes()));
+        self.push_ply(key, &bytes)
+    }
+
+    pub fn remove(&self, key: Key) {
+        skip_failed(self.sequencer.remove_as(key));
+    }
+
+    // True once the window has closed.  Producers should return promptly
+    // after this, because the viewer waits for them before exiting.
+    pub fn exiting(&self) -> bool {
+        *self.exit.borrow()
+    }
+}
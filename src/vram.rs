@@ -0,0 +1,126 @@
+use crate::Key;
+use std::{
+    collections::VecDeque,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+// Accounting of the GPU buffers held by loaded artifacts, so that
+// accumulating many frames fails politely instead of deep inside wgpu.
+// Every sequencer keeps a Budget of its own artifacts, oldest first, and
+// evicts from the front to stay under --vram-limit.  The total across all
+// of them is kept here for the window to show.
+
+static TOTAL: AtomicU64 = AtomicU64::new(0);
+
+// Bytes allocated by all artifacts, in every pane.
+pub fn total() -> u64 {
+    TOTAL.load(Ordering::Relaxed)
+}
+
+pub fn mebibytes(bytes: u64) -> f32 {
+    bytes as f32 / (1024.0 * 1024.0)
+}
+
+pub struct Budget {
+    limit: Option<u64>,
+    // Bytes held per key, in the order they were allocated.
+    allocations: VecDeque<(Key, u64)>,
+    used: u64,
+}
+
+impl Budget {
+    pub fn new(limit: Option<u64>) -> Budget {
+        Budget {
+            limit,
+            allocations: VecDeque::new(),
+            used: 0,
+        }
+    }
+
+    // Account for `bytes` newly allocated under `key`, replacing whatever
+    // the key held before; it is now the newest.  Returns the oldest other
+    // keys to evict to get back under the limit, already accounted as
+    // freed.  Returns None, accounting for nothing, if the allocation
+    // alone exceeds the limit.
+    pub fn allocate(&mut self, key: &Key, bytes: u64) -> Option<Vec<Key>> {
+        if self.limit.is_some_and(|limit| bytes > limit) {
+            return None;
+        }
+        self.free(key);
+
+        let mut evicted = vec![];
+        if let Some(limit) = self.limit {
+            while self.used + bytes > limit {
+                let (old, _) = self.allocations.front().unwrap().clone();
+                self.free(&old);
+                evicted.push(old);
+            }
+        }
+
+        self.allocations.push_back((key.clone(), bytes));
+        self.used += bytes;
+        TOTAL.fetch_add(bytes, Ordering::Relaxed);
+        Some(evicted)
+    }
+
+    // Account for the buffers of `from` being kept under `to` instead,
+    // neither newer nor older than they were.
+    pub fn rename(&mut self, from: &Key, to: &Key) {
+        if let Some((key, _)) = self.allocations.iter_mut().find(|(key, _)| key == from) {
+            *key = to.clone();
+        }
+    }
+
+    // Account for the buffers of `key` being dropped.
+    pub fn free(&mut self, key: &Key) {
+        if let Some(index) = self.allocations.iter().position(|(k, _)| k == key) {
+            let (_, bytes) = self.allocations.remove(index).unwrap();
+            self.used -= bytes;
+            TOTAL.fetch_sub(bytes, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(artifact: &str) -> Key {
+        Key {
+            instance: None,
+            artifact: artifact.to_string(),
+        }
+    }
+
+    #[test]
+    fn evicts_least_recent_past_limit() {
+        let mut budget = Budget::new(Some(100));
+        assert_eq!(budget.allocate(&key("a"), 40), Some(vec![]));
+        assert_eq!(budget.allocate(&key("b"), 40), Some(vec![]));
+        assert_eq!(budget.allocate(&key("c"), 40), Some(vec![key("a")]));
+        assert_eq!(budget.used, 80);
+
+        // Loading b again makes it the newest, so c goes first.
+        assert_eq!(budget.allocate(&key("b"), 70), Some(vec![key("c")]));
+        assert_eq!(budget.allocate(&key("d"), 60), Some(vec![key("b")]));
+        assert_eq!(budget.used, 60);
+    }
+
+    #[test]
+    fn allocation_over_limit_is_refused() {
+        let mut budget = Budget::new(Some(100));
+        budget.allocate(&key("a"), 40);
+        assert_eq!(budget.allocate(&key("b"), 101), None);
+        assert_eq!(budget.used, 40);
+    }
+
+    #[test]
+    fn no_limit() {
+        let mut budget = Budget::new(None);
+        for name in ["a", "b", "c"] {
+            assert_eq!(budget.allocate(&key(name), 1 << 40), Some(vec![]));
+        }
+        budget.free(&key("b"));
+        assert_eq!(budget.used, 2 << 40);
+    }
+}
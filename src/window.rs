@@ -1,17 +1,39 @@
-use std::{collections::HashMap, sync::OnceLock};
-use wgpu::util::DeviceExt;
+use itertools::Itertools;
+use cgmath::{MetricSpace, Point3};
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+use tokio::sync::watch;
+use wgpu::util::{DeviceExt, StagingBelt};
 use winit::{
     application::ApplicationHandler,
     dpi,
     event::*,
     event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
-    keyboard::{Key, NamedKey},
     window::{Window, WindowAttributes, WindowId},
 };
 
 use crate::{
-    pipeline, Artifact, ArtifactsLock, Camera, CameraController, CameraUniform, InjectionEvent,
-    Projection, RenderArtifact,
+    capture::{self, Capture},
+    overlay::{Corner, Overlay},
+    palette,
+    pipeline::point_cloud::{PointUniform, MAX_POINT_SIZE, MIN_POINT_SIZE},
+    playback,
+    pool,
+    profile::Profiler,
+    progress,
+    upload,
+    reference::{self, Reference},
+    vram,
+    history::History,
+    model::{Bounds, Frustum},
+    shortcut::{Action, SHORTCUTS},
+    pipeline, Artifact, ArtifactKind, ArtifactUniform, ArtifactsLock, Camera, EdgeColor, CameraController, CameraUniform, InjectionEvent, Projection, ProjectionKind,
+    Colormap, ColormapAxis, Fog, FogUniform, Key, Orbit, RenderArtifact, RenderMode, SavedView, Settings, Texture, View, WorldviewConfig,
 };
 
 // The dependency injection thread needs to load GPU buffers, and for that
@@ -20,77 +42,332 @@ use crate::{
 // wgpu surface until the window is created, sharing these references
 // is a PITA.  They are not Serialize either, so we cannot even use an
 // async channel to message between threads.  So, let's use global
-// variables so any thread can get these critical objects.
-pub static DEVICE: OnceLock<wgpu::Device> = OnceLock::new();
-pub static QUEUE: OnceLock<wgpu::Queue> = OnceLock::new();
+// variable so any thread can get these critical objects.
+//
+// The handle is taken out again on shutdown, once every other thread is
+// done with it, so the device is dropped at a well defined point.
+pub struct Gpu {
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    // Artifact uploads; see upload.rs.
+    pub belt: Mutex<StagingBelt>,
+}
+
+impl Gpu {
+    fn new(device: wgpu::Device, queue: wgpu::Queue) -> Gpu {
+        Gpu {
+            device,
+            queue,
+            belt: Mutex::new(StagingBelt::new(upload::CHUNK_SIZE)),
+        }
+    }
+}
+
+static GPU: RwLock<Option<Arc<Gpu>>> = RwLock::new(None);
+
+// The GPU, if the window is up and the viewer is not shutting down.
+pub fn gpu() -> Option<Arc<Gpu>> {
+    GPU.read().unwrap().clone()
+}
+
+// Where frames are drawn: the window's surface, or without a window
+// (--headless) a texture of the same size the window would have.
+enum Target<'win> {
+    Window {
+        window: &'win Window,
+        surface: wgpu::Surface<'win>,
+        // Whether the surface can be copied out of, for captures.
+        readable: bool,
+    },
+    Offscreen {
+        texture: Arc<wgpu::Texture>,
+    },
+}
+
+impl<'win> Target<'win> {
+    fn size(&self) -> dpi::PhysicalSize<u32> {
+        match self {
+            Target::Window { window, .. } => window.inner_size(),
+            Target::Offscreen { texture } => dpi::PhysicalSize::new(texture.width(), texture.height()),
+        }
+    }
+
+    fn surface(&self) -> Option<&wgpu::Surface<'win>> {
+        match self {
+            Target::Window { surface, .. } => Some(surface),
+            Target::Offscreen { .. } => None,
+        }
+    }
+}
 
 enum ControlState {
     Inactive,
     DragAngle,
+    Pan,
 }
 
-pub struct WindowState<'win> {
-    surface: wgpu::Surface<'win>,
-    window: &'win Window,
+// A viewport showing one set of artifacts through its own camera.  The
+// window is divided evenly between panes, side by side; normally there
+// is just one.
+struct Pane {
     artifacts: ArtifactsLock,
-    pub surface_capabilities: wgpu::SurfaceCapabilities,
+    camera: Camera,
+    camera_buffer: wgpu::Buffer,
+    camera_uniform: CameraUniform,
+    world_bind_group: wgpu::BindGroup,
+    // Recently injected files, to step back through (--history).
+    history: Option<History>,
+}
+
+pub struct WindowState<'win> {
+    gpu: Arc<Gpu>,
+    target: Target<'win>,
+    panes: Vec<Pane>,
+    // The pane under the mouse, which the camera controls act on.
+    active: usize,
+    // Whether all panes follow the active pane's camera.
+    linked: bool,
+    // The format of the window's frames, which every pipeline draws in.
+    pub surface_format: wgpu::TextureFormat,
     pub point_cloud_pipeline_layout: wgpu::PipelineLayout,
     pub wireframe_pipeline_layout: wgpu::PipelineLayout,
     pub mesh_pipeline_layout: wgpu::PipelineLayout,
+    pub textured_pipeline_layout: wgpu::PipelineLayout,
     artifact_bind_group_layout: wgpu::BindGroupLayout,
-    pub world_bind_group: wgpu::BindGroup,
-    pipeline: HashMap<String, wgpu::RenderPipeline>,
-    artifact_bind_group: HashMap<String, wgpu::BindGroup>,
-    artifact_uniform_buffer: HashMap<String, wgpu::Buffer>,
-    camera: Camera,
-    camera_buffer: wgpu::Buffer,
-    camera_uniform: CameraUniform,
+    pub edge_color: EdgeColor,
+    // How far fills are pushed back behind the lines on them.
+    pub depth_bias: i32,
+    // Pipelines depend only on how they draw, not on what, so artifacts
+    // drawn alike share one.  Every artifact type draws a given render
+    // mode from the same vertex layout.
+    pipeline: HashMap<(RenderMode, wgpu::TextureFormat), wgpu::RenderPipeline>,
+    render_mode: HashMap<String, RenderMode>,
+    // Point clouds are colored by height from the start with --colormap,
+    // and with viridis when picked with the M key otherwise.
+    colormap: Option<Colormap>,
+    colormap_axis: ColormapAxis,
+    // Whether filled meshes have their edges drawn over them (--edges).
+    edges: bool,
+    // The ground grid and coordinate axes, drawn if on.
+    reference: Reference,
+    grid: bool,
+    axes: bool,
+    // Made again whenever the frame size changes; see depth_view().
+    depth: Option<wgpu::Texture>,
+    // Artifact names drawn first (--layer-order).
+    layer_order: Vec<String>,
+    // Opacity by artifact name (--alpha); the last given wins.
+    alpha: HashMap<String, f32>,
+    // Colors by artifact name (--color); the last given wins.
+    colors: HashMap<String, [f32; 4]>,
+    // Unconfigured colors come from the palette, by artifact name.
+    palette: bool,
+    focus: Option<String>,
+    // Uniforms of each instance, whose colors may come from its own
+    // file.  The type is part of the key because a pane renames an
+    // artifact that reuses a name of another type, but panes do not know
+    // each other's names.
+    artifact_bind_group: HashMap<(Key, ArtifactKind), wgpu::BindGroup>,
+    artifact_uniform_buffer: HashMap<(Key, ArtifactKind), wgpu::Buffer>,
+    // The --texture image, shared by all textured meshes.
+    texture_bind_group: Option<wgpu::BindGroup>,
     camera_controller: CameraController,
     projection: Projection,
     control_state: ControlState,
+    // Redraw every frame instead of only on events.
+    continuous: bool,
+    overlay: Overlay,
+    settings: Settings,
+    fog: Option<Fog>,
+    // Fog parameters, bound in every pane's world bind group.
+    fog_buffer: wgpu::Buffer,
+    // Points are squares this many pixels across.  Bound in the world
+    // bind groups too.
+    point_size: f32,
+    point_buffer: wgpu::Buffer,
+    legend: Corner,
+    show_legend: bool,
+    show_help: bool,
+    // Whether a large file was loading as of the last frame.
+    loading: bool,
+    profiler: Option<Profiler>,
+    // Minimized or fully covered; nothing drawn would be seen.
+    minimized: bool,
+    occluded: bool,
+    // Injection pauses while the window is hidden, if configured to, and
+    // while any pane shows an old frame from its history.
+    paused: watch::Sender<bool>,
+    pause_hidden: bool,
+    // Asks the injectors to load their whole directory again.
+    rescan: watch::Sender<()>,
+    // Pauses, steps and restarts playback.
+    control: watch::Sender<playback::Control>,
+    // Shown with the GPU memory in use, when there is one.
+    vram_limit: Option<u64>,
+    // Scale factor forced by --scale, instead of the monitor's.
+    scale: Option<f32>,
+    // A turntable capture in progress, and the next frame number.
+    orbit: Option<(Orbit, u32)>,
+    capture: Capture,
+    // A screenshot asked for with the S key, taken on the next frame.
+    screenshot: bool,
+    screenshot_dir: PathBuf,
+    // Where the V key saves the view, and Shift+V restores it from.
+    camera_file: PathBuf,
 }
 
-impl<'win> WindowState<'win> {
-    pub async fn new(window: &'win Window, artifacts: ArtifactsLock) -> WindowState<'win> {
-        let size = window.inner_size();
-        let instance = wgpu::Instance::default();
-        let surface = instance.create_surface(window).unwrap();
+// Draw the artifact, then if given the edges pipeline, its edges over it.
+fn render_with_edges<'rpass>(
+    artifact: &'rpass Artifact,
+    mode: RenderMode,
+    edges: Option<&'rpass wgpu::RenderPipeline>,
+    render_pass: &mut wgpu::RenderPass<'rpass>,
+) {
+    artifact.render(mode, render_pass);
+    if let Some(pipeline) = edges {
+        render_pass.set_pipeline(pipeline);
+        artifact.render(RenderMode::Edges, render_pass);
+    }
+}
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .unwrap();
+impl<'win> WindowState<'win> {
+    pub async fn new(
+        window: &'win Window,
+        panes: Vec<(ArtifactsLock, Option<History>)>,
+        config: &WorldviewConfig,
+        paused: watch::Sender<bool>,
+        rescan: watch::Sender<()>,
+        control: watch::Sender<playback::Control>,
+    ) -> Result<WindowState<'win>, String> {
+        let (instance, backends) = instance(&config.backends);
+        let surface = instance
+            .create_surface(window)
+            .map_err(|err| format!("Cannot create a surface for the window: {}", err))?;
 
+        let (adapter, device, queue) = request_device(&instance, backends, Some(&surface), config.profile).await?;
         let surface_capabilities = surface.get_capabilities(&adapter);
+        let readable = surface_capabilities.usages.contains(wgpu::TextureUsages::COPY_SRC);
+        if config.orbit.is_some() && !readable {
+            return Err("This GPU cannot read back the window, so --orbit cannot capture frames".to_string());
+        }
+        // Colors are given in linear space, so prefer a format that
+        // converts them to sRGB on output; not every platform lists one
+        // first.
+        let surface_format = surface_capabilities
+            .formats
+            .iter()
+            .copied()
+            .find(|format| format.is_srgb())
+            .unwrap_or(surface_capabilities.formats[0]);
+        log::info!("Surface format {:?}", surface_format);
 
-        let (device, queue) = adapter
-            .request_device(&Default::default(), None)
-            .await
-            .unwrap();
+        let gpu = Arc::new(Gpu::new(device, queue));
+        *GPU.write().unwrap() = Some(gpu.clone());
+        let target = Target::Window {
+            window,
+            surface,
+            readable,
+        };
+        Ok(WindowState::build(target, gpu, surface_format, panes, config, paused, rescan, control))
+    }
 
-        let camera = Camera::default();
-        let projection = Projection::default(size);
-        let camera_controller = CameraController::new();
+    // A renderer without a window (--headless), drawing into a texture the
+    // size the window would open at.  The GPU must be up already; see
+    // start_offscreen_gpu().
+    pub fn offscreen(
+        panes: Vec<(ArtifactsLock, Option<History>)>,
+        config: &WorldviewConfig,
+        paused: watch::Sender<bool>,
+    ) -> Result<WindowState<'static>, String> {
+        let gpu = gpu().ok_or("The GPU is not up")?;
+        let size = requested_size(config).unwrap_or(DEFAULT_SIZE.into());
+        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        let texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("offscreen"),
+            size: wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let target = Target::Offscreen {
+            texture: Arc::new(texture),
+        };
+        // Nothing can ask for a rescan, or steer playback, without a
+        // window.
+        let (rescan, _) = watch::channel(());
+        let (control, _) = watch::channel(playback::Control::Restart);
+        Ok(WindowState::build(target, gpu, format, panes, config, paused, rescan, control))
+    }
 
-        let mut camera_uniform = CameraUniform::new();
-        camera_uniform.update_view_proj(&camera, &projection);
+    // Everything but the target is set up the same, with or without a
+    // window.
+    #[allow(clippy::too_many_arguments)]
+    fn build(
+        target: Target<'win>,
+        gpu: Arc<Gpu>,
+        surface_format: wgpu::TextureFormat,
+        panes: Vec<(ArtifactsLock, Option<History>)>,
+        config: &WorldviewConfig,
+        paused: watch::Sender<bool>,
+        rescan: watch::Sender<()>,
+        control: watch::Sender<playback::Control>,
+    ) -> WindowState<'win> {
+        let (device, queue) = (&gpu.device, &gpu.queue);
+        // The window may not have its requested size yet; the projection
+        // starts out at that size, not the OS's first guess, and follows
+        // any resize.
+        let size = requested_size(config).unwrap_or_else(|| target.size());
+        let profile = device.features().contains(Profiler::FEATURES);
+        if config.profile && !profile {
+            log::warn!("GPU timestamp queries are not supported; profiling is off");
+        }
+        let profiler = profile.then(|| Profiler::new(device, queue));
 
-        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Camera Uniform Buffer"),
-            contents: bytemuck::cast_slice(&[camera_uniform]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
+        let mut projection = Projection::new(size, config.fov, config.near, config.far);
+        projection.resize(size.width / panes.len() as u32, size.height);
+        let camera_controller = CameraController::new(
+            config.sensitivity,
+            config.invert_x,
+            config.invert_y,
+            config.smooth.unwrap_or(0.0),
+        );
 
         let world_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries: &[
-                    // CameraUniform
+                    // CameraUniform; shaded meshes light fragments from
+                    // the camera position.
                     wgpu::BindGroupLayoutEntry {
                         binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // FogUniform
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // PointUniform
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
                         visibility: wgpu::ShaderStages::VERTEX,
                         ty: wgpu::BindingType::Buffer {
                             ty: wgpu::BufferBindingType::Uniform,
@@ -103,22 +380,73 @@ impl<'win> WindowState<'win> {
                 label: Some("uniform_bind_group_layout"),
             });
 
-        let world_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &world_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: camera_buffer.as_entire_binding(),
-            }],
-            label: Some("world_bind_group"),
+        let fog_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Fog Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[FogUniform::new(config.fog.as_ref(), config.settings.background())]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let point_size = config.point_size.clamp(MIN_POINT_SIZE, MAX_POINT_SIZE);
+        let point_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Point Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[PointUniform::new(
+                point_size * config.scale.unwrap_or(1.0),
+                size.width / panes.len() as u32,
+                size.height,
+            )]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
+        // Every pane has its own camera, bound as its world bind group.
+        let panes = panes
+            .into_iter()
+            .map(|(artifacts, history)| {
+                let camera = Camera::new(config.up);
+                let mut camera_uniform = CameraUniform::new();
+                camera_uniform.update_view_proj(&camera, &projection);
+
+                let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Camera Uniform Buffer"),
+                    contents: bytemuck::cast_slice(&[camera_uniform]),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                });
+
+                let world_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &world_bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: camera_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: fog_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: point_buffer.as_entire_binding(),
+                        },
+                    ],
+                    label: Some("world_bind_group"),
+                });
+
+                Pane {
+                    artifacts,
+                    camera,
+                    camera_buffer,
+                    camera_uniform,
+                    world_bind_group,
+                    history,
+                }
+            })
+            .collect();
+
         let artifact_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries: &[
                     // ArtifactUniform
                     wgpu::BindGroupLayoutEntry {
                         binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
                         ty: wgpu::BindingType::Buffer {
                             ty: wgpu::BufferBindingType::Uniform,
                             has_dynamic_offset: false,
@@ -131,104 +459,304 @@ impl<'win> WindowState<'win> {
             });
 
         let point_cloud_pipeline_layout = pipeline::PointCloud::create_pipeline_layout(
-            &device,
+            device,
             &world_bind_group_layout,
             &artifact_bind_group_layout,
         );
 
         let wireframe_pipeline_layout = pipeline::Wireframe::create_pipeline_layout(
-            &device,
+            device,
             &world_bind_group_layout,
             &artifact_bind_group_layout,
         );
 
         let mesh_pipeline_layout = pipeline::Mesh::create_pipeline_layout(
-            &device,
+            device,
             &world_bind_group_layout,
             &artifact_bind_group_layout,
         );
 
-        DEVICE.set(device).unwrap();
-        QUEUE.set(queue).unwrap();
+        let texture_bind_group_layout = Texture::create_bind_group_layout(device);
+        let textured_pipeline_layout = pipeline::Mesh::create_textured_pipeline_layout(
+            device,
+            &world_bind_group_layout,
+            &artifact_bind_group_layout,
+            &texture_bind_group_layout,
+        );
+        let texture_bind_group = config
+            .texture
+            .as_ref()
+            .map(|texture| texture.create_bind_group(device, queue, &texture_bind_group_layout));
 
-        WindowState {
-            surface,
-            window,
-            artifacts,
-            surface_capabilities,
+        let overlay = Overlay::new(device, surface_format);
+        let reference = Reference::new(
+            device,
+            &mesh_pipeline_layout,
+            &artifact_bind_group_layout,
+            surface_format,
+            config.up,
+            config.grid.unwrap_or(reference::DEFAULT_GRID_SPACING),
+            config.grid_extent,
+        );
+
+        let mut app = WindowState {
+            gpu: gpu.clone(),
+            target,
+            panes,
+            active: 0,
+            linked: true,
+            surface_format,
             point_cloud_pipeline_layout,
             wireframe_pipeline_layout,
             mesh_pipeline_layout,
+            textured_pipeline_layout,
             artifact_bind_group_layout,
-            world_bind_group,
+            edge_color: config.edge_color,
+            depth_bias: config.depth_bias,
             pipeline: HashMap::new(),
+            render_mode: HashMap::new(),
+            colormap: config.colormap,
+            colormap_axis: config.colormap_axis,
+            edges: config.edges,
+            reference,
+            grid: config.grid.is_some(),
+            axes: config.axes,
+            depth: None,
+            layer_order: config.layer_order.clone(),
+            alpha: config.alphas.iter().map(|a| (a.artifact.clone(), a.alpha)).collect(),
+            colors: config.colors.iter().map(|c| (c.artifact.clone(), c.color)).collect(),
+            palette: config.palette,
+            focus: None,
             artifact_bind_group: HashMap::new(),
             artifact_uniform_buffer: HashMap::new(),
-            camera,
-            camera_buffer,
-            camera_uniform,
+            texture_bind_group,
             camera_controller,
             projection,
             control_state: ControlState::Inactive,
+            continuous: config.continuous || config.orbit.is_some(),
+            overlay,
+            settings: config.settings.clone(),
+            fog: config.fog,
+            fog_buffer,
+            point_size,
+            point_buffer,
+            legend: config.legend.unwrap_or_default(),
+            show_legend: config.legend.is_some(),
+            show_help: false,
+            loading: false,
+            profiler,
+            minimized: false,
+            occluded: false,
+            paused,
+            pause_hidden: config.pause_hidden,
+            rescan,
+            control,
+            vram_limit: config.vram_limit,
+            scale: config.scale,
+            orbit: config.orbit.clone().map(|orbit| (orbit, 0)),
+            capture: Capture::new(),
+            screenshot: false,
+            screenshot_dir: config.screenshot_dir.clone(),
+            camera_file: config.camera.clone().unwrap_or_else(|| PathBuf::from(DEFAULT_CAMERA_FILE)),
+        };
+        if config.camera.is_some() {
+            app.load_camera();
         }
+        app
     }
 
-    fn resize(&self, size: dpi::PhysicalSize<u32>) {
-        let format = self.surface_capabilities.formats[0];
-        let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format,
-            width: size.width,
-            height: size.height,
-            present_mode: wgpu::PresentMode::Fifo,
-            alpha_mode: wgpu::CompositeAlphaMode::Auto,
-            view_formats: vec![format],
-            desired_maximum_frame_latency: 2,
-        };
+    fn resize(&mut self, size: dpi::PhysicalSize<u32>) {
+        // A minimized window has no area, and a surface cannot either.
+        self.minimized = size.width == 0 || size.height == 0;
+        self.visibility_changed();
+        if self.minimized {
+            return;
+        }
+
+        if let Target::Window { surface, readable, .. } = &self.target {
+            let format = self.surface_format;
+            let config = wgpu::SurfaceConfiguration {
+                // Captured frames and screenshots are copied out of the
+                // surface.
+                usage: match readable {
+                    true => wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                    false => wgpu::TextureUsages::RENDER_ATTACHMENT,
+                },
+                format,
+                width: size.width,
+                height: size.height,
+                present_mode: wgpu::PresentMode::Fifo,
+                alpha_mode: wgpu::CompositeAlphaMode::Auto,
+                view_formats: vec![format],
+                desired_maximum_frame_latency: 2,
+            };
+            surface.configure(&self.gpu.device, &config);
+        }
+        self.projection
+            .resize(size.width / self.panes.len() as u32, size.height);
+        self.update_cameras();
+        self.update_points();
+        // Not every platform redraws after a resize by itself, and until
+        // it does, the old frame is shown stretched to the new shape.
+        self.request_redraw();
+    }
+
+    // Offscreen, frames are drawn only when asked for; see
+    // render_offscreen().
+    fn request_redraw(&self) {
+        if let Target::Window { window, .. } = &self.target {
+            window.request_redraw();
+        }
+    }
+
+    fn size(&self) -> dpi::PhysicalSize<u32> {
+        self.target.size()
+    }
 
-        let device = DEVICE.get().unwrap();
-        self.surface.configure(&device, &config);
+    fn readable(&self) -> bool {
+        match &self.target {
+            Target::Window { readable, .. } => *readable,
+            Target::Offscreen { .. } => true,
+        }
     }
 
-    fn redraw(&mut self) {
-        self.camera_controller.update_camera(&mut self.camera);
-        self.camera_uniform
-            .update_view_proj(&self.camera, &self.projection);
-        let surface = &self.surface;
-        let output = match surface.get_current_texture() {
+    // Apply pending camera controls to the active pane, and when linked,
+    // make every other pane follow it.
+    fn update_cameras(&mut self) {
+        let active = &mut self.panes[self.active];
+        self.camera_controller.update_camera(&mut active.camera, &mut self.projection);
+
+        if self.linked {
+            let camera = active.camera.clone();
+            for pane in self.panes.iter_mut() {
+                pane.camera = camera.clone();
+            }
+        }
+
+        for pane in self.panes.iter_mut() {
+            pane.camera_uniform
+                .update_view_proj(&pane.camera, &self.projection);
+        }
+    }
+
+    // The next frame to draw into.  A surface is lost or outdated after
+    // sleep and resume, a GPU reset, or a display change; configuring it
+    // again, as for a resize to the current size, usually brings it back.
+    // Offscreen there is no surface at all.
+    fn current_texture(&mut self) -> Result<wgpu::SurfaceTexture, wgpu::SurfaceError> {
+        match self.target.surface().ok_or(wgpu::SurfaceError::Lost)?.get_current_texture() {
+            Err(e @ (wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated)) => {
+                log::warn!("Surface {:?}; reconfiguring", e);
+                self.resize(self.size());
+                self.target.surface().ok_or(wgpu::SurfaceError::Lost)?.get_current_texture()
+            }
+            result => result,
+        }
+    }
+
+    fn redraw(&mut self, event_loop: &ActiveEventLoop) {
+        self.update_cameras();
+        let output = match self.current_texture() {
             Ok(surface) => surface,
+            // Nothing more can be drawn; give up cleanly.
+            Err(wgpu::SurfaceError::OutOfMemory) => {
+                log::error!("Out of GPU memory for the surface, exiting");
+                event_loop.exit();
+                return;
+            }
+            // Possibly transient; the next redraw tries again.
             Err(e) => {
                 log::error!("surface {:?}", e);
                 return;
             }
         };
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
 
-        let device = match DEVICE.get() {
-            Some(device) => device,
-            None => {
-                log::debug!("Waiting for WGPU initialization");
-                return;
+        // A turntable starts once there is something to see.
+        let orbiting = self.orbit.is_some()
+            && self.panes.iter().any(|pane| !pane.artifacts.lock().unwrap().is_empty())
+            && progress::fraction().is_none();
+        let screenshot = std::mem::take(&mut self.screenshot);
+        self.draw(&output.texture, orbiting || screenshot);
+        output.present();
+
+        let gpu = self.gpu.clone();
+        let device = &gpu.device;
+        if screenshot {
+            let path = capture::screenshot_path(&self.screenshot_dir);
+            match self.capture.save(device, &path) {
+                Ok(()) => log::info!("Saved {}", path.display()),
+                Err(err) => log::error!("{}: {}", path.display(), err),
             }
+        }
+        if orbiting {
+            self.captured(device, event_loop);
+        }
+    }
+
+    // Draw a frame without a window (--headless) and save it as a PNG.
+    pub fn render_offscreen(&mut self, path: &Path) -> Result<(), String> {
+        let texture = match &self.target {
+            Target::Offscreen { texture } => texture.clone(),
+            Target::Window { .. } => return Err("not drawing offscreen".to_string()),
         };
+        self.update_cameras();
+        self.draw(&texture, true);
+        self.capture.save(&self.gpu.device, path)
+    }
+
+    // Draw every pane into `texture`, and the overlay over them all, and
+    // submit the frame.  If `capture`, it is also copied out, to be saved
+    // once the GPU is done.
+    fn draw(&mut self, texture: &wgpu::Texture, capture: bool) {
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let gpu = self.gpu.clone();
+        let device = &gpu.device;
+        if let Some(profiler) = &mut self.profiler {
+            profiler.begin(device);
+        }
 
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Encoder"),
         });
+        let depth = self.depth_view(device, texture.size());
 
         // Lock the artifacts and the queue as late as possible, to 
         // minimize contention with the dependency injector that is 
         // concurrently writing buffers.
-        let artifacts = self.artifacts.lock().unwrap();
+        let artifacts: Vec<_> = self
+            .panes
+            .iter()
+            .map(|pane| pane.artifacts.lock().unwrap())
+            .collect();
 
         // Initialize GPU resources for any new artifacts that have arrived.
-        for (key, artifact) in artifacts.iter() {
-            let key = &key.artifact;
-            if !self.pipeline.contains_key(key) {
-                let pipeline = artifact.create_pipeline(&device, &self);
-                let buffer = artifact.create_uniform_buffer(&device);
+        // Pipelines are built lazily per render mode, so switching modes
+        // only pays for a pipeline the first time it is used.
+        let mut loaded = HashSet::new();
+        for (instance, artifact) in artifacts.iter().flat_map(|artifacts| artifacts.iter()) {
+            let key = &instance.artifact;
+            let mode = self.draw_mode(key, artifact);
+            let edges = (self.edges && mode.is_filled()).then_some(RenderMode::Edges);
+            for mode in [Some(mode), edges].into_iter().flatten() {
+                if !self.pipeline.contains_key(&(mode, self.surface_format)) {
+                    let pipeline = mode.create_pipeline(device, self);
+                    log::debug!("Built the {:?} pipeline", mode);
+                    self.pipeline.insert((mode, self.surface_format), pipeline);
+                }
+            }
+
+            let uniform = (instance.clone(), artifact.kind());
+            loaded.insert(uniform.clone());
+            if !self.artifact_bind_group.contains_key(&uniform) {
+                let mut color = self.color(key);
+                if let Some(alpha) = self.alpha.get(key) {
+                    let mut with_alpha = color.unwrap_or(artifact.color());
+                    with_alpha[3] = *alpha;
+                    color = Some(with_alpha);
+                }
+                let transform = self.settings.transforms.get(key).into();
+                let buffer = artifact.create_uniform_buffer(device, color, transform);
                 let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
                     layout: &self.artifact_bind_group_layout,
                     entries: &[wgpu::BindGroupEntry {
@@ -238,12 +766,111 @@ impl<'win> WindowState<'win> {
                     label: Some("artifact_bind_group"),
                 });
 
-                self.pipeline.insert(key.clone(), pipeline);
-                self.artifact_bind_group.insert(key.clone(), bind_group);
-                self.artifact_uniform_buffer.insert(key.clone(), buffer);
+                self.artifact_bind_group.insert(uniform.clone(), bind_group);
+                self.artifact_uniform_buffer.insert(uniform, buffer);
+            }
+        }
+        // Instances replaced or removed since the last redraw leave their
+        // uniforms behind.
+        self.artifact_bind_group.retain(|uniform, _| loaded.contains(uniform));
+        self.artifact_uniform_buffer.retain(|uniform, _| loaded.contains(uniform));
+
+        // Points colored by height span the bounds of every instance of
+        // their artifact, so the colors hold still as frames come and go.
+        let mut heights: HashMap<(String, ArtifactKind), Bounds> = HashMap::new();
+        for (key, artifact) in artifacts.iter().flat_map(|artifacts| artifacts.iter()) {
+            if self.render_mode(&key.artifact, artifact) != RenderMode::Height {
+                continue;
+            }
+            if let Some(bounds) = artifact.bounds() {
+                heights
+                    .entry((key.artifact.clone(), artifact.kind()))
+                    .and_modify(|union| *union = union.union(bounds))
+                    .or_insert(bounds);
+            }
+        }
+        let (axis, colormap) = (self.colormap_axis, self.colormap.unwrap_or_default());
+        for (key, artifact) in artifacts.iter().flat_map(|artifacts| artifacts.iter()) {
+            let Some(bounds) = heights.get(&(key.artifact.clone(), artifact.kind())) else {
+                continue;
+            };
+            gpu.queue.write_buffer(
+                &self.artifact_uniform_buffer[&(key.clone(), artifact.kind())],
+                ArtifactUniform::HEIGHT_OFFSET,
+                bytemuck::cast_slice(&ArtifactUniform::height(bounds, axis, colormap)),
+            );
+        }
+
+        // The legend explains the colormap of the focused artifact, or else
+        // of the first colormapped one by name.
+        let colormap = artifacts
+            .iter()
+            .flat_map(|artifacts| artifacts.iter())
+            .filter_map(|(key, artifact)| {
+                let colormap = match heights.get(&(key.artifact.clone(), artifact.kind())) {
+                    Some(bounds) => {
+                        let index = axis.index();
+                        Some((axis.label(), bounds.min[index], bounds.max[index], colormap))
+                    }
+                    None => artifact.colormap(),
+                };
+                Some((key, colormap?))
+            })
+            .min_by_key(|(key, _)| {
+                (Some(&key.artifact) != self.focus.as_ref(), key.artifact.clone(), key.instance.clone())
+            })
+            .map(|(_, colormap)| colormap);
+
+        // Viewports must fit the surface texture, which may briefly lag
+        // behind the window size while resizing.
+        let size = texture.size();
+        // The overlay is laid out in pixels at the --scale, if forced.
+        let scale = self.scale.unwrap_or(1.0);
+        let (width, height) = (size.width as f32 / scale, size.height as f32 / scale);
+        self.overlay.clear();
+        if let (true, Some((title, min, max, colormap))) = (self.show_legend, colormap) {
+            self.overlay
+                .legend(self.legend, title, min, max, colormap, width, height);
+        }
+        if let Some(fraction) = progress::fraction() {
+            self.overlay.progress(fraction, width, height);
+        }
+        let mut lines = vec![];
+        if let Some(limit) = self.vram_limit {
+            lines.push(format!(
+                "VRAM {:.0}/{:.0} MiB",
+                vram::mebibytes(vram::total()),
+                vram::mebibytes(limit)
+            ));
+        }
+        for (index, pane) in self.panes.iter().enumerate() {
+            if let Some((back, key)) = pane.history.as_ref().and_then(|history| history.position()) {
+                let pane = match self.panes.len() {
+                    1 => String::new(),
+                    _ => format!(" (pane {})", index + 1),
+                };
+                lines.push(format!("History -{} {}{}; End for live", back, key, pane));
             }
         }
+        if let Some(profiler) = &self.profiler {
+            lines.extend(
+                profiler
+                    .readout
+                    .iter()
+                    .map(|(label, milliseconds)| format!("{} {:.2}ms", label, milliseconds)),
+            );
+        }
+        self.overlay.readout(&lines, height);
+        if self.show_help {
+            let rows: Vec<_> = SHORTCUTS
+                .iter()
+                .map(|shortcut| (shortcut.label, shortcut.description))
+                .collect();
+            self.overlay.help(&rows, width, height);
+        }
+        self.overlay.prepare(device, &gpu.queue, width, height);
 
+        let background = self.settings.background();
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
@@ -252,81 +879,614 @@ impl<'win> WindowState<'win> {
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.9,
-                            g: 0.9,
-                            b: 0.9,
+                            r: background[0] as f64,
+                            g: background[1] as f64,
+                            b: background[2] as f64,
                             a: 1.0,
                         }),
                         store: wgpu::StoreOp::Store,
                     },
                 })],
-                depth_stencil_attachment: None,
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
                 ..Default::default()
             });
 
-            // Upload global constants common to all the artifacts; these
-            // include camera position and projection.
-            render_pass.set_bind_group(0, &self.world_bind_group, &[]);
+            let width = size.width as f32 / self.panes.len() as f32;
+            for (index, (pane, artifacts)) in self.panes.iter().zip(artifacts.iter()).enumerate() {
+                render_pass.set_viewport(
+                    index as f32 * width,
+                    0.0,
+                    width,
+                    size.height as f32,
+                    0.0,
+                    1.0,
+                );
 
-            for (key, artifact) in artifacts.iter() {
-                let key = &key.artifact;
-                render_pass.set_pipeline(self.pipeline.get(key).unwrap());
+                // Upload global constants common to all the artifacts;
+                // these include camera position and projection.
+                render_pass.set_bind_group(0, &pane.world_bind_group, &[]);
+                self.reference.render(self.grid, self.axes, &mut render_pass);
 
-                // Upload constants specific to the artifact; these
-                // include colors.
-                render_pass.set_bind_group(1, &self.artifact_bind_group.get(key).unwrap(), &[]);
+                // Artifacts left out by the settings filter stay loaded,
+                // so they show again as soon as the filter allows.  Those
+                // wholly out of the camera's view are not drawn either,
+                // which adds up when accumulating many frames.  Points
+                // just outside the edge, whose squares would reach in by
+                // a pixel or two, are lost.
+                let frustum = Frustum::of(pane.camera_uniform.view_proj());
+                let shown = self
+                    .draw_order(artifacts, pane.camera_uniform.view_position())
+                    .into_iter()
+                    .filter(|(key, _)| self.settings.shows(&key.artifact))
+                    .filter(|(key, artifact)| match artifact.bounds() {
+                        Some(bounds) => {
+                            frustum.intersects(&bounds.transformed(self.settings.transforms.get(&key.artifact)))
+                        }
+                        None => true,
+                    });
+                for (instance, artifact) in shown {
+                    let key = &instance.artifact;
+                    let mode = self.draw_mode(key, artifact);
+                    render_pass.set_pipeline(self.pipeline.get(&(mode, self.surface_format)).unwrap());
 
-                match artifact {
-                    Artifact::PointCloud(point_cloud) => {
-                        point_cloud.render(&mut render_pass);
-                    }
-                    Artifact::Wireframe(wireframe) => {
-                        wireframe.render(&mut render_pass);
+                    // Upload constants specific to the artifact; these
+                    // include colors.
+                    let uniform = (instance.clone(), artifact.kind());
+                    render_pass.set_bind_group(1, self.artifact_bind_group.get(&uniform).unwrap(), &[]);
+                    if let (RenderMode::Textured, Some(bind_group)) = (mode, &self.texture_bind_group) {
+                        render_pass.set_bind_group(2, bind_group, &[]);
                     }
-                    Artifact::Mesh(mesh) => {
-                        mesh.render(&mut render_pass);
+
+                    // The edges are drawn over the fill right away, with the
+                    // same bind groups.
+                    let edges = match self.edges && mode.is_filled() {
+                        true => self.pipeline.get(&(RenderMode::Edges, self.surface_format)),
+                        false => None,
+                    };
+                    match &mut self.profiler {
+                        Some(profiler) => profiler.time(&mut render_pass, key, |render_pass| {
+                            render_with_edges(artifact, mode, edges, render_pass)
+                        }),
+                        None => render_with_edges(artifact, mode, edges, &mut render_pass),
                     }
                 }
             }
+
+            // Decorations span the whole window, over all panes.
+            render_pass.set_viewport(0.0, 0.0, size.width as f32, size.height as f32, 0.0, 1.0);
+            self.overlay.render(&mut render_pass);
         }
 
-        // Lock the queue as late as possible.
-        let queue = QUEUE.get().unwrap();
+        let queue = &gpu.queue;
 
-        // Upload the camera viewpoint.
-        queue.write_buffer(
-            &self.camera_buffer,
-            0,
-            bytemuck::cast_slice(&[self.camera_uniform]),
-        );
+        // Upload the camera viewpoints.
+        for pane in &self.panes {
+            queue.write_buffer(
+                &pane.camera_buffer,
+                0,
+                bytemuck::cast_slice(&[pane.camera_uniform]),
+            );
+        }
+
+        if let Some(profiler) = &mut self.profiler {
+            profiler.resolve(&mut encoder);
+        }
+        if capture {
+            self.capture.copy(device, &mut encoder, texture);
+        }
 
         // Let 'er rip.  Render the frame.
         queue.submit([encoder.finish()]);
-        output.present();
+        if let Some(profiler) = &mut self.profiler {
+            profiler.submitted();
+        }
+    }
+
+    // Save the frame just rendered, then turn the camera for the next one.
+    // Exit after the last.
+    fn captured(&mut self, device: &wgpu::Device, event_loop: &ActiveEventLoop) {
+        let (orbit, frame) = self.orbit.as_mut().unwrap();
+        let path = orbit.path(*frame);
+        if let Err(err) = self.capture.save(device, &path) {
+            log::error!("{}: {}", path.display(), err);
+            event_loop.exit();
+            return;
+        }
+        log::debug!("Captured {}", path.display());
+
+        *frame += 1;
+        if *frame >= orbit.frames {
+            log::info!("Captured {} frames in {}", orbit.frames, orbit.out_dir.display());
+            event_loop.exit();
+            return;
+        }
+        let degrees = orbit.degrees;
+        self.panes[self.active].camera.orbit(degrees);
+        self.update_cameras();
+    }
+
+    // The depth buffer for frames of `size`, kept until the size changes.
+    fn depth_view(&mut self, device: &wgpu::Device, size: wgpu::Extent3d) -> wgpu::TextureView {
+        if self.depth.as_ref().map(|depth| depth.size()) != Some(size) {
+            self.depth = Some(device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("depth"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: pipeline::DEPTH_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            }));
+        }
+        self.depth.as_ref().unwrap().create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    // The render mode chosen by the user for this artifact name, if the
+    // artifact supports it, otherwise its natural mode, or for point
+    // clouds with --colormap, colored by height.
+    fn render_mode(&self, key: &String, artifact: &Artifact) -> RenderMode {
+        let modes = artifact.render_modes();
+        match self.render_mode.get(key) {
+            Some(mode) if modes.contains(mode) => *mode,
+            _ if self.colormap.is_some() && modes.contains(&RenderMode::Height) => RenderMode::Height,
+            _ => artifact.natural_mode(),
+        }
+    }
+
+    // How the artifact is drawn: as render_mode(), except that a mesh
+    // seen through (--alpha) is filled with blending.
+    fn draw_mode(&self, key: &String, artifact: &Artifact) -> RenderMode {
+        match self.render_mode(key, artifact) {
+            RenderMode::Fill if self.alpha(key) < 1.0 => RenderMode::SeeThrough,
+            mode => mode,
+        }
+    }
+
+    // The configured color of an artifact: from --color, else the
+    // settings file, else the palette.  None leaves the artifact type's
+    // own.
+    fn color(&self, key: &String) -> Option<[f32; 4]> {
+        match self.colors.get(key).or_else(|| self.settings.colors.get(key)) {
+            Some(color) => Some(*color),
+            None => self.palette.then(|| palette::color(key)),
+        }
+    }
+
+    // The opacity of an artifact: from --alpha, or else its configured
+    // color.  The palette is opaque.
+    fn alpha(&self, key: &String) -> f32 {
+        match self.alpha.get(key) {
+            Some(alpha) => *alpha,
+            None => self.color(key).map_or(1.0, |color| color[3]),
+        }
+    }
+
+    // Artifacts in the order they are drawn: first the --layer-order
+    // names, as listed, and then the rest.  Within a layer, translucent
+    // artifacts come last, to blend over everything opaque, and ties go
+    // by name and instance, so the picture is the same on every frame and
+    // every run.
+    //
+    // Blending is only right back to front, so translucent artifacts are
+    // drawn farthest from the eye first, by the centers of their bounds.
+    // That is as far as the order goes: the triangles within a mesh are
+    // not sorted, nor are artifacts that pass through each other.
+    fn draw_order<'a>(&self, artifacts: &'a HashMap<Key, Artifact>, eye: Point3<f32>) -> Vec<(&'a Key, &'a Artifact)> {
+        let shown = artifacts.iter().map(|(key, artifact)| {
+            // Colored points blend by their alpha, if they have one.
+            let translucent = match self.draw_mode(&key.artifact, artifact) {
+                RenderMode::Translucent => true,
+                RenderMode::Colored => matches!(artifact, Artifact::PointCloud(point_cloud) if point_cloud.is_translucent()),
+                _ => self.alpha(&key.artifact) < 1.0,
+            };
+            let distance = match (translucent, artifact.bounds()) {
+                (true, Some(bounds)) => {
+                    let transform = self.settings.transforms.get(&key.artifact);
+                    bounds.transformed(transform).center().distance2(eye)
+                }
+                _ => 0.0,
+            };
+            (key, translucent, distance, (key, artifact))
+        });
+        sort_for_drawing(&self.layer_order, shown)
+    }
+
+    // Move the focus to the next loaded artifact name, in sorted order.
+    fn cycle_focus(&mut self) {
+        let names: Vec<String> = self
+            .panes
+            .iter()
+            .flat_map(|pane| {
+                let artifacts = pane.artifacts.lock().unwrap();
+                artifacts
+                    .keys()
+                    .map(|key| key.artifact.clone())
+                    .collect::<Vec<_>>()
+            })
+            .sorted()
+            .dedup()
+            .collect();
+
+        self.focus = match &self.focus {
+            Some(focus) => names
+                .iter()
+                .skip_while(|name| *name != focus)
+                .nth(1)
+                .or(names.first())
+                .cloned(),
+            None => names.first().cloned(),
+        };
+
+        if let Some(focus) = &self.focus {
+            log::info!("Focus {}", focus);
+        }
+    }
+
+    // Step the focused artifact to its next supported render mode.
+    fn cycle_render_mode(&mut self) {
+        if self.focus.is_none() {
+            self.cycle_focus();
+        }
+        let focus = match &self.focus {
+            Some(focus) => focus.clone(),
+            None => return,
+        };
+
+        let mut mode = None;
+        for pane in &self.panes {
+            let artifacts = pane.artifacts.lock().unwrap();
+            if let Some((_, artifact)) = artifacts.iter().find(|(key, _)| key.artifact == focus) {
+                let modes = artifact.render_modes();
+                let current = self.render_mode(&focus, artifact);
+                let position = modes.iter().position(|mode| *mode == current).unwrap_or(0);
+                mode = Some(modes[(position + 1) % modes.len()]);
+                break;
+            }
+        }
+        let mode = match mode {
+            Some(mode) => mode,
+            None => return,
+        };
+
+        log::info!("Render {} as {:?}", focus, mode);
+        self.render_mode.insert(focus, mode);
+        self.request_redraw();
+    }
+
+    // Poll and redraw every frame when rendering continuously, otherwise
+    // sleep until an event arrives.
+    fn control_flow(&self) -> ControlFlow {
+        match (self.continuous && !self.hidden(), self.loading) {
+            (true, _) => ControlFlow::Poll,
+            // Wake up now and then to advance the progress bar.
+            (false, true) => ControlFlow::WaitUntil(Instant::now() + Duration::from_millis(100)),
+            (false, false) => ControlFlow::Wait,
+        }
+    }
+
+    fn toggle_edges(&mut self) {
+        self.edges = !self.edges;
+        log::info!("Edges over filled meshes {}", if self.edges { "on" } else { "off" });
+        self.request_redraw();
+    }
+
+    fn toggle_grid(&mut self) {
+        self.grid = !self.grid;
+        log::info!("Ground grid {}", if self.grid { "on" } else { "off" });
+        self.request_redraw();
+    }
+
+    fn toggle_axes(&mut self) {
+        self.axes = !self.axes;
+        log::info!("Coordinate axes {}", if self.axes { "on" } else { "off" });
+        self.request_redraw();
+    }
+
+    fn toggle_continuous(&mut self) {
+        self.continuous = !self.continuous;
+        log::info!("Continuous rendering {}", if self.continuous { "on" } else { "off" });
+        self.request_redraw();
     }
 
     fn reset_view(&mut self) {
-        self.camera = Camera::default();
-        self.projection = Projection::default(self.window.inner_size());
-        self.camera_uniform
-            .update_view_proj(&self.camera, &self.projection);
-        self.window.request_redraw();
+        for pane in self.panes.iter_mut() {
+            pane.camera = Camera::new(pane.camera.up());
+        }
+        // The field of view and clip planes are settings rather than part
+        // of the view, so they survive the reset.
+        let size = self.size();
+        self.projection
+            .resize(size.width / self.panes.len() as u32, size.height);
+        if self.projection.kind() == ProjectionKind::Orthographic {
+            self.projection.fit(self.panes[self.active].camera.target_distance());
+        }
+        self.update_cameras();
+        self.request_redraw();
+    }
+
+    // Linked panes follow the active one in update_cameras().
+    fn snap_view(&mut self, view: View) {
+        self.panes[self.active].camera.snap(view);
+        self.update_cameras();
+        self.request_redraw();
+    }
+
+    // Fit everything shown into view (the F key), looking the way the
+    // camera already does.  Linked panes frame what all of them show.
+    // The clip planes widen if the artifacts would not fit between them.
+    pub(crate) fn frame_all(&mut self) {
+        let panes = match self.linked {
+            true => &self.panes[..],
+            false => &self.panes[self.active..=self.active],
+        };
+        let bounds = panes
+            .iter()
+            .flat_map(|pane| {
+                let artifacts = pane.artifacts.lock().unwrap();
+                artifacts
+                    .iter()
+                    .filter(|(key, _)| self.settings.shows(&key.artifact))
+                    .filter_map(|(key, artifact)| {
+                        Some(artifact.bounds()?.transformed(self.settings.transforms.get(&key.artifact)))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .reduce(Bounds::union);
+        let bounds = match bounds {
+            Some(bounds) => bounds,
+            None => {
+                log::info!("Nothing to frame");
+                return;
+            }
+        };
+
+        let radius = bounds.radius();
+        let fovy = cgmath::Deg(self.projection.fovy()).into();
+        let distance = self.panes[self.active]
+            .camera
+            .frame(bounds.center(), radius, fovy, self.projection.aspect());
+        self.projection.include(distance - radius, distance + radius);
+        if self.projection.kind() == ProjectionKind::Orthographic {
+            self.projection.fit(distance);
+        }
+        self.update_cameras();
+        self.request_redraw();
+    }
+
+    fn zoom_fovy(&mut self, degrees: f32) {
+        self.projection.set_fovy(self.projection.fovy() + degrees);
+        log::info!("Field of view {:.0}°", self.projection.fovy());
+        self.update_cameras();
+        self.request_redraw();
+    }
+
+    fn toggle_projection(&mut self) {
+        let distance = self.panes[self.active].camera.target_distance();
+        let kind = self.projection.toggle_kind(distance);
+        log::info!("{:?} projection", kind);
+        self.update_cameras();
+        self.request_redraw();
+    }
+
+    fn save_camera(&self) {
+        let view = SavedView::of(&self.panes[self.active].camera, &self.projection);
+        match view.save(&self.camera_file) {
+            Ok(()) => log::info!("Saved the camera to {}", self.camera_file.display()),
+            Err(err) => log::error!("Cannot save the camera to {}: {}", self.camera_file.display(), err),
+        }
+    }
+
+    // A file that cannot be read leaves the view as it is.  Linked panes
+    // follow the active one in update_cameras().
+    fn load_camera(&mut self) {
+        let view = match SavedView::from_path(&self.camera_file) {
+            Ok(view) => view,
+            Err(err) => {
+                log::error!("Cannot restore the camera from {}: {}", self.camera_file.display(), err);
+                return;
+            }
+        };
+        view.apply(&mut self.panes[self.active].camera, &mut self.projection);
+        log::info!("Restored the camera from {}", self.camera_file.display());
+        self.update_cameras();
+        self.request_redraw();
+    }
+
+    // Points are sized in pixels at the --scale, if forced, in panes that
+    // may have just changed size.
+    fn update_points(&self) {
+        let size = self.size();
+        let points = PointUniform::new(
+            self.point_size * self.scale.unwrap_or(1.0),
+            size.width / self.panes.len() as u32,
+            size.height,
+        );
+        self.gpu.queue.write_buffer(&self.point_buffer, 0, bytemuck::cast_slice(&[points]));
+    }
+
+    fn resize_points(&mut self, pixels: f32) {
+        self.point_size = (self.point_size + pixels).clamp(MIN_POINT_SIZE, MAX_POINT_SIZE);
+        log::info!("Point size {} px", self.point_size);
+        self.update_points();
+        self.request_redraw();
+    }
+
+    fn cycle_sensitivity(&mut self) {
+        let sensitivity = self.camera_controller.cycle_sensitivity();
+        log::info!("Mouse sensitivity {}", sensitivity);
+    }
+
+    // Toggle between all panes following one camera, and each pane
+    // keeping its own.  Relinking snaps the others to the active pane.
+    fn toggle_linked(&mut self) {
+        self.linked = !self.linked;
+        log::info!("Cameras {}", if self.linked { "linked" } else { "independent" });
+        self.update_cameras();
+        self.request_redraw();
+    }
+
+    fn perform(&mut self, action: Action, event_loop: &ActiveEventLoop) {
+        match action {
+            Action::Quit => event_loop.exit(),
+            Action::ResetView => self.reset_view(),
+            Action::FrameAll => self.frame_all(),
+            Action::CycleFocus => self.cycle_focus(),
+            Action::CycleRenderMode => self.cycle_render_mode(),
+            Action::ToggleEdges => self.toggle_edges(),
+            Action::ToggleGrid => self.toggle_grid(),
+            Action::ToggleAxes => self.toggle_axes(),
+            Action::ToggleContinuous => self.toggle_continuous(),
+            Action::ToggleLinked => self.toggle_linked(),
+            Action::NarrowFov => self.zoom_fovy(-5.0),
+            Action::WidenFov => self.zoom_fovy(5.0),
+            Action::ToggleProjection => self.toggle_projection(),
+            Action::CycleSensitivity => self.cycle_sensitivity(),
+            Action::GrowPoints => self.resize_points(1.0),
+            Action::ShrinkPoints => self.resize_points(-1.0),
+            Action::ToggleLegend => {
+                self.show_legend = !self.show_legend;
+                self.request_redraw();
+            }
+            Action::ToggleHelp => {
+                self.show_help = !self.show_help;
+                self.request_redraw();
+            }
+            Action::SnapView(view) => self.snap_view(view),
+            Action::HistoryBack | Action::HistoryForward | Action::HistoryLive => self.step_history(action),
+            Action::Rescan => {
+                log::info!("Reloading the watched directory");
+                self.rescan.send_replace(());
+            }
+            Action::PlaybackPause => {
+                self.control.send_replace(playback::Control::Pause);
+            }
+            Action::PlaybackForward => {
+                self.control.send_replace(playback::Control::Forward);
+            }
+            Action::PlaybackBack => {
+                self.control.send_replace(playback::Control::Back);
+            }
+            Action::PlaybackRestart => {
+                self.control.send_replace(playback::Control::Restart);
+            }
+            Action::Screenshot => {
+                if !self.readable() {
+                    log::warn!("This GPU cannot read back the window, so screenshots are not possible");
+                    return;
+                }
+                self.screenshot = true;
+                self.request_redraw();
+            }
+            Action::SaveCamera => self.save_camera(),
+            Action::LoadCamera => self.load_camera(),
+        }
+    }
+
+    // Step through the active pane's history.  The replay task uploads
+    // the frame chosen, and the window redraws when it lands.
+    fn step_history(&mut self, action: Action) {
+        let history = match &self.panes[self.active].history {
+            Some(history) => history,
+            None => {
+                log::info!("No history is kept; see --history");
+                return;
+            }
+        };
+        match action {
+            Action::HistoryBack => {
+                if !history.back() {
+                    log::info!("No older frame in the history");
+                }
+            }
+            Action::HistoryForward => history.forward(),
+            _ => history.live(),
+        }
+        self.update_paused();
+        self.request_redraw();
+    }
+
+    fn update_paused(&self) {
+        let scrubbing = self
+            .panes
+            .iter()
+            .any(|pane| pane.history.as_ref().is_some_and(|history| history.shown().is_some()));
+        let paused = (self.pause_hidden && self.hidden()) || scrubbing;
+        self.paused
+            .send_if_modified(|old| std::mem::replace(old, paused) != paused);
+    }
+
+    // Colors and transforms live in the artifact bind groups; drop them
+    // all, to be rebuilt with the new settings on the next redraw.  Fog
+    // without a color of its own follows the background.
+    pub(crate) fn reconfigure(&mut self, settings: Settings) {
+        self.settings = settings;
+        self.gpu.queue.write_buffer(
+            &self.fog_buffer,
+            0,
+            bytemuck::cast_slice(&[FogUniform::new(self.fog.as_ref(), self.settings.background())]),
+        );
+        self.artifact_bind_group.clear();
+        self.artifact_uniform_buffer.clear();
+        self.request_redraw();
+    }
+
+    fn hidden(&self) -> bool {
+        self.minimized || self.occluded
+    }
+
+    // Nothing is drawn while the window is hidden, and injection pauses
+    // too if configured.  Catch up as soon as it shows again.
+    fn visibility_changed(&mut self) {
+        let hidden = self.hidden();
+        self.update_paused();
+        if !hidden {
+            self.request_redraw();
+        }
+    }
+
+    // Make the pane under the mouse the one the camera controls act on.
+    fn set_active(&mut self, x: f64) {
+        let width = self.size().width as f64 / self.panes.len() as f64;
+        self.active = ((x / width) as usize).min(self.panes.len() - 1);
     }
 }
 
 impl<'win> ApplicationHandler<InjectionEvent> for WindowState<'win> {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        event_loop.set_control_flow(ControlFlow::Wait);
+        event_loop.set_control_flow(self.control_flow());
+    }
+
+    // Control flow is re-applied on every pass, so toggling continuous
+    // rendering at runtime takes effect immediately.
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        // Keep redrawing while loading, and once more after, to clear
+        // the progress bar, and while a smoothed camera settles.
+        let loading = progress::fraction().is_some();
+        let settling = self.camera_controller.settling();
+        if !self.hidden() && (self.continuous || loading || self.loading || settling) {
+            self.request_redraw();
+        }
+        self.loading = loading;
+        event_loop.set_control_flow(self.control_flow());
     }
 
     fn user_event(&mut self, _event_loop: &ActiveEventLoop, event: InjectionEvent) {
         match event {
             InjectionEvent::Add(_key) => {
-                self.window.request_redraw();
+                self.request_redraw();
             }
             InjectionEvent::Remove(_key) => {
-                self.window.request_redraw();
+                self.request_redraw();
             }
+            InjectionEvent::Settings(settings) => self.reconfigure(*settings),
         }
     }
 
@@ -336,20 +1496,23 @@ impl<'win> ApplicationHandler<InjectionEvent> for WindowState<'win> {
         _device: DeviceId,
         event: DeviceEvent,
     ) {
-        match event {
-            DeviceEvent::MouseMotion { delta } => {
-                match self.control_state {
-                    ControlState::Inactive => return,
-                    ControlState::DragAngle => {
-                        self.camera_controller.process_mouse(delta.0, delta.1);
-                    }
+        if let DeviceEvent::MouseMotion { delta } = event {
+            match self.control_state {
+                ControlState::Inactive => return,
+                ControlState::DragAngle => {
+                    self.camera_controller.process_mouse(delta.0, delta.1);
+                }
+                // Drag the world along with the cursor: the target keeps
+                // under it, however far away it is.
+                ControlState::Pan => {
+                    let height = self.size().height.max(1) as f32;
+                    let camera = &mut self.panes[self.active].camera;
+                    let per_pixel = self.projection.view_height(camera.target_distance()) / height;
+                    camera.pan(-delta.0 as f32 * per_pixel, delta.1 as f32 * per_pixel);
                 }
-                self.camera_controller.update_camera(&mut self.camera);
-                self.camera_uniform
-                    .update_view_proj(&self.camera, &self.projection);
-                self.window.request_redraw();
             }
-            _ => {}
+            self.update_cameras();
+            self.request_redraw();
         }
     }
 
@@ -371,52 +1534,406 @@ impl<'win> ApplicationHandler<InjectionEvent> for WindowState<'win> {
                         ..
                     },
                 ..
-            } => match logical_key {
-                Key::Named(NamedKey::Escape) => {
-                    event_loop.exit();
-                }
-                Key::Named(NamedKey::Space) => {
-                    self.reset_view();
+            } => {
+                if let Some(action) = Action::from_key(&logical_key) {
+                    self.perform(action, event_loop);
                 }
-                _ => {}
-            },
+            }
             WindowEvent::Resized(size) => {
                 self.resize(size);
             }
-            WindowEvent::RedrawRequested => {
-                self.redraw();
+            // With a forced scale, the window keeps its size in pixels on
+            // every monitor.
+            WindowEvent::ScaleFactorChanged {
+                mut inner_size_writer,
+                ..
+            } if self.scale.is_some() => {
+                inner_size_writer
+                    .request_inner_size(self.size())
+                    .ok();
+            }
+            // Nothing is drawn while hidden; see visibility_changed().
+            WindowEvent::RedrawRequested if !self.hidden() => {
+                self.redraw(event_loop);
+            }
+            WindowEvent::Occluded(occluded) => {
+                self.occluded = occluded;
+                self.visibility_changed();
+            }
+            WindowEvent::Focused(true) => {
+                self.request_redraw();
             }
             WindowEvent::MouseInput {
-                button: MouseButton::Left,
+                button: button @ (MouseButton::Left | MouseButton::Middle),
                 state,
                 ..
             } => {
-                self.control_state = match state {
-                    ElementState::Pressed => ControlState::DragAngle,
-                    ElementState::Released => ControlState::Inactive,
+                self.control_state = match (state, button) {
+                    (ElementState::Pressed, MouseButton::Middle) => ControlState::Pan,
+                    (ElementState::Pressed, _) => ControlState::DragAngle,
+                    (ElementState::Released, _) => ControlState::Inactive,
+                }
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                // Keep acting on the same pane for the whole drag.
+                if let ControlState::Inactive = self.control_state {
+                    self.set_active(position.x);
                 }
             }
             WindowEvent::MouseWheel { delta, .. } => {
                 self.camera_controller.process_scroll(delta);
-                self.camera_controller.update_camera(&mut self.camera);
-                self.camera_uniform
-                    .update_view_proj(&self.camera, &self.projection);
-                self.window.request_redraw();
+                self.update_cameras();
+                self.request_redraw();
             }
             _ => {}
         }
     }
 }
 
-pub async fn run(artifacts: ArtifactsLock, event_loop: EventLoop<InjectionEvent>) {
+// A graphics API for wgpu to draw through (--backend).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Backend {
+    Vulkan,
+    Metal,
+    Dx12,
+    Gl,
+}
+
+impl Backend {
+    fn bits(self) -> wgpu::Backends {
+        match self {
+            Backend::Vulkan => wgpu::Backends::VULKAN,
+            Backend::Metal => wgpu::Backends::METAL,
+            Backend::Dx12 => wgpu::Backends::DX12,
+            Backend::Gl => wgpu::Backends::GL,
+        }
+    }
+}
+
+// An instance on the backends chosen with --backend, or else those in
+// WGPU_BACKEND, e.g. "gl", or else every one wgpu has on this platform.
+fn instance(chosen: &[Backend]) -> (wgpu::Instance, wgpu::Backends) {
+    let backends = match chosen.is_empty() {
+        true => wgpu::util::backend_bits_from_env().unwrap_or_default(),
+        false => chosen.iter().fold(wgpu::Backends::empty(), |bits, backend| bits | backend.bits()),
+    };
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends,
+        ..Default::default()
+    });
+    (instance, backends)
+}
+
+// Sort `items`, each given with its key, whether it is translucent, and
+// its squared distance from the eye, into the draw order described at
+// WindowState::draw_order().
+fn sort_for_drawing<'a, T>(layer_order: &[String], items: impl Iterator<Item = (&'a Key, bool, f32, T)>) -> Vec<T> {
+    items
+        .map(|(key, translucent, distance, item)| {
+            let layer = layer_order
+                .iter()
+                .position(|name| *name == key.artifact)
+                .unwrap_or(layer_order.len());
+            ((layer, translucent), distance, key, item)
+        })
+        .sorted_by(|(a, a_distance, a_key, _), (b, b_distance, b_key, _)| {
+            a.cmp(b)
+                .then(b_distance.total_cmp(a_distance))
+                .then_with(|| (&a_key.artifact, &a_key.instance).cmp(&(&b_key.artifact, &b_key.instance)))
+        })
+        .map(|(_, _, _, item)| item)
+        .collect()
+}
+
+// The hardware adapter and device if there are any, otherwise a software
+// fallback such as llvmpipe: slow, but enough for containers and CI
+// runners.  Profiling features are requested only if asked for and
+// supported.  Failing that, the error names the backends tried.
+async fn request_device(
+    instance: &wgpu::Instance,
+    backends: wgpu::Backends,
+    surface: Option<&wgpu::Surface<'_>>,
+    profile: bool,
+) -> Result<(wgpu::Adapter, wgpu::Device, wgpu::Queue), String> {
+    for force_fallback_adapter in [false, true] {
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: surface,
+                force_fallback_adapter,
+            })
+            .await;
+        let adapter = match adapter {
+            Some(adapter) => adapter,
+            None => {
+                log::warn!("No GPU adapter found (fallback: {})", force_fallback_adapter);
+                continue;
+            }
+        };
+
+        let required_features = match profile && adapter.features().contains(Profiler::FEATURES) {
+            true => Profiler::FEATURES,
+            false => wgpu::Features::empty(),
+        };
+        let device = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    required_features,
+                    ..Default::default()
+                },
+                None,
+            )
+            .await;
+
+        let info = adapter.get_info();
+        match device {
+            Ok((device, queue)) => {
+                log::info!("GPU adapter {} ({:?}, {:?})", info.name, info.device_type, info.backend);
+                return Ok((adapter, device, queue));
+            }
+            Err(err) => log::warn!("GPU adapter {} cannot open a device: {}", info.name, err),
+        }
+    }
+
+    let tried = clap::ValueEnum::value_variants()
+        .iter()
+        .filter(|backend: &&Backend| backends.contains(backend.bits()))
+        .map(|backend| clap::ValueEnum::to_possible_value(backend).unwrap().get_name().to_string())
+        .join(", ");
+    Err(format!(
+        "No usable GPU adapter, not even a software fallback, on the backends tried: {}.  \
+         Check that Vulkan, Metal or DX12 drivers are installed (in a container, Mesa \
+         provides llvmpipe), or try another backend with --backend gl or WGPU_BACKEND=gl.",
+        match tried.is_empty() {
+            true => "none",
+            false => &tried,
+        }
+    ))
+}
+
+// Bring up the GPU with no window to draw in (--headless).  This happens
+// before any injector starts, so that no artifact arrives to find no GPU.
+pub async fn start_offscreen_gpu(backends: &[Backend], profile: bool) -> Result<(), String> {
+    let (instance, backends) = instance(backends);
+    let (_, device, queue) = request_device(&instance, backends, None, profile).await?;
+    *GPU.write().unwrap() = Some(Arc::new(Gpu::new(device, queue)));
+    Ok(())
+}
+
+// The GPU for tests, brought up once for all of them.  False when there
+// is no adapter at all, for the tests to skip.
+#[cfg(test)]
+pub async fn start_test_gpu() -> bool {
+    static STARTED: tokio::sync::OnceCell<bool> = tokio::sync::OnceCell::const_new();
+    *STARTED
+        .get_or_init(|| async { start_offscreen_gpu(&[], false).await.is_ok() })
+        .await
+}
+
+// Window size at scale 1, when --scale, --width or --height sets it.
+const DEFAULT_SIZE: (u32, u32) = (1024, 768);
+
+// The window size asked for, in pixels, if any.  A forced scale sizes the
+// window in pixels, the same on every machine, rather than by the scale
+// factor of the OS.
+fn requested_size(config: &WorldviewConfig) -> Option<dpi::PhysicalSize<u32>> {
+    if config.scale.is_none() && config.width.is_none() && config.height.is_none() {
+        return None;
+    }
+    let (width, height) = DEFAULT_SIZE;
+    let scale = config.scale.unwrap_or(1.0);
+    Some(dpi::PhysicalSize::new(
+        (config.width.unwrap_or(width) as f32 * scale) as u32,
+        (config.height.unwrap_or(height) as f32 * scale) as u32,
+    ))
+}
+
+// Where the V key saves the view without --camera.
+const DEFAULT_CAMERA_FILE: &str = "camera.json";
+
+// Center the window on a monitor (--monitor).  Some platforms, Wayland
+// among them, leave window placement to the compositor, and ignore this.
+fn move_to_monitor(window: &Window, index: usize) {
+    let monitor = match window.available_monitors().nth(index) {
+        Some(monitor) => monitor,
+        None => {
+            log::warn!(
+                "No monitor {}; there are {}",
+                index,
+                window.available_monitors().count()
+            );
+            return;
+        }
+    };
+    let (position, size, outer) = (monitor.position(), monitor.size(), window.outer_size());
+    window.set_outer_position(dpi::PhysicalPosition::new(
+        position.x + (size.width as i32 - outer.width as i32).max(0) / 2,
+        position.y + (size.height as i32 - outer.height as i32).max(0) / 2,
+    ));
+    log::info!(
+        "Opened on monitor {} ({})",
+        index,
+        monitor.name().unwrap_or_else(|| "unnamed".to_string())
+    );
+}
+
+// Run the window, with a pane per set of artifacts, until it closes.  Then
+// `shutdown` must stop every other thread that may touch the GPU; only
+// after that are the window's GPU resources, the artifacts and finally the
+// device itself released.
+pub async fn run(
+    panes: Vec<(ArtifactsLock, Option<History>)>,
+    event_loop: EventLoop<InjectionEvent>,
+    config: &WorldviewConfig,
+    paused: watch::Sender<bool>,
+    rescan: watch::Sender<()>,
+    control: watch::Sender<playback::Control>,
+    shutdown: impl Future<Output = ()>,
+) {
+    let mut attributes = WindowAttributes::default();
+    if let Some(size) = requested_size(config) {
+        attributes = attributes.with_inner_size(size);
+    }
+    if let Some(title) = &config.title {
+        attributes = attributes.with_title(title);
+    }
+
     // Interoperability between winit, wgpu, and various platforms is
     // complicated and the API's are currently in rapid flux (as of July
     // 2024).  Step around this fight for now with a deprecated pattern.
     #[allow(deprecated)]
-    let window = event_loop
-        .create_window(WindowAttributes::default())
-        .unwrap();
+    let window = event_loop.create_window(attributes).unwrap();
+    if let Some(index) = config.monitor {
+        move_to_monitor(&window, index);
+    }
 
-    let mut app = WindowState::new(&window, artifacts).await;
+    // Without a GPU there is nothing to show, but the other threads still
+    // need a clean exit.
+    let mut app = match WindowState::new(&window, panes.clone(), config, paused, rescan, control).await {
+        Ok(app) => app,
+        Err(err) => {
+            log::error!("{}", err);
+            shutdown.await;
+            std::process::exit(1);
+        }
+    };
     event_loop.run_app(&mut app).unwrap();
+
+    shutdown.await;
+    release(app, panes);
+}
+
+// Once nothing else uses the GPU, wait for submitted work to finish
+// before the buffers it refers to go away.
+pub(crate) fn release(app: WindowState, panes: Vec<(ArtifactsLock, Option<History>)>) {
+    let gpu = GPU.write().unwrap().take().unwrap();
+    gpu.device.poll(wgpu::Maintain::Wait);
+    drop(app);
+    for (artifacts, _) in panes {
+        artifacts.lock().unwrap().clear();
+    }
+    pool::clear();
+    log::debug!("GPU released");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sequence::Accumulate, EventProxy, Instance, Sequencer};
+    use std::{fs::File, io::Cursor};
+
+    // Three points of one color, beside those of the instance before.
+    fn cloud(instance: u32, rgb: [u8; 3]) -> String {
+        let x = 2.0 * instance as f32;
+        let [r, g, b] = rgb;
+        format!(
+            "ply\nformat ascii 1.0\nelement vertex 3\n\
+             property float x\nproperty float y\nproperty float z\n\
+             property uchar red\nproperty uchar green\nproperty uchar blue\nend_header\n\
+             {x} 0 0 {r} {g} {b}\n{} 0 0 {r} {g} {b}\n{x} 1 0 {r} {g} {b}\n",
+            x + 1.0
+        )
+    }
+
+    // Draw point clouds, each given by name, instance and color, in one
+    // pane.  Returns the renderer and the RGB pixels drawn, or None
+    // without a GPU.
+    async fn render(clouds: &[(&str, u32, [u8; 3])]) -> Option<(WindowState<'static>, Vec<u8>)> {
+        if !start_test_gpu().await {
+            return None;
+        }
+        let config = WorldviewConfig::default();
+        let artifacts: ArtifactsLock = Arc::new(Mutex::new(HashMap::new()));
+        let (sender, _) = tokio::sync::mpsc::unbounded_channel();
+        let sequencer = Accumulate::new(artifacts.clone(), EventProxy::Headless(sender), &config);
+        for &(name, instance, rgb) in clouds {
+            let key = Key {
+                instance: Some(Instance::Number(instance)),
+                artifact: name.to_string(),
+            };
+            sequencer.add_reader(key, &mut Cursor::new(cloud(instance, rgb))).unwrap();
+        }
+
+        let (paused, _) = watch::channel(false);
+        let mut app = WindowState::offscreen(vec![(artifacts, None)], &config, paused).unwrap();
+        app.frame_all();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scene.png");
+        app.render_offscreen(&path).unwrap();
+
+        let mut reader = png::Decoder::new(File::open(&path).unwrap()).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+        Some((app, pixels))
+    }
+
+    fn shows(pixels: &[u8], rgb: [u8; 3]) -> bool {
+        pixels.chunks_exact(3).any(|pixel| pixel == rgb)
+    }
+
+    // Two instances of one point cloud, in different colors, each get
+    // uniforms of their own, and both show.
+    #[tokio::test]
+    async fn instances_have_their_own_uniforms() {
+        let Some((app, pixels)) = render(&[("cloud", 0, [255, 0, 0]), ("cloud", 1, [0, 255, 0])]).await else {
+            return;
+        };
+        assert_eq!(app.artifact_bind_group.len(), 2);
+        assert!(shows(&pixels, [255, 0, 0]));
+        assert!(shows(&pixels, [0, 255, 0]));
+    }
+
+    // Pipelines are cached by render mode and format, so two point clouds
+    // drawn alike share one.
+    #[tokio::test]
+    async fn point_clouds_share_a_pipeline() {
+        let Some((app, pixels)) = render(&[("left", 0, [255, 0, 0]), ("right", 1, [0, 255, 0])]).await else {
+            return;
+        };
+        let modes: Vec<_> = app.pipeline.keys().collect();
+        assert_eq!(modes, [&(RenderMode::Colored, app.surface_format)]);
+        assert!(shows(&pixels, [255, 0, 0]));
+        assert!(shows(&pixels, [0, 255, 0]));
+    }
+
+    // --layer-order names come first, as listed, then the rest by name;
+    // within a layer, opaque before translucent, and translucent from
+    // the farthest.
+    #[test]
+    fn draw_order_follows_layer_order() {
+        let key = |name: &str| Key {
+            instance: None,
+            artifact: name.to_string(),
+        };
+        let items = [
+            (key("a"), false, 0.0),
+            (key("b"), true, 1.0),
+            (key("c"), false, 0.0),
+            (key("d"), false, 0.0),
+            (key("e"), true, 4.0),
+        ];
+        let layer_order = ["c".to_string(), "a".to_string()];
+        let items = items.iter().map(|(key, translucent, distance)| (key, *translucent, *distance, key.artifact.as_str()));
+        assert_eq!(sort_for_drawing(&layer_order, items), ["c", "a", "d", "e", "b"]);
+    }
 }